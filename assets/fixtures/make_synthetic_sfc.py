@@ -0,0 +1,397 @@
+#!/usr/bin/env python3
+"""Assembles a synthetic world byte by byte from the Creatures 2 world layout.
+
+Usage: python3 assets/fixtures/make_synthetic_sfc.py assets/fixtures/synthetic.sfc
+
+Deliberately independent of the Rust ArchiveWriter, so that the round trip
+tests compare the writer against bytes it didn't produce.
+"""
+import struct
+import sys
+
+
+class Archive:
+    def __init__(self):
+        self.b = bytearray()
+        self.count = 0  # last index used in the MFC archive map
+        self.classes = {}
+
+    def u8(self, v): self.b += struct.pack('<B', v)
+    def u16(self, v): self.b += struct.pack('<H', v)
+    def u32(self, v): self.b += struct.pack('<I', v)
+    def i32(self, v): self.b += struct.pack('<i', v)
+    def raw(self, v): self.b += v
+
+    def fixed(self, text, size):
+        data = text.encode('latin-1')
+        assert len(data) <= size
+        self.raw(data + b'\0' * (size - len(data)))
+
+    def cstring(self, text):
+        data = text.encode('latin-1')
+        assert len(data) < 255, text
+        self.u8(len(data))
+        self.raw(data)
+
+    def new_object(self, class_name, schema=0):
+        """Header or class tag for a new object, returning its map index."""
+        if class_name in self.classes:
+            self.u16(0x8000 | self.classes[class_name])
+        else:
+            self.u16(0xffff)
+            self.u16(schema)
+            self.u16(len(class_name))
+            self.raw(class_name.encode())
+            self.count += 1
+            self.classes[class_name] = self.count
+        self.count += 1
+        return self.count
+
+    def reference(self, index):
+        self.u16(index)
+
+
+def gallery(a, name, sizes, users):
+    index = a.new_object('CGallery')
+    a.u32(len(sizes))
+    a.fixed(name, 4)
+    a.u32(0)
+    a.u32(users)
+    offset = 8 + 8 * len(sizes)
+    for width, height in sizes:
+        a.u16(a.classes['CGallery'])
+        a.u8(0)
+        a.u32(width)
+        a.u32(height)
+        a.u32(offset)
+        offset += width * height * 2
+    return index
+
+
+def rect(a, left, top, right, bottom):
+    for v in (left, top, right, bottom):
+        a.u32(v)
+
+
+def classifier(a, family, genus, species, event=0):
+    a.u16(family | genus << 8)
+    a.u32(species | event << 16)
+
+
+def object_base(a, cls, ident, gallery_of, scripts, attr=0, movement=0,
+                limit=(0, 0, 8352, 2400), vars_=(), room=0):
+    """Fields shared by Object and SimpleObject, up to the scripts."""
+    classifier(a, *cls)
+    a.i32(ident)
+    a.u8(movement)
+    a.u8(attr)
+    rect(a, *limit)
+    a.u16(0)  # vehicle
+    a.u8(0)  # active
+    gallery_of(a)
+    a.u32(10)  # timer rate
+    a.u32(3)  # timer
+    a.u16(0)  # object pointer
+    a.u32(0)  # active sound
+    values = list(vars_) + [0] * (100 - len(vars_))
+    for v in values:
+        a.u32(v)
+    a.u8(6)  # min door size
+    a.i32(300)  # range
+    a.i32(-1)  # falling object
+    a.i32(2)  # gravity
+    a.i32(0)
+    a.i32(0)  # velocity
+    a.i32(30)  # restitution
+    a.i32(10)  # aerodynamic
+    a.u16(room)
+    a.u32(0)  # wall last collided
+    a.u8(0)  # threat
+    a.u8(1)  # running
+    a.u32(len(scripts))
+    for (event, source) in scripts:
+        classifier(a, cls[0], cls[1], cls[2], event)
+        a.cstring(source)
+
+
+def entity(a, image, base, plane, x, y, anim=None):
+    a.new_object('Entity')
+    a.u16(a.classes['CGallery'])
+    a.u8(image)
+    a.u8(base)
+    a.i32(plane)
+    a.i32(x)
+    a.i32(y)
+    if anim is None:
+        a.u8(0)
+    else:
+        a.u8(1)
+        a.fixed(anim, 99)
+
+
+def simple_body(a, cls, ident, gallery_of, scripts, x, y, plane, image=0,
+                attr=0, anim=''):
+    object_base(a, cls, ident, gallery_of, scripts, attr=attr)
+    entity(a, image, 0, plane, x, y, anim or None)
+    a.u16(a.classes['CGallery'])
+    a.u8(image)
+    a.u8(0)
+    a.i32(plane)
+    a.i32(x)
+    a.i32(y)
+    a.u8(1 if anim else 0)
+    a.fixed(anim, 99)
+    a.i32(plane)  # normal plane
+    a.raw(bytes([0, 1, 255]))  # click actions
+    a.u8(1)  # touch
+    for points in ([(5, 5)], [(10, 20), (12, 20)]):
+        a.u16(len(points))
+        for x_, y_ in points:
+            a.i32(x_)
+            a.i32(y_)
+
+
+def compound_body(a, cls, ident, gallery_of, scripts, parts, hotspots):
+    object_base(a, cls, ident, gallery_of, scripts, attr=0b110)
+    a.u32(len(parts))
+    for part in parts:
+        if part is None:
+            a.reference(0)
+            continue
+        image, x, y, plane, rel_x, rel_y = part
+        entity(a, image, 0, plane, x, y)
+        a.i32(rel_x)
+        a.i32(rel_y)
+    for hotspot in hotspots:
+        for v in hotspot:
+            a.i32(v)
+    for i in range(6):
+        a.i32(i if i < 3 else -1)
+    for i in range(6):
+        a.u16(1000 + i)
+    for i in range(6):
+        a.u8(0xff if i < 3 else 0)
+
+
+def new_gallery(name, sizes, users, keep):
+    def write(a):
+        keep[name] = gallery(a, name, sizes, users)
+    return write
+
+
+def shared_gallery(name, keep):
+    def write(a):
+        a.reference(keep[name])
+    return write
+
+
+def room(a, room_id, rect_, doors, room_type, music, temp, bacteria, points,
+         visited=0, drop=0):
+    a.new_object('CRoom')
+    a.u32(room_id)
+    a.u16(a.classes['MapData'])
+    rect(a, *rect_)
+    for side in range(4):
+        neighbours = doors.get(side, [])
+        a.u16(len(neighbours))
+        for neighbour, amount_open in neighbours:
+            a.new_object('CDoor')
+            a.u8(amount_open)
+            a.u32(neighbour)
+    a.i32(room_type)
+    a.u8(100)  # floor value
+    a.u8(40)  # inorganic nutrient
+    a.u8(60)  # organic nutrient
+    a.u8(temp)
+    a.i32(-1)
+    a.u8(128)  # pressure
+    a.i32(-1)
+    a.i32(3)
+    a.i32(0)  # wind
+    a.u8(200)  # light
+    a.i32(-1)
+    a.u8(0)  # radiation
+    a.i32(-1)
+    for i in range(100):
+        state, antigen, fatal, infect, toxins = bacteria.get(i, (0, 0, 0, 0, (0, 0, 0, 0)))
+        a.u8(state)
+        a.u8(antigen)
+        a.u8(fatal)
+        a.u8(infect)
+        a.raw(bytes(toxins))
+    a.u16(len(points))
+    for x, y in points:
+        a.i32(x)
+        a.i32(y)
+    a.u32(visited)
+    a.cstring(music)
+    a.u32(drop)
+
+
+BALL_SCRIPTS = [
+    (9, 'inst,doif posl lt 100,setv xvec 5,elif posr gt 8000,setv xvec -5,'
+        'else,addv obv0 1,endi,doif obv0 ge 10 and actv eq 0,setv obv0 0,endi,endm'),
+    (1, 'snde drum,reps 3,anim [012R],wait 5,repe,setv var0 0,loop,addv var0 1,'
+        'mesg writ ownr 0,untl var0 ge 4,gsub down,stop,subr down,setv actv 0,retn,endm'),
+    (4, 'enum 2 5 0,mesg writ targ 1,next,loop,wait 10,doif touc ownr pntr eq 1,'
+        'setv actv 1,endi,ever,endm'),
+]
+
+COMPOUND_SCRIPTS = [
+    (1, 'part 1,anim [0123R],part 0,pose 2,setv actv 1,endm'),
+    (2, 'esee 4 0 0,doif dead eq 0,chem 3 10,endi,next,setv actv 0,endm'),
+]
+
+VEHICLE_SCRIPTS = [
+    (9, 'inst,cabn 10 20 120 80,setv xvec 3,doif bump ne 0,negv var0,'
+        'setv xvec var0,endi,endm'),
+]
+
+LIFT_SCRIPTS = [
+    (9, 'doif obv0 eq 1,setv yvec -2,elif obv0 eq 2,setv yvec 2,else,'
+        'setv yvec 0,endi,endm'),
+]
+
+BLACKBOARD_SCRIPTS = [
+    (1, 'bbd: show 1,bbd: word 0 1000 [hand],rndv var1 0 47,bbd: emit var1,endm'),
+]
+
+BUTTON_SCRIPTS = [
+    (1, 'targ ownr,doif obv0 eq 0,setv obv0 1,else,setv obv0 0,endi,endm'),
+]
+
+SCENERY_SCRIPTS = [
+    (9, 'anim [01234R],wait 50,endm'),
+]
+
+
+def world():
+    a = Archive()
+    galleries = {}
+
+    a.new_object('MapData')
+    a.u32(1)  # wraps
+    a.u32(2)  # time of day
+    a.u32(17)  # day in year
+    a.u32(3)  # year
+    gallery(a, 'back', [(144, 150)] * (58 * 16), 1)
+
+    rooms = [
+        (1, (0, 600, 800, 1200), {2: [(2, 255)]}, 1, 'Jungle', 180,
+         {0: (0x82, 3, 40, 90, (1, 2, 3, 4)), 7: (1, 9, 10, 20, (0, 0, 5, 0))},
+         [(0, 1100), (400, 1150), (800, 1120)], 1, 1),
+        (2, (800, 600, 1600, 1200), {0: [(1, 255)], 2: [(3, 128)]}, 0, 'Mound',
+         150, {}, [(800, 1120), (1600, 1120)], 0, 0),
+        (3, (1600, 700, 2400, 1200), {0: [(2, 128)], 3: [(4, 200)]}, 2, 'Ocean',
+         90, {3: (1, 1, 1, 1, (9, 9, 9, 9))}, [], 0, 2),
+        (4, (1600, 1200, 2400, 1500), {1: [(3, 200)]}, 3, 'Desert', 250, {},
+         [(1600, 1490), (2400, 1490)], 1, 0),
+    ]
+    a.u32(len(rooms))
+    for args in rooms:
+        room(a, *args[:8], visited=args[8], drop=args[9])
+
+    objects = []
+
+    def ball(a):
+        a.new_object('Object')
+        object_base(a, (2, 5, 1), 101, new_gallery('toys', [(40, 40)] * 6, 3, galleries),
+                    BALL_SCRIPTS, attr=0b11000111, vars_=[7, 0, 3], room=1)
+    objects.append(ball)
+
+    def toy(a):
+        a.new_object('SimpleObject')
+        simple_body(a, (2, 5, 2), 102, shared_gallery('toys', galleries),
+                    [(1, 'snde drum,setv actv 0,endm')], 300, 1050, 1500, image=3,
+                    attr=0b11000111)
+    objects.append(toy)
+
+    def machine(a):
+        a.new_object('CompoundObject')
+        compound_body(a, (2, 8, 1), 103,
+                      new_gallery('mach', [(120, 90), (30, 30), (30, 30)], 2, galleries),
+                      COMPOUND_SCRIPTS,
+                      [(0, 900, 1000, 3000, 0, 0), None, (1, 960, 1020, 3001, 60, 20)],
+                      [(0, 0, 60, 90), (60, 0, 120, 90), (-1, -1, -1, -1),
+                       (-1, -1, -1, -1), (-1, -1, -1, -1), (-1, -1, -1, -1)])
+    objects.append(machine)
+
+    def submarine(a):
+        a.new_object('Vehicle')
+        compound_body(a, (3, 1, 1), 104, shared_gallery('mach', galleries),
+                      VEHICLE_SCRIPTS,
+                      [(0, 1700, 900, 4000, 0, 0), (2, 1740, 930, 4001, 40, 30)],
+                      [(0, 0, 120, 90)] + [(-1, -1, -1, -1)] * 5)
+        rect(a, 10, 20, 120, 80)
+        a.i32(-3)
+        a.i32(0)
+        a.u8(0)
+    objects.append(submarine)
+
+    lift = {}
+
+    def elevator(a):
+        lift['index'] = a.new_object('Lift')
+        compound_body(a, (3, 2, 1), 105,
+                      new_gallery('lift', [(100, 150), (20, 20)], 3, galleries),
+                      LIFT_SCRIPTS, [(0, 1000, 700, 2500, 0, 0)],
+                      [(0, 0, 100, 150)] + [(-1, -1, -1, -1)] * 5)
+        rect(a, 5, 5, 95, 145)
+        a.i32(0)
+        a.i32(2)
+        a.u8(1)
+        a.u32(2)  # buttons
+        a.u32(0)  # current button
+        a.raw(bytes([0, 1, 0, 0, 255]))
+        for y in (700, 1050, 0, 0, 0, 0, 0, 0):
+            a.u32(y)
+    objects.append(elevator)
+
+    def button(lift_id, y):
+        def write(a):
+            a.new_object('CallButton')
+            simple_body(a, (2, 22, lift_id), 110 + lift_id, shared_gallery('lift', galleries),
+                        BUTTON_SCRIPTS, 1110, y, 2600, image=1, attr=0b110)
+            a.reference(lift['index'])
+            a.u8(lift_id)
+        return write
+    objects.append(button(0, 690))
+    objects.append(button(1, 1040))
+
+    def blackboard(a):
+        a.new_object('Blackboard')
+        compound_body(a, (2, 20, 1), 106,
+                      new_gallery('bbrd', [(200, 150)], 1, galleries),
+                      BLACKBOARD_SCRIPTS, [(0, 1200, 650, 1000, 0, 0)],
+                      [(0, 0, 200, 150)] + [(-1, -1, -1, -1)] * 5)
+        a.u32(0x10)
+        a.u32(0xff)
+        a.u32(0x08)
+        a.u8(4)
+        a.u8(6)
+        words = ['ball', 'hand', 'lift', 'food', 'push', 'pull']
+        for i in range(48):
+            word = words[i] if i < len(words) else ''
+            a.u32(1000 + i if word else 0)
+            a.fixed(word, 11)
+    objects.append(blackboard)
+
+    a.u32(len(objects))
+    for write in objects:
+        write(a)
+
+    scenery = [(0, 200, 700), (1, 1000, 650), (2, 1800, 1250)]
+    a.u32(len(scenery))
+    for i, (image, x, y) in enumerate(scenery):
+        gallery_of = (new_gallery('scen', [(300, 200)] * 5, 3, galleries) if i == 0
+                      else shared_gallery('scen', galleries))
+        a.new_object('SimpleObject')
+        simple_body(a, (1, 1, i + 1), 200 + i, gallery_of, SCENERY_SCRIPTS if i == 0 else [],
+                    x, y, 500, image=image, anim='01234R' if i == 0 else '')
+
+    return bytes(a.b)
+
+
+if __name__ == '__main__':
+    with open(sys.argv[1], 'wb') as f:
+        f.write(world())
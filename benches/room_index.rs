@@ -0,0 +1,96 @@
+//! Compares finding the room at a point with the `RoomIndex` against looking
+//! through every room, as `room_number` used to.
+//!
+//! Run with `cargo bench --bench room_index`.
+
+use std::{hint::black_box, time::Instant};
+
+use bevy::prelude::*;
+use cl::{
+    components::{room::Room, room_index::RoomIndex},
+    wrap::WrappedSpace,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const ROOM_WIDTH: f32 = 400.0;
+const ROOM_HEIGHT: f32 = 300.0;
+const ROWS: usize = 12;
+const QUERIES: usize = 200_000;
+
+/// The old lookup: the first room whose rect contains the point.
+fn linear_room_at(rooms: &[Room], point: Vec2) -> Option<u32> {
+    rooms
+        .iter()
+        .find(|room| WrappedSpace::WORLD.contains(room.rect, point))
+        .map(|room| room.room_id)
+}
+
+fn time(name: &str, mut lookup: impl FnMut(Vec2) -> Option<u32>, points: &[Vec2]) -> f64 {
+    let start = Instant::now();
+    let found = points
+        .iter()
+        .filter(|&&point| black_box(lookup(black_box(point))).is_some())
+        .count();
+    let per_query = start.elapsed().as_nanos() as f64 / points.len() as f64;
+
+    println!(
+        "{:>8}: {:>8.1} ns per query, {} found",
+        name, per_query, found
+    );
+    per_query
+}
+
+fn main() {
+    // A world tiled with rooms, a few hundred like the worlds C2 ships with.
+    let width = WrappedSpace::WORLD.width();
+    let columns = (width / ROOM_WIDTH) as usize;
+    let rooms: Vec<Room> = (0..ROWS)
+        .flat_map(|row| (0..columns).map(move |column| (row, column)))
+        .enumerate()
+        .map(|(room_id, (row, column))| {
+            let (x, y) = (column as f32 * ROOM_WIDTH, -(row as f32) * ROOM_HEIGHT);
+            Room {
+                room_id: room_id as u32,
+                rect: Rect::new(x, y, x + ROOM_WIDTH, y - ROOM_HEIGHT),
+                ..Default::default()
+            }
+        })
+        .collect();
+
+    let index = RoomIndex::new(
+        WrappedSpace::WORLD,
+        rooms
+            .iter()
+            .enumerate()
+            .map(|(entity, room)| (Entity::from_raw(entity as u32), room)),
+    );
+
+    let mut rng = StdRng::seed_from_u64(0);
+    let height = ROWS as f32 * ROOM_HEIGHT;
+    let points: Vec<Vec2> = (0..QUERIES)
+        .map(|_| {
+            Vec2::new(
+                rng.gen_range(0.0..width),
+                rng.gen_range(-height * 1.1..height * 0.1),
+            )
+        })
+        .collect();
+
+    for &point in points.iter().take(1000) {
+        assert_eq!(
+            index.room_at(point).map(|room| room.room_id),
+            linear_room_at(&rooms, point),
+            "the index and the scan disagree at {}",
+            point
+        );
+    }
+
+    println!("{} rooms, {} queries", rooms.len(), points.len());
+    let linear = time("linear", |point| linear_room_at(&rooms, point), &points);
+    let indexed = time(
+        "index",
+        |point| index.room_at(point).map(|room| room.room_id),
+        &points,
+    );
+    println!("the index is {:.1}x faster", linear / indexed);
+}
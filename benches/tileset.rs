@@ -0,0 +1,154 @@
+//! Compares frame times of the pooled, chunked background against searching
+//! every tile entity for every visible tile each frame, as
+//! `render_background_wrapped` used to.
+//!
+//! Run with `cargo bench --bench tileset`.
+
+use std::{hint::black_box, time::Instant};
+
+use bevy::prelude::*;
+use cl::{
+    display::tileset::{
+        load_tile_atlas, render_background_tiles, BackgroundTiles, RenderTile, TileAtlas, TilePool,
+        TileView,
+    },
+    geometry::WorldGeometry,
+};
+
+const CAMERAS: usize = 3;
+const FRAMES: usize = 600;
+/// How far each camera scrolls a frame.
+const PAN: f32 = 7.0;
+
+fn pan_views(mut views: Query<&mut TileView>) {
+    for mut view in views.iter_mut() {
+        view.viewport.min.x += PAN;
+        view.viewport.max.x += PAN;
+    }
+}
+
+/// The old renderer: for each tile in view, load its image by name and look
+/// through every tile entity for the one showing it.
+fn searched_tiles(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    geometry: Res<WorldGeometry>,
+    views: Query<(Entity, &TileView)>,
+    mut tiles: Query<(&mut Transform, &RenderTile)>,
+) {
+    for (camera, view) in views.iter() {
+        let margin = view.viewport.inflate(200.0);
+        let margin = Rect::from_corners(
+            margin.min / geometry.tile_size,
+            margin.max / geometry.tile_size,
+        )
+        .as_irect();
+
+        for y in margin.min.y..=margin.max.y {
+            for x in margin.min.x..=margin.max.x {
+                let Some(column) = geometry.tile_column(x) else {
+                    continue;
+                };
+                let Ok(row) = u32::try_from(-y) else {
+                    continue;
+                };
+                if row >= geometry.rows {
+                    continue;
+                }
+
+                let tile = RenderTile {
+                    camera,
+                    index: geometry.tile_index(column, row),
+                    side: x.div_euclid(geometry.columns as i32),
+                };
+                let image: Handle<Image> =
+                    asset_server.load(format!("sprites/Back.s16#{}", tile.index));
+                let translation = Vec3::new(
+                    x as f32 * geometry.tile_size.x,
+                    y as f32 * geometry.tile_size.y,
+                    -0.01,
+                );
+
+                let found: Vec<_> = tiles
+                    .iter_mut()
+                    .filter(|(_, rendered)| **rendered == tile)
+                    .collect();
+
+                if found.is_empty() {
+                    commands.spawn((
+                        Sprite {
+                            image,
+                            ..Default::default()
+                        },
+                        Transform::from_translation(translation),
+                        tile,
+                    ));
+                } else {
+                    for (mut transform, _) in found {
+                        transform.translation = translation;
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn app<M>(renderer: impl IntoSystemConfigs<M>) -> App {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        AssetPlugin::default(),
+        HierarchyPlugin,
+        TransformPlugin,
+    ));
+    app.init_asset::<Image>();
+    app.init_asset::<TextureAtlasLayout>();
+    app.init_resource::<WorldGeometry>();
+    app.init_resource::<TileAtlas>();
+    app.init_resource::<TilePool>();
+    app.add_systems(Update, (pan_views, renderer).chain());
+
+    app.world_mut().spawn(BackgroundTiles);
+    for camera in 0..CAMERAS {
+        let left = camera as f32 * 2000.0;
+        app.world_mut().spawn(TileView {
+            viewport: Rect::new(left, -200.0, left + 1280.0, -920.0),
+        });
+    }
+
+    app
+}
+
+fn time(name: &str, mut app: App) -> f64 {
+    // The first frame spawns every tile either way.
+    app.update();
+
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        app.update();
+    }
+    let per_frame = start.elapsed().as_secs_f64() * 1000.0 / FRAMES as f64;
+
+    let tiles = app
+        .world_mut()
+        .query::<&RenderTile>()
+        .iter(app.world())
+        .count();
+    println!(
+        "{:>8}: {:>8.3} ms per frame, {} tile entities",
+        name,
+        black_box(per_frame),
+        tiles
+    );
+    per_frame
+}
+
+fn main() {
+    println!("{} cameras, {} frames", CAMERAS, FRAMES);
+    let searched = time("searched", app(searched_tiles));
+    let pooled = time(
+        "pooled",
+        app((load_tile_atlas, render_background_tiles).chain()),
+    );
+    println!("the pooled renderer is {:.1}x faster", searched / pooled);
+}
@@ -0,0 +1,332 @@
+//! Converts S16 and C16 sprite files to and from a folder of PNGs.
+//!
+//! ```text
+//! s16 unpack sprites/Back.s16 back/
+//! s16 pack back/ sprites/Back.s16
+//! ```
+//!
+//! Files ending in `.c16` are read and written run-length encoded.
+//!
+//! Unpacking writes a PNG per frame and a `manifest.txt` giving the pixel
+//! format and the frames in order, which packing reads back. Edit the PNGs,
+//! or add and reorder frames in the manifest, to make new sprites.
+
+use std::{
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use cl::formats::s16::codec::{S16Error, S16File, S16Format, S16Frame};
+use image::RgbaImage;
+
+const MANIFEST: &str = "manifest.txt";
+
+const USAGE: &str = "usage:
+    s16 unpack <file.s16|file.c16> <folder>
+    s16 pack <folder> <file.s16|file.c16>";
+
+#[derive(Debug)]
+enum ToolError {
+    Usage,
+    Io(PathBuf, std::io::Error),
+    Image(PathBuf, image::ImageError),
+    S16(PathBuf, S16Error),
+    TooLarge(PathBuf),
+    Manifest(usize, String),
+}
+
+impl Display for ToolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ToolError::Usage => write!(f, "{}", USAGE),
+            ToolError::Io(path, err) => write!(f, "{}: {}", path.display(), err),
+            ToolError::Image(path, err) => write!(f, "{}: {}", path.display(), err),
+            ToolError::S16(path, err) => write!(f, "{}: {}", path.display(), err),
+            ToolError::TooLarge(path) => {
+                write!(
+                    f,
+                    "{}: frames can be at most 65535 wide and high",
+                    path.display()
+                )
+            }
+            ToolError::Manifest(line, message) => {
+                write!(f, "{} line {}: {}", MANIFEST, line, message)
+            }
+        }
+    }
+}
+
+/// The pixel format and frame files of an unpacked sprite.
+#[derive(Debug, PartialEq)]
+struct Manifest {
+    format: S16Format,
+    frames: Vec<String>,
+}
+
+impl Manifest {
+    fn parse(text: &str) -> Result<Self, ToolError> {
+        let mut format = None;
+        let mut frames = Vec::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            match line.strip_prefix("format ") {
+                Some(name) => {
+                    format = Some(match name.trim() {
+                        "rgb555" => S16Format::Rgb555,
+                        "rgb565" => S16Format::Rgb565,
+                        other => {
+                            return Err(ToolError::Manifest(
+                                number + 1,
+                                format!("unknown format {}", other),
+                            ))
+                        }
+                    })
+                }
+                None => frames.push(line.to_string()),
+            }
+        }
+
+        Ok(Self {
+            format: format.unwrap_or_default(),
+            frames,
+        })
+    }
+
+    fn write(&self) -> String {
+        let format = match self.format {
+            S16Format::Rgb555 => "rgb555",
+            S16Format::Rgb565 => "rgb565",
+        };
+
+        let mut text = format!("# frames are packed in this order\nformat {}\n", format);
+        for frame in &self.frames {
+            text.push_str(frame);
+            text.push('\n');
+        }
+        text
+    }
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, ToolError> {
+    fs::read(path).map_err(|err| ToolError::Io(path.to_path_buf(), err))
+}
+
+fn write(path: &Path, bytes: impl AsRef<[u8]>) -> Result<(), ToolError> {
+    fs::write(path, bytes).map_err(|err| ToolError::Io(path.to_path_buf(), err))
+}
+
+fn is_c16(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("c16"))
+}
+
+fn unpack(sprite: &Path, folder: &Path) -> Result<usize, ToolError> {
+    let bytes = read(sprite)?;
+    let file = if is_c16(sprite) {
+        S16File::decode_c16(&bytes)
+    } else {
+        S16File::decode(&bytes)
+    }
+    .map_err(|err| ToolError::S16(sprite.to_path_buf(), err))?;
+
+    fs::create_dir_all(folder).map_err(|err| ToolError::Io(folder.to_path_buf(), err))?;
+
+    let mut manifest = Manifest {
+        format: file.format,
+        frames: Vec::new(),
+    };
+
+    for (index, frame) in file.frames.iter().enumerate() {
+        let name = format!("{}.png", index);
+        let path = folder.join(&name);
+
+        // A PNG can't be empty, so empty frames are only recorded in the
+        // manifest, with their size.
+        let image = RgbaImage::from_raw(
+            frame.width as u32,
+            frame.height as u32,
+            frame.to_rgba8(file.format),
+        )
+        .filter(|image| !image.is_empty());
+
+        match image {
+            Some(image) => image
+                .save(&path)
+                .map_err(|err| ToolError::Image(path, err))?,
+            None => {
+                manifest
+                    .frames
+                    .push(format!("empty {}x{}", frame.width, frame.height));
+                continue;
+            }
+        }
+
+        manifest.frames.push(name);
+    }
+
+    write(&folder.join(MANIFEST), manifest.write())?;
+    Ok(file.frames.len())
+}
+
+fn pack(folder: &Path, sprite: &Path) -> Result<usize, ToolError> {
+    let manifest_path = folder.join(MANIFEST);
+    let text = String::from_utf8_lossy(&read(&manifest_path)?).into_owned();
+    let manifest = Manifest::parse(&text)?;
+
+    let frames = manifest
+        .frames
+        .iter()
+        .map(|name| {
+            if let Some(size) = name.strip_prefix("empty ") {
+                let (width, height) = size
+                    .split_once('x')
+                    .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+                    .unwrap_or((0, 0));
+                return Ok(S16Frame {
+                    width,
+                    height,
+                    pixels: vec![0; width as usize * height as usize],
+                });
+            }
+
+            let path = folder.join(name);
+            let image = image::open(&path)
+                .map_err(|err| ToolError::Image(path.clone(), err))?
+                .into_rgba8();
+            let (Ok(width), Ok(height)) =
+                (u16::try_from(image.width()), u16::try_from(image.height()))
+            else {
+                return Err(ToolError::TooLarge(path));
+            };
+
+            S16Frame::from_rgba8(manifest.format, width, height, image.as_raw())
+                .map_err(|err| ToolError::S16(path, err))
+        })
+        .collect::<Result<Vec<_>, ToolError>>()?;
+
+    let count = frames.len();
+    let file = S16File {
+        format: manifest.format,
+        frames,
+    };
+    let bytes = if is_c16(sprite) {
+        file.encode_c16()
+    } else {
+        file.encode()
+    }
+    .map_err(|err| ToolError::S16(sprite.to_path_buf(), err))?;
+    write(sprite, bytes)?;
+
+    Ok(count)
+}
+
+fn run(args: &[String]) -> Result<String, ToolError> {
+    match args {
+        [command, from, to] if command == "unpack" => {
+            let frames = unpack(Path::new(from), Path::new(to))?;
+            Ok(format!("unpacked {} frames into {}", frames, to))
+        }
+        [command, from, to] if command == "pack" => {
+            let frames = pack(Path::new(from), Path::new(to))?;
+            Ok(format!("packed {} frames into {}", frames, to))
+        }
+        _ => Err(ToolError::Usage),
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match run(&args) {
+        Ok(message) => {
+            println!("{}", message);
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[test]
+fn test_manifest() {
+    let manifest = Manifest {
+        format: S16Format::Rgb565,
+        frames: vec!["2.png".into(), "0.png".into(), "empty 0x0".into()],
+    };
+
+    assert_eq!(Manifest::parse(&manifest.write()).unwrap(), manifest);
+    assert!(Manifest::parse("format rgb888\n").is_err());
+}
+
+#[test]
+fn test_unpack_and_pack() {
+    let folder = std::env::temp_dir().join(format!("s16-test-{}", std::process::id()));
+    let original = folder.join("original.s16");
+    let frames = folder.join("frames");
+    let packed = folder.join("packed.s16");
+
+    let file = S16File {
+        format: S16Format::Rgb565,
+        frames: vec![
+            S16Frame {
+                width: 2,
+                height: 2,
+                pixels: vec![0xf800, 0, 0x07e0, 0x001f],
+            },
+            S16Frame::default(),
+            S16Frame {
+                width: 3,
+                height: 1,
+                pixels: vec![0xffff, 0x1234, 0],
+            },
+        ],
+    };
+
+    fs::create_dir_all(&folder).unwrap();
+    fs::write(&original, file.encode().unwrap()).unwrap();
+
+    assert_eq!(unpack(&original, &frames).unwrap(), 3);
+    assert!(frames.join("0.png").exists());
+    assert_eq!(pack(&frames, &packed).unwrap(), 3);
+
+    let round_trip = S16File::decode(&fs::read(&packed).unwrap()).unwrap();
+    fs::remove_dir_all(&folder).unwrap();
+
+    assert_eq!(round_trip, file);
+}
+
+#[test]
+fn test_pack_c16() {
+    let folder = std::env::temp_dir().join(format!("c16-test-{}", std::process::id()));
+    let frames = folder.join("frames");
+    let packed = folder.join("packed.c16");
+
+    let file = S16File {
+        format: S16Format::Rgb565,
+        frames: vec![S16Frame {
+            width: 3,
+            height: 2,
+            pixels: vec![0, 0xf800, 0xf800, 0x001f, 0, 0],
+        }],
+    };
+
+    fs::create_dir_all(&folder).unwrap();
+    fs::write(folder.join("original.c16"), file.encode_c16().unwrap()).unwrap();
+
+    assert_eq!(unpack(&folder.join("original.c16"), &frames).unwrap(), 1);
+    assert_eq!(pack(&frames, &packed).unwrap(), 1);
+
+    let round_trip = S16File::decode_c16(&fs::read(&packed).unwrap()).unwrap();
+    fs::remove_dir_all(&folder).unwrap();
+
+    assert_eq!(round_trip, file);
+}
@@ -13,7 +13,7 @@ use bevy::{
     },
 };
 
-use crate::{constants::WORLD_WIDTH, display::get_viewport_rect, state::GameState};
+use crate::{display::get_viewport_rect, geometry::WorldGeometry, state::GameState};
 use creature_eye_view::CreatureEyeView;
 use main_camera::MainCamera;
 
@@ -40,8 +40,11 @@ impl Plugin for GameCameraPlugin {
                     .run_if(in_state(GameState::Running)),
                 creature_eye_view::select_new_eye_view
                     .run_if(input_just_pressed(MouseButton::Left)),
+                keyboard_scrolling.run_if(in_state(GameState::Running)),
             ),
         );
+
+        app.insert_resource(ScrollSpeed { speed: 200.0 });
     }
 }
 
@@ -143,7 +146,10 @@ pub fn wrap_cameras(
         &GlobalTransform,
         &OrthographicProjection,
     )>,
+    geometry: Res<WorldGeometry>,
 ) {
+    let space = geometry.space();
+
     camera_query
         .iter_mut()
         .for_each(|(camera, mut camera_transform, global_transform, ortho)| {
@@ -151,14 +157,74 @@ pub fn wrap_cameras(
                 return;
             };
 
+            if !space.wraps() {
+                return;
+            }
+
             if viewport.min.x <= 0.0 - viewport.width() {
-                camera_transform.translation.x += WORLD_WIDTH;
+                camera_transform.translation.x += space.width();
                 camera_transform.translation.x = camera_transform.translation.x.floor();
             }
 
-            if viewport.min.x >= WORLD_WIDTH {
-                camera_transform.translation.x -= WORLD_WIDTH;
+            if viewport.min.x >= space.width() {
+                camera_transform.translation.x -= space.width();
                 camera_transform.translation.x = camera_transform.translation.x.floor();
             }
         });
 }
+
+#[derive(Resource, Default)]
+struct ScrollSpeed {
+    speed: f32,
+}
+
+fn keyboard_scrolling(
+    time: Res<Time>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut query: Query<(&mut Transform, &mut OrthographicProjection, &Camera), With<MainCamera>>,
+    scroll_speed: Res<ScrollSpeed>,
+) {
+    for (mut transform, mut ortho, _) in query.iter_mut() {
+        let mut direction = Vec3::ZERO;
+
+        if keyboard_input.pressed(KeyCode::KeyA) {
+            direction -= Vec3::new(1.0, 0.0, 0.0);
+        }
+
+        if keyboard_input.pressed(KeyCode::KeyD) {
+            direction += Vec3::new(1.0, 0.0, 0.0);
+        }
+
+        if keyboard_input.pressed(KeyCode::KeyW) {
+            direction += Vec3::new(0.0, 1.0, 0.0);
+        }
+
+        if keyboard_input.pressed(KeyCode::KeyS) {
+            direction -= Vec3::new(0.0, 1.0, 0.0);
+        }
+
+        if keyboard_input.pressed(KeyCode::KeyZ) {
+            ortho.scale = ortho.scale.lerp(ortho.scale + 2.0, time.delta_secs());
+        }
+
+        if keyboard_input.pressed(KeyCode::KeyX) {
+            ortho.scale = ortho.scale.lerp(ortho.scale - 2.0, time.delta_secs());
+        }
+
+        // ortho.scale = ortho.scale.clamp(0.80, 2.0);
+
+        direction *= ortho.scale;
+
+        let z = transform.translation.z;
+        transform.translation += time.delta_secs() * direction * scroll_speed.speed;
+        // Important! We need to restore the Z values when moving the camera around.
+        // Bevy has a specific camera setup and this can mess with how our layers are shown.
+        transform.translation.z = z;
+
+        transform.translation = Vec3 {
+            x: transform.translation.x.round(),
+            y: transform.translation.y.round(),
+            z: transform.translation.z.round(),
+        };
+    }
+}
@@ -0,0 +1,129 @@
+use super::Span;
+
+/// A parsed script, the statements run from `inst`/first command to `endm`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Script {
+    pub body: Block,
+}
+
+pub type Block = Vec<Statement>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Statement {
+    pub kind: StatementKind,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum StatementKind {
+    /// A command from [`super::commands::COMMANDS`], e.g. `mvto 10 20`.
+    Command { name: &'static str, args: Vec<Expr> },
+    /// `doif`, any `elif`s and an optional `else`, ending with `endi`.
+    Doif {
+        branches: Vec<(Condition, Block)>,
+        otherwise: Option<Block>,
+    },
+    /// `reps count ... repe`
+    Reps { count: Expr, body: Block },
+    /// `loop ... untl condition`, or `loop ... ever` when `until` is `None`.
+    Loop {
+        body: Block,
+        until: Option<Condition>,
+    },
+    /// `enum`, `esee` or `etch family genus species ... next`
+    Enum {
+        kind: EnumKind,
+        family: Expr,
+        genus: Expr,
+        species: Expr,
+        body: Block,
+    },
+    /// `subr label`, the start of a subroutine that runs until `retn`.
+    Subr { label: String },
+    /// `gsub label`
+    Gsub { label: String },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EnumKind {
+    /// Every object matching the classifier.
+    All,
+    /// Objects the owner can see.
+    Seen,
+    /// Objects the owner is touching.
+    Touching,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExprKind {
+    Integer(i32),
+    String(String),
+    /// A bare word taken literally, such as a sound or sprite file name.
+    Token(String),
+    /// `var0` to `var9`, local to the running script.
+    Var(u8),
+    /// `obv0` to `obv9`, stored on the target object.
+    Obv(u8),
+    /// A value from [`super::commands::VALUES`], e.g. `posl` or `rand 1 10`.
+    Value {
+        name: &'static str,
+        args: Vec<Expr>,
+    },
+}
+
+/// One or more comparisons joined with `and`/`or`, evaluated left to right.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Condition {
+    pub first: Comparison,
+    pub rest: Vec<(Logic, Comparison)>,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Comparison {
+    pub left: Expr,
+    pub op: CompareOp,
+    pub right: Expr,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    /// Every bit set in the right hand side is set in the left.
+    BitsTrue,
+    /// Every bit set in the right hand side is clear in the left.
+    BitsFalse,
+}
+
+impl CompareOp {
+    pub fn from_word(word: &str) -> Option<Self> {
+        Some(match word {
+            "eq" => CompareOp::Eq,
+            "ne" => CompareOp::Ne,
+            "lt" => CompareOp::Lt,
+            "gt" => CompareOp::Gt,
+            "le" => CompareOp::Le,
+            "ge" => CompareOp::Ge,
+            "bt" => CompareOp::BitsTrue,
+            "bf" => CompareOp::BitsFalse,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Logic {
+    And,
+    Or,
+}
@@ -0,0 +1,203 @@
+//! Signatures of the C2 commands and values the parser knows about. Flow
+//! control (`doif`, `reps`, `loop`, `enum`, `subr`, `gsub` and their closing
+//! words) is handled by the parser itself and isn't listed here.
+
+/// What an argument slot accepts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Arg {
+    /// Any value: an integer, variable or value command.
+    Value,
+    /// Something that can be assigned to: `var0`-`var9`, `obv0`-`obv9` or a
+    /// value in [`SETTABLE`].
+    Variable,
+    /// A bracketed string such as `[012R]`.
+    String,
+    /// A bare word taken literally, such as a sound name or label.
+    Token,
+}
+
+#[derive(Debug)]
+pub struct Signature {
+    pub name: &'static str,
+    pub args: &'static [Arg],
+}
+
+const V: Arg = Arg::Value;
+const VAR: Arg = Arg::Variable;
+const S: Arg = Arg::String;
+const T: Arg = Arg::Token;
+
+const fn sig(name: &'static str, args: &'static [Arg]) -> Signature {
+    Signature { name, args }
+}
+
+pub const COMMANDS: &[Signature] = &[
+    // Script flow
+    sig("inst", &[]),
+    sig("slow", &[]),
+    sig("stop", &[]),
+    sig("endm", &[]),
+    sig("over", &[]),
+    sig("retn", &[]),
+    sig("wait", &[V]),
+    // Variables
+    sig("setv", &[VAR, V]),
+    sig("addv", &[VAR, V]),
+    sig("subv", &[VAR, V]),
+    sig("mulv", &[VAR, V]),
+    sig("divv", &[VAR, V]),
+    sig("modv", &[VAR, V]),
+    sig("andv", &[VAR, V]),
+    sig("orv", &[VAR, V]),
+    sig("negv", &[VAR]),
+    sig("rndv", &[VAR, V, V]),
+    // Targeting
+    sig("targ", &[V]),
+    sig("rtar", &[V, V, V]),
+    sig("star", &[V, V, V]),
+    sig("norn", &[V]),
+    // Objects
+    sig("anim", &[S]),
+    sig("pose", &[V]),
+    sig("base", &[V]),
+    sig("part", &[V]),
+    sig("mvto", &[V, V]),
+    sig("mvby", &[V, V]),
+    sig("tele", &[V, V]),
+    sig("kill", &[V]),
+    sig("tick", &[V]),
+    sig("bhvr", &[V, V]),
+    sig("drop", &[]),
+    sig("cabn", &[V, V, V, V]),
+    sig("gpas", &[V, V, V, V]),
+    sig("dpas", &[V, V, V]),
+    sig("spot", &[V, V, V, V, V]),
+    sig("scrx", &[V, V, V, V]),
+    sig("new: simp", &[V, V, V, T, V, V, V]),
+    sig("new: comp", &[V, V, V, T, V, V, V]),
+    sig("new: vhcl", &[V, V, V, T, V, V, V]),
+    sig("new: lift", &[V, V, V, T, V, V, V]),
+    sig("new: bkbd", &[V, V, V, T, V, V, V]),
+    sig("new: cbtn", &[V, V, V, T, V, V, V]),
+    sig("new: part", &[V, V, V, V, V]),
+    // Blackboards
+    sig("bbd: word", &[V, V, S]),
+    sig("bbd: show", &[V]),
+    sig("bbd: emit", &[V]),
+    sig("bbd: edit", &[V]),
+    // Sound
+    sig("snde", &[T]),
+    sig("sndc", &[T]),
+    sig("sndl", &[T]),
+    sig("sndq", &[T, V]),
+    sig("stpc", &[]),
+    // Messages and stimuli
+    sig("mesg writ", &[V, V]),
+    sig("mesg wrt+", &[V, V, V, V, V]),
+    sig("stim writ", &[V, V, V, V, V, V, V, V, V, V, V, V]),
+    sig("stim shou", &[V, V, V, V, V, V, V, V, V, V, V]),
+    sig("stim sign", &[V, V, V, V, V, V, V, V, V, V, V]),
+    sig("emit", &[V, V]),
+    // Creatures
+    sig("aslp", &[V]),
+    sig("chem", &[V, V]),
+    sig("injr", &[V, V]),
+    sig("dead", &[]),
+    // Camera
+    sig("sys: camt", &[]),
+    sig("sys: cmra", &[V, V]),
+    // Debugging
+    sig("dbgm", &[S]),
+    sig("dbgv", &[V]),
+];
+
+pub const VALUES: &[Signature] = &[
+    // Objects the script relates to
+    sig("targ", &[]),
+    sig("ownr", &[]),
+    sig("from", &[]),
+    sig("_it_", &[]),
+    sig("pntr", &[]),
+    sig("norn", &[]),
+    sig("carr", &[]),
+    sig("_p1_", &[]),
+    sig("_p2_", &[]),
+    // Properties of the target
+    sig("attr", &[]),
+    sig("actv", &[]),
+    sig("pose", &[]),
+    sig("posl", &[]),
+    sig("posr", &[]),
+    sig("post", &[]),
+    sig("posb", &[]),
+    sig("wdth", &[]),
+    sig("hght", &[]),
+    sig("xvec", &[]),
+    sig("yvec", &[]),
+    sig("clas", &[]),
+    sig("objp", &[]),
+    sig("fmly", &[]),
+    sig("gnus", &[]),
+    sig("spcs", &[]),
+    sig("tick", &[]),
+    sig("bump", &[]),
+    sig("baby", &[]),
+    sig("thrt", &[]),
+    sig("size", &[]),
+    sig("grav", &[]),
+    sig("accg", &[]),
+    sig("rest", &[]),
+    sig("aero", &[]),
+    sig("rnge", &[]),
+    sig("dead", &[]),
+    sig("aslp", &[]),
+    sig("chem", &[V]),
+    sig("driv", &[V]),
+    sig("obst", &[V]),
+    sig("room", &[V]),
+    // Queries
+    sig("rand", &[V, V]),
+    sig("totl", &[V, V, V]),
+    sig("touc", &[V, V]),
+    // Environment of the room the target is in
+    sig("temp", &[]),
+    sig("lite", &[]),
+    sig("radn", &[]),
+    sig("ontr", &[]),
+    sig("intr", &[]),
+    sig("pres", &[]),
+    sig("hsrc", &[]),
+    sig("lsrc", &[]),
+    sig("rsrc", &[]),
+    sig("psrc", &[]),
+    sig("wndx", &[]),
+    sig("wndy", &[]),
+    sig("gndw", &[]),
+    sig("grnd", &[V]),
+    // Calendar
+    sig("seas", &[]),
+    sig("date", &[]),
+    sig("time", &[]),
+    sig("year", &[]),
+];
+
+/// Values that can also be the first argument of `setv` and friends.
+pub const SETTABLE: &[&str] = &[
+    "actv", "attr", "xvec", "yvec", "clas", "objp", "baby", "thrt", "size", "grav", "accg", "rest",
+    "aero", "rnge",
+];
+
+pub fn command(name: &str) -> Option<&'static Signature> {
+    COMMANDS.iter().find(|signature| signature.name == name)
+}
+
+pub fn value(name: &str) -> Option<&'static Signature> {
+    VALUES.iter().find(|signature| signature.name == name)
+}
+
+/// Whether `word` starts a two word command such as `new: simp`.
+pub fn is_namespace(word: &str) -> bool {
+    COMMANDS
+        .iter()
+        .any(|signature| signature.name.split_once(' ').map(|(first, _)| first) == Some(word))
+}
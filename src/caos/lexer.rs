@@ -0,0 +1,131 @@
+use super::{Diagnostic, Span};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+    /// A command, value or bare token such as a sound name, lowercased.
+    Word(String),
+    Integer(i32),
+    /// Text between square brackets, e.g. `[012R]`.
+    String(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+/// Splits a script into tokens. Whitespace and commas separate tokens, and a
+/// `*` at the start of a token comments out the rest of the line.
+pub fn tokenize(source: &str) -> (Vec<Token>, Vec<Diagnostic>) {
+    let mut tokens = Vec::new();
+    let mut diagnostics = Vec::new();
+    let mut chars = source.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() || c == ',' {
+            chars.next();
+            continue;
+        }
+
+        if c == '*' {
+            while chars.next_if(|&(_, c)| c != '\n').is_some() {}
+            continue;
+        }
+
+        if c == '[' {
+            chars.next();
+            let mut end = source.len();
+            let mut closed = false;
+
+            for (i, c) in chars.by_ref() {
+                if c == ']' {
+                    end = i;
+                    closed = true;
+                    break;
+                }
+            }
+
+            if !closed {
+                diagnostics.push(Diagnostic::new(
+                    "unterminated string, expected `]`",
+                    Span::new(start, source.len()),
+                ));
+            }
+
+            let span = Span::new(start, if closed { end + 1 } else { end });
+            tokens.push(Token {
+                kind: TokenKind::String(source[start + 1..end].to_string()),
+                span,
+            });
+            continue;
+        }
+
+        let mut end = start;
+        while let Some((i, c)) =
+            chars.next_if(|&(_, c)| !(c.is_whitespace() || c == ',' || c == '['))
+        {
+            end = i + c.len_utf8();
+        }
+
+        let text = &source[start..end];
+        let span = Span::new(start, end);
+
+        let kind = if is_integer(text) {
+            match text.parse() {
+                Ok(value) => TokenKind::Integer(value),
+                Err(_) => {
+                    diagnostics.push(Diagnostic::new(
+                        format!("integer `{}` is out of range", text),
+                        span,
+                    ));
+                    TokenKind::Integer(0)
+                }
+            }
+        } else {
+            TokenKind::Word(text.to_lowercase())
+        };
+
+        tokens.push(Token { kind, span });
+    }
+
+    (tokens, diagnostics)
+}
+
+fn is_integer(text: &str) -> bool {
+    let digits = text.strip_prefix('-').unwrap_or(text);
+    !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
+#[test]
+fn test_tokenize() {
+    let (tokens, diagnostics) = tokenize("SETV var0 -12,anim [012R] * done\nendm");
+
+    assert!(diagnostics.is_empty());
+    assert_eq!(
+        tokens.iter().map(|token| &token.kind).collect::<Vec<_>>(),
+        [
+            &TokenKind::Word("setv".to_string()),
+            &TokenKind::Word("var0".to_string()),
+            &TokenKind::Integer(-12),
+            &TokenKind::Word("anim".to_string()),
+            &TokenKind::String("012R".to_string()),
+            &TokenKind::Word("endm".to_string()),
+        ]
+    );
+    assert_eq!(tokens[4].span, Span::new(19, 25));
+    assert_eq!(tokens[5].span, Span::new(33, 37));
+}
+
+#[test]
+fn test_tokenize_errors() {
+    let (_, diagnostics) = tokenize("setv var0 99999999999 anim [01");
+
+    assert_eq!(
+        diagnostics,
+        [
+            Diagnostic::new("integer `99999999999` is out of range", Span::new(10, 21)),
+            Diagnostic::new("unterminated string, expected `]`", Span::new(27, 30)),
+        ]
+    );
+}
@@ -0,0 +1,138 @@
+//! CAOS, the scripting language objects in Creatures 2 are driven by.
+//!
+//! Scripts are read with [`parse`], which turns source text into the AST in
+//! [`ast`]. Commands and values are looked up in the tables in [`commands`],
+//! which give the number and kind of arguments each one takes, as CAOS has no
+//! delimiters to mark where an argument list ends.
+//!
+//! Parsed scripts are compiled for the virtual machine in [`vm`], and
+//! [`runtime`] runs them for objects in the world once per fixed tick, looking
+//! up each object's scripts in the [`scriptorium`].
+
+pub mod ast;
+pub mod commands;
+pub mod lexer;
+pub mod parser;
+pub mod runtime;
+pub mod scriptorium;
+pub mod vm;
+
+use std::fmt::Display;
+
+use bevy::prelude::*;
+
+use crate::state::GameState;
+
+pub use ast::Script;
+
+pub struct GameCaosPlugin;
+
+impl Plugin for GameCaosPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<runtime::ScriptEvent>();
+        app.init_resource::<runtime::ScriptBudget>();
+        app.init_resource::<scriptorium::Scriptorium>();
+        app.add_systems(Update, scriptorium::install_world_scripts);
+        app.register_type::<runtime::ScriptTimer>();
+        app.add_systems(
+            FixedUpdate,
+            (
+                runtime::tick_timers,
+                runtime::start_scripts,
+                runtime::run_scripts,
+            )
+                .chain()
+                .run_if(in_state(GameState::Running)),
+        );
+    }
+}
+
+/// Byte range of a token or node in the script source.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// The smallest span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+}
+
+/// A problem found while reading a script.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub message: String,
+    pub span: Span,
+}
+
+impl Diagnostic {
+    pub fn new(message: impl Into<String>, span: Span) -> Self {
+        Self {
+            message: message.into(),
+            span,
+        }
+    }
+
+    /// Line and column, both starting at 1, of the start of the span.
+    pub fn position(&self, source: &str) -> (usize, usize) {
+        let before = &source[..self.span.start.min(source.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before.chars().rev().take_while(|&c| c != '\n').count() + 1;
+        (line, column)
+    }
+
+    /// Formats the diagnostic with the offending line and the span
+    /// underlined, for logging.
+    pub fn render(&self, source: &str) -> String {
+        let (line, column) = self.position(source);
+        let text = source.lines().nth(line - 1).unwrap_or_default();
+        let width = source[self.span.start.min(source.len())..self.span.end.min(source.len())]
+            .chars()
+            .take_while(|&c| c != '\n')
+            .count()
+            .max(1);
+
+        format!(
+            "{}:{}: {}\n{}\n{}{}",
+            line,
+            column,
+            self.message,
+            text,
+            " ".repeat(column - 1),
+            "^".repeat(width)
+        )
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} at {}..{}",
+            self.message, self.span.start, self.span.end
+        )
+    }
+}
+
+/// Parses a whole script. Reading carries on past errors so that every
+/// problem in the script is reported at once.
+pub fn parse(source: &str) -> Result<Script, Vec<Diagnostic>> {
+    let (tokens, mut diagnostics) = lexer::tokenize(source);
+    let (script, parse_diagnostics) = parser::Parser::new(&tokens).parse_script();
+
+    diagnostics.extend(parse_diagnostics);
+
+    if diagnostics.is_empty() {
+        Ok(script)
+    } else {
+        diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+        Err(diagnostics)
+    }
+}
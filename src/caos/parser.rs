@@ -0,0 +1,679 @@
+use super::{
+    ast::{Block, CompareOp, Comparison, Condition, EnumKind, Expr, ExprKind, Logic, Script},
+    ast::{Statement, StatementKind},
+    commands::{self, Arg},
+    lexer::{Token, TokenKind},
+    Diagnostic, Span,
+};
+
+/// Words that open or close a block, handled here rather than in the
+/// command table.
+const FLOW_WORDS: &[&str] = &[
+    "doif", "elif", "else", "endi", "reps", "repe", "loop", "untl", "ever", "enum", "esee", "etch",
+    "next", "subr", "gsub",
+];
+
+type ParseResult<T> = Result<T, Diagnostic>;
+
+pub struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+    diagnostics: Vec<Diagnostic>,
+    subroutines: Vec<String>,
+    calls: Vec<(String, Span)>,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(tokens: &'a [Token]) -> Self {
+        Self {
+            tokens,
+            position: 0,
+            diagnostics: Vec::new(),
+            subroutines: Vec::new(),
+            calls: Vec::new(),
+        }
+    }
+
+    /// Parses every token as one script, returning what could be read along
+    /// with any problems found.
+    pub fn parse_script(mut self) -> (Script, Vec<Diagnostic>) {
+        let body = self.parse_block(&[]);
+
+        for (label, span) in std::mem::take(&mut self.calls) {
+            if !self.subroutines.contains(&label) {
+                self.diagnostics.push(Diagnostic::new(
+                    format!("no `subr` named `{}`", label),
+                    span,
+                ));
+            }
+        }
+
+        (Script { body }, self.diagnostics)
+    }
+
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn peek_word(&self) -> Option<&'a str> {
+        match self.peek() {
+            Some(Token {
+                kind: TokenKind::Word(word),
+                ..
+            }) => Some(word),
+            _ => None,
+        }
+    }
+
+    fn next(&mut self) -> Option<&'a Token> {
+        let token = self.tokens.get(self.position)?;
+        self.position += 1;
+        Some(token)
+    }
+
+    /// Span just past the last token, for errors at the end of the script.
+    fn end_span(&self) -> Span {
+        let end = self.tokens.last().map_or(0, |token| token.span.end);
+        Span::new(end, end)
+    }
+
+    fn previous_span(&self) -> Span {
+        match self.position {
+            0 => Span::default(),
+            position => self.tokens[position - 1].span,
+        }
+    }
+
+    fn parse_block(&mut self, terminators: &[&str]) -> Block {
+        let mut block = Vec::new();
+
+        loop {
+            match self.peek_word() {
+                None if self.peek().is_none() => break,
+                Some(word) if terminators.contains(&word) => break,
+                _ => {}
+            }
+
+            match self.parse_statement() {
+                Ok(statement) => block.push(statement),
+                Err(diagnostic) => {
+                    self.diagnostics.push(diagnostic);
+                    self.recover();
+                }
+            }
+        }
+
+        block
+    }
+
+    /// Skips to the next word that can start a statement, so that one bad
+    /// command doesn't hide errors in the rest of the script.
+    fn recover(&mut self) {
+        while let Some(token) = self.peek() {
+            if let TokenKind::Word(word) = &token.kind {
+                if FLOW_WORDS.contains(&word.as_str())
+                    || commands::command(word).is_some()
+                    || commands::is_namespace(word)
+                {
+                    break;
+                }
+            }
+            self.position += 1;
+        }
+    }
+
+    fn parse_statement(&mut self) -> ParseResult<Statement> {
+        let Some(token) = self.next() else {
+            return Err(Diagnostic::new(
+                "expected a command, found end of script",
+                self.end_span(),
+            ));
+        };
+
+        let word = match &token.kind {
+            TokenKind::Word(word) => word.as_str(),
+            TokenKind::Integer(value) => {
+                return Err(Diagnostic::new(
+                    format!("expected a command, found number {}", value),
+                    token.span,
+                ))
+            }
+            TokenKind::String(_) => {
+                return Err(Diagnostic::new(
+                    "expected a command, found a string",
+                    token.span,
+                ))
+            }
+        };
+
+        let start = token.span;
+
+        let kind = match word {
+            "doif" => self.parse_doif(start)?,
+            "reps" => {
+                let count = self.parse_value()?;
+                let body = self.parse_block(&["repe"]);
+                self.expect("repe", "reps", start)?;
+                StatementKind::Reps { count, body }
+            }
+            "loop" => {
+                let body = self.parse_block(&["untl", "ever"]);
+                let until = match self.next() {
+                    Some(token) if token.kind == TokenKind::Word("untl".to_string()) => {
+                        Some(self.parse_condition()?)
+                    }
+                    Some(token) if token.kind == TokenKind::Word("ever".to_string()) => None,
+                    _ => return Err(Diagnostic::new("`loop` is missing `untl` or `ever`", start)),
+                };
+                StatementKind::Loop { body, until }
+            }
+            "enum" | "esee" | "etch" => {
+                let kind = match word {
+                    "enum" => EnumKind::All,
+                    "esee" => EnumKind::Seen,
+                    _ => EnumKind::Touching,
+                };
+                let family = self.parse_value()?;
+                let genus = self.parse_value()?;
+                let species = self.parse_value()?;
+                let body = self.parse_block(&["next"]);
+                self.expect("next", word, start)?;
+                StatementKind::Enum {
+                    kind,
+                    family,
+                    genus,
+                    species,
+                    body,
+                }
+            }
+            "subr" => {
+                let label = self.parse_label()?;
+                self.subroutines.push(label.clone());
+                StatementKind::Subr { label }
+            }
+            "gsub" => {
+                let label = self.parse_label()?;
+                self.calls.push((label.clone(), self.previous_span()));
+                StatementKind::Gsub { label }
+            }
+            "elif" | "else" | "endi" => return Err(unmatched(word, "doif", start)),
+            "repe" => return Err(unmatched(word, "reps", start)),
+            "untl" | "ever" => return Err(unmatched(word, "loop", start)),
+            "next" => return Err(unmatched(word, "enum", start)),
+            _ => self.parse_command(word, start)?,
+        };
+
+        Ok(Statement {
+            kind,
+            span: start.to(self.previous_span()),
+        })
+    }
+
+    fn parse_doif(&mut self, start: Span) -> ParseResult<StatementKind> {
+        let mut branches = Vec::new();
+        let mut otherwise = None;
+
+        let condition = self.parse_condition()?;
+        let body = self.parse_block(&["elif", "else", "endi"]);
+        branches.push((condition, body));
+
+        loop {
+            match self.next() {
+                Some(Token {
+                    kind: TokenKind::Word(word),
+                    ..
+                }) => match word.as_str() {
+                    "elif" => {
+                        let condition = self.parse_condition()?;
+                        let body = self.parse_block(&["elif", "else", "endi"]);
+                        branches.push((condition, body));
+                    }
+                    "else" => {
+                        otherwise = Some(self.parse_block(&["endi"]));
+                        self.expect("endi", "doif", start)?;
+                        break;
+                    }
+                    _ => break,
+                },
+                _ => return Err(Diagnostic::new("`doif` is missing `endi`", start)),
+            }
+        }
+
+        Ok(StatementKind::Doif {
+            branches,
+            otherwise,
+        })
+    }
+
+    fn parse_command(&mut self, word: &str, start: Span) -> ParseResult<StatementKind> {
+        let mut signature = None;
+
+        if commands::is_namespace(word) {
+            if let Some(second) = self.peek_word() {
+                signature = commands::command(&format!("{} {}", word, second));
+                if signature.is_some() {
+                    self.position += 1;
+                }
+            }
+        }
+
+        let Some(signature) = signature.or_else(|| commands::command(word)) else {
+            let message = match (commands::is_namespace(word), self.peek_word()) {
+                (true, Some(second)) => format!("unknown command `{} {}`", word, second),
+                _ => format!("unknown command `{}`", word),
+            };
+            return Err(Diagnostic::new(message, start));
+        };
+
+        let args = self.parse_args(signature.name, signature.args)?;
+
+        Ok(StatementKind::Command {
+            name: signature.name,
+            args,
+        })
+    }
+
+    fn parse_args(&mut self, name: &str, kinds: &[Arg]) -> ParseResult<Vec<Expr>> {
+        kinds
+            .iter()
+            .map(|kind| match kind {
+                Arg::Value => self.parse_value(),
+                Arg::Variable => self.parse_variable(),
+                Arg::String => self.parse_string(),
+                Arg::Token => self.parse_token(),
+            })
+            .collect::<ParseResult<Vec<_>>>()
+            .map_err(|diagnostic| Diagnostic {
+                message: format!("{} for `{}`", diagnostic.message, name),
+                ..diagnostic
+            })
+    }
+
+    fn parse_value(&mut self) -> ParseResult<Expr> {
+        let Some(token) = self.next() else {
+            return Err(Diagnostic::new(
+                "expected a value, found end of script",
+                self.end_span(),
+            ));
+        };
+
+        let kind = match &token.kind {
+            TokenKind::Integer(value) => ExprKind::Integer(*value),
+            TokenKind::String(_) => {
+                return Err(Diagnostic::new(
+                    "expected a value, found a string",
+                    token.span,
+                ))
+            }
+            TokenKind::Word(word) => {
+                if let Some(kind) = variable(word) {
+                    kind
+                } else if let Some(signature) = commands::value(word) {
+                    let args = self.parse_args(signature.name, signature.args)?;
+                    ExprKind::Value {
+                        name: signature.name,
+                        args,
+                    }
+                } else {
+                    return Err(Diagnostic::new(
+                        format!("unknown value `{}`", word),
+                        token.span,
+                    ));
+                }
+            }
+        };
+
+        Ok(Expr {
+            kind,
+            span: token.span.to(self.previous_span()),
+        })
+    }
+
+    fn parse_variable(&mut self) -> ParseResult<Expr> {
+        let Some(token) = self.next() else {
+            return Err(Diagnostic::new(
+                "expected a variable, found end of script",
+                self.end_span(),
+            ));
+        };
+
+        let kind = match &token.kind {
+            TokenKind::Word(word) => match variable(word) {
+                Some(kind) => Some(kind),
+                None => commands::SETTABLE
+                    .iter()
+                    .find(|&&name| name == word)
+                    .map(|&name| ExprKind::Value {
+                        name,
+                        args: Vec::new(),
+                    }),
+            },
+            _ => None,
+        };
+
+        match kind {
+            Some(kind) => Ok(Expr {
+                kind,
+                span: token.span,
+            }),
+            None => Err(Diagnostic::new("expected a variable", token.span)),
+        }
+    }
+
+    fn parse_string(&mut self) -> ParseResult<Expr> {
+        match self.next() {
+            Some(Token {
+                kind: TokenKind::String(string),
+                span,
+            }) => Ok(Expr {
+                kind: ExprKind::String(string.clone()),
+                span: *span,
+            }),
+            Some(token) => Err(Diagnostic::new("expected a string in brackets", token.span)),
+            None => Err(Diagnostic::new(
+                "expected a string, found end of script",
+                self.end_span(),
+            )),
+        }
+    }
+
+    fn parse_token(&mut self) -> ParseResult<Expr> {
+        match self.next() {
+            Some(Token {
+                kind: TokenKind::Word(word),
+                span,
+            }) => Ok(Expr {
+                kind: ExprKind::Token(word.clone()),
+                span: *span,
+            }),
+            Some(Token {
+                kind: TokenKind::Integer(value),
+                span,
+            }) => Ok(Expr {
+                kind: ExprKind::Token(value.to_string()),
+                span: *span,
+            }),
+            Some(token) => Err(Diagnostic::new("expected a name", token.span)),
+            None => Err(Diagnostic::new(
+                "expected a name, found end of script",
+                self.end_span(),
+            )),
+        }
+    }
+
+    fn parse_label(&mut self) -> ParseResult<String> {
+        match self.parse_token()?.kind {
+            ExprKind::Token(label) => Ok(label),
+            _ => unreachable!(),
+        }
+    }
+
+    fn parse_condition(&mut self) -> ParseResult<Condition> {
+        let first = self.parse_comparison()?;
+        let start = first.left.span;
+        let mut rest = Vec::new();
+
+        loop {
+            let logic = match self.peek_word() {
+                Some("and") => Logic::And,
+                Some("or") => Logic::Or,
+                _ => break,
+            };
+            self.position += 1;
+            rest.push((logic, self.parse_comparison()?));
+        }
+
+        Ok(Condition {
+            first,
+            rest,
+            span: start.to(self.previous_span()),
+        })
+    }
+
+    fn parse_comparison(&mut self) -> ParseResult<Comparison> {
+        let left = self.parse_value()?;
+
+        let op = match self.next() {
+            Some(Token {
+                kind: TokenKind::Word(word),
+                span,
+            }) => CompareOp::from_word(word).ok_or_else(|| {
+                Diagnostic::new(
+                    format!("expected a comparison such as `eq`, found `{}`", word),
+                    *span,
+                )
+            })?,
+            Some(token) => {
+                return Err(Diagnostic::new(
+                    "expected a comparison such as `eq`",
+                    token.span,
+                ))
+            }
+            None => {
+                return Err(Diagnostic::new(
+                    "expected a comparison, found end of script",
+                    self.end_span(),
+                ))
+            }
+        };
+
+        let right = self.parse_value()?;
+
+        Ok(Comparison { left, op, right })
+    }
+
+    fn expect(&mut self, word: &str, opener: &str, start: Span) -> ParseResult<()> {
+        if self.peek_word() == Some(word) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(Diagnostic::new(
+                format!("`{}` is missing `{}`", opener, word),
+                start,
+            ))
+        }
+    }
+}
+
+fn unmatched(word: &str, opener: &str, span: Span) -> Diagnostic {
+    Diagnostic::new(format!("`{}` without `{}`", word, opener), span)
+}
+
+/// `var0`-`var9` and `obv0`-`obv9`.
+fn variable(word: &str) -> Option<ExprKind> {
+    let (prefix, digit) = word.split_at_checked(3)?;
+    let index = match digit.as_bytes() {
+        [digit @ b'0'..=b'9'] => digit - b'0',
+        _ => return None,
+    };
+
+    match prefix {
+        "var" => Some(ExprKind::Var(index)),
+        "obv" => Some(ExprKind::Obv(index)),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_parse_script() {
+    let script = super::parse(
+        "inst,setv var0 rand 1 10,doif var0 gt 5 and obv1 bt 2,anim [012R],\
+         elif var0 eq 1,mesg writ _it_ 1,else,gsub move,endi,\
+         reps 3,snde bees,repe,enum 2 0 0,addv obv0 1,next,\
+         loop,mvby 1 0,untl posl ge 100,new: simp 2 8 1 blnk 1 0 3000,\
+         stop,subr move,mvto 10 20,retn,endm",
+    )
+    .expect("script should parse");
+
+    let names: Vec<_> = script
+        .body
+        .iter()
+        .map(|statement| match &statement.kind {
+            StatementKind::Command { name, .. } => *name,
+            StatementKind::Doif { .. } => "doif",
+            StatementKind::Reps { .. } => "reps",
+            StatementKind::Loop { .. } => "loop",
+            StatementKind::Enum { .. } => "enum",
+            StatementKind::Subr { .. } => "subr",
+            StatementKind::Gsub { .. } => "gsub",
+        })
+        .collect();
+
+    assert_eq!(
+        names,
+        [
+            "inst",
+            "setv",
+            "doif",
+            "reps",
+            "enum",
+            "loop",
+            "new: simp",
+            "stop",
+            "subr",
+            "mvto",
+            "retn",
+            "endm"
+        ]
+    );
+
+    let StatementKind::Command { args, .. } = &script.body[1].kind else {
+        unreachable!()
+    };
+    assert_eq!(args[0].kind, ExprKind::Var(0));
+    assert_eq!(args[1].span, Span::new(15, 24));
+
+    let StatementKind::Doif {
+        branches,
+        otherwise,
+    } = &script.body[2].kind
+    else {
+        unreachable!()
+    };
+    assert_eq!(branches.len(), 2);
+    assert_eq!(branches[0].0.rest[0].0, Logic::And);
+    assert_eq!(branches[0].0.rest[0].1.op, CompareOp::BitsTrue);
+    assert_eq!(
+        otherwise.as_ref().unwrap()[0].kind,
+        StatementKind::Gsub {
+            label: "move".to_string()
+        }
+    );
+
+    let StatementKind::Loop { until, .. } = &script.body[5].kind else {
+        unreachable!()
+    };
+    assert!(until.is_some());
+}
+
+#[test]
+fn test_parse_script_diagnostics() {
+    let source = "inst\nfoo 1 2\nsetv 5 1\ngsub nowhere\ndoif var0 eq 1\nmvto 1";
+    let diagnostics = super::parse(source).expect_err("script has errors");
+
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.message.as_str())
+        .collect();
+
+    assert_eq!(
+        messages,
+        [
+            "unknown command `foo`",
+            "expected a variable for `setv`",
+            "no `subr` named `nowhere`",
+            "`doif` is missing `endi`",
+            "expected a value, found end of script for `mvto`",
+        ]
+    );
+    assert_eq!(
+        diagnostics[0].render(source),
+        "2:1: unknown command `foo`\nfoo 1 2\n^^^"
+    );
+    assert_eq!(
+        diagnostics[2].render(source),
+        "4:6: no `subr` named `nowhere`\ngsub nowhere\n     ^^^^^^^"
+    );
+}
+
+#[test]
+fn test_parse_sfc_scripts() {
+    use crate::formats::sfc::{ArchiveTable, Doc};
+
+    let buf = include_bytes!("../../assets/fixtures/synthetic.sfc");
+    let mut registry = std::sync::Arc::new(std::sync::Mutex::new(ArchiveTable::empty()));
+    let (_, doc) = Doc::parse(buf, &mut registry).expect("synthetic.sfc should parse");
+
+    let scripts = doc
+        .objects
+        .iter()
+        .flat_map(|object| object.scripts())
+        .chain(
+            doc.simple_object_pointer
+                .iter()
+                .flat_map(|simple_object| &simple_object.scripts),
+        );
+
+    // Names the flow control each statement uses, so the synthetic world is known
+    // to exercise all of it.
+    fn flow(block: &Block, seen: &mut std::collections::BTreeSet<&'static str>) {
+        for statement in block {
+            match &statement.kind {
+                StatementKind::Doif {
+                    branches,
+                    otherwise,
+                } => {
+                    seen.insert("doif");
+                    if branches.len() > 1 {
+                        seen.insert("elif");
+                    }
+                    for (_, body) in branches {
+                        flow(body, seen);
+                    }
+                    if let Some(body) = otherwise {
+                        seen.insert("else");
+                        flow(body, seen);
+                    }
+                }
+                StatementKind::Reps { body, .. } => {
+                    seen.insert("reps");
+                    flow(body, seen);
+                }
+                StatementKind::Loop { body, until } => {
+                    seen.insert(if until.is_some() { "untl" } else { "ever" });
+                    flow(body, seen);
+                }
+                StatementKind::Enum { body, .. } => {
+                    seen.insert("enum");
+                    flow(body, seen);
+                }
+                StatementKind::Subr { .. } => {
+                    seen.insert("subr");
+                }
+                StatementKind::Gsub { .. } => {
+                    seen.insert("gsub");
+                }
+                StatementKind::Command { .. } => {}
+            }
+        }
+    }
+
+    let mut seen = std::collections::BTreeSet::new();
+    for script in scripts {
+        match super::parse(script.source()) {
+            Ok(parsed) => flow(&parsed.body, &mut seen),
+            Err(diagnostics) => {
+                let rendered: Vec<_> = diagnostics
+                    .iter()
+                    .map(|diagnostic| diagnostic.render(script.source()))
+                    .collect();
+                panic!("{:?}: {}", script.classifier(), rendered.join("\n"));
+            }
+        }
+    }
+
+    assert_eq!(
+        seen.into_iter().collect::<Vec<_>>(),
+        ["doif", "elif", "else", "enum", "ever", "gsub", "reps", "subr", "untl"]
+    );
+}
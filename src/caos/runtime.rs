@@ -0,0 +1,459 @@
+//! Runs object scripts in the ECS: timers and messages start event scripts,
+//! and running scripts get a slice of the tick each.
+
+use bevy::prelude::*;
+
+use super::{
+    ast::EnumKind,
+    scriptorium::{ScriptKey, Scriptorium},
+    vm::{Context, Host, Operand, Process, Status, Value},
+};
+use crate::{
+    components::object::{Agent, AgentPart, Animation, Gallery, Pose},
+    formats::sfc::{Attributes, Objvars},
+};
+
+pub const DEACTIVATE: u16 = 0;
+pub const ACTIVATE_1: u16 = 1;
+pub const ACTIVATE_2: u16 = 2;
+pub const HIT: u16 = 3;
+pub const PICKUP: u16 = 4;
+pub const DROP: u16 = 5;
+pub const COLLISION: u16 = 6;
+pub const TIMER: u16 = 9;
+
+/// Asks an object to run its script for `event`.
+#[derive(Event, Clone, Debug)]
+pub struct ScriptEvent {
+    pub agent: Entity,
+    pub event: u16,
+    pub from: Option<Entity>,
+    pub p1: Value,
+    pub p2: Value,
+}
+
+impl ScriptEvent {
+    pub fn new(agent: Entity, event: u16) -> Self {
+        Self {
+            agent,
+            event,
+            from: None,
+            p1: Value::default(),
+            p2: Value::default(),
+        }
+    }
+}
+
+/// How many instructions scripts may run each tick, outside of `inst`.
+#[derive(Resource, Clone, Debug)]
+pub struct ScriptBudget {
+    pub per_agent: u32,
+    pub per_tick: u32,
+}
+
+impl Default for ScriptBudget {
+    fn default() -> Self {
+        Self {
+            per_agent: 5,
+            per_tick: 5000,
+        }
+    }
+}
+
+/// `obv0`-`obv9` and the rest of the object's variables.
+#[derive(Component, Clone, Debug)]
+pub struct ObjectVariables(pub Vec<Value>);
+
+impl ObjectVariables {
+    pub fn from_objvars(vars: &[Objvars]) -> Self {
+        let mut values: Vec<_> = vars
+            .iter()
+            .map(|var| Value::Integer(var.var as i32))
+            .collect();
+        values.resize(values.len().max(10), Value::default());
+        Self(values)
+    }
+}
+
+/// Runs the timer script every `rate` ticks, set with `tick`.
+#[derive(Component, Reflect, Clone, Debug, Default)]
+pub struct ScriptTimer {
+    pub rate: u32,
+    pub elapsed: u32,
+}
+
+#[derive(Component)]
+pub struct RunningScript {
+    pub event: u16,
+    pub process: Process,
+}
+
+pub fn tick_timers(
+    mut timers: Query<(Entity, &mut ScriptTimer)>,
+    mut events: EventWriter<ScriptEvent>,
+) {
+    for (agent, mut timer) in timers.iter_mut() {
+        if timer.rate == 0 {
+            continue;
+        }
+
+        timer.elapsed += 1;
+        if timer.elapsed >= timer.rate {
+            timer.elapsed = 0;
+            events.send(ScriptEvent::new(agent, TIMER));
+        }
+    }
+}
+
+/// Starts the scripts asked for this tick. Timer scripts wait for a running
+/// script to finish, other events interrupt it.
+pub fn start_scripts(
+    mut commands: Commands,
+    mut events: EventReader<ScriptEvent>,
+    scriptorium: Res<Scriptorium>,
+    agents: Query<(&Agent, Option<&RunningScript>)>,
+) {
+    for event in events.read() {
+        let Ok((agent, running)) = agents.get(event.agent) else {
+            continue;
+        };
+
+        let key = ScriptKey::for_object(&agent.classifier, event.event);
+        let Some(program) = scriptorium.find(key) else {
+            continue;
+        };
+
+        if event.event == TIMER && running.is_some() {
+            continue;
+        }
+
+        let mut process = Process::new(program.clone(), event.agent);
+        process.context.from = event.from;
+        process.p1 = event.p1;
+        process.p2 = event.p2;
+
+        commands.entity(event.agent).insert(RunningScript {
+            event: event.event,
+            process,
+        });
+    }
+}
+
+/// Runs each script for its slice of the tick, starting from a different
+/// object each tick so that none are starved when the budget runs out.
+pub fn run_scripts(world: &mut World, mut cursor: Local<usize>) {
+    let budget = world.resource::<ScriptBudget>().clone();
+    let mut remaining = budget.per_tick;
+
+    let agents: Vec<Entity> = world
+        .query_filtered::<Entity, With<RunningScript>>()
+        .iter(world)
+        .collect();
+
+    if agents.is_empty() {
+        return;
+    }
+
+    *cursor %= agents.len();
+    let start = *cursor;
+    *cursor += 1;
+
+    for &agent in agents[start..].iter().chain(&agents[..start]) {
+        if remaining == 0 {
+            break;
+        }
+
+        let Some(mut running) = world.entity_mut(agent).take::<RunningScript>() else {
+            continue;
+        };
+
+        let mut slice = budget.per_agent.min(remaining);
+        let before = slice;
+        let result = running.process.run(&mut WorldHost { world }, &mut slice);
+        remaining -= before - slice;
+
+        match result {
+            Ok(Status::Finished) => {}
+            Ok(_) => {
+                if let Ok(mut entity) = world.get_entity_mut(agent) {
+                    entity.insert(running);
+                }
+            }
+            Err(diagnostic) => {
+                let name = world
+                    .get::<Name>(agent)
+                    .map_or_else(|| agent.to_string(), |name| name.to_string());
+                warn!(
+                    "{} event {} script stopped: {}",
+                    name,
+                    running.event,
+                    diagnostic.render(running.process.program().source())
+                );
+            }
+        }
+    }
+}
+
+/// The C2 message numbers sent with `mesg writ`, and the events they run.
+fn message_event(message: i32) -> Result<u16, String> {
+    Ok(match message {
+        0 => ACTIVATE_1,
+        1 => ACTIVATE_2,
+        2 => DEACTIVATE,
+        3 => HIT,
+        4 => PICKUP,
+        5 => DROP,
+        _ => return Err(format!("unknown message {}", message)),
+    })
+}
+
+fn matches(value: i32, wanted: i32) -> bool {
+    wanted == 0 || value == wanted
+}
+
+struct WorldHost<'w> {
+    world: &'w mut World,
+}
+
+impl WorldHost<'_> {
+    fn targ(context: &Context) -> Result<Entity, String> {
+        context
+            .targ
+            .ok_or_else(|| "no target object, use `targ` first".to_string())
+    }
+
+    fn get_mut<T: Component>(&mut self, agent: Entity) -> Result<Mut<'_, T>, String> {
+        self.world
+            .get_mut::<T>(agent)
+            .ok_or_else(|| format!("object {} has no {}", agent, short_name::<T>()))
+    }
+
+    fn get<T: Component>(&self, agent: Entity) -> Result<&T, String> {
+        self.world
+            .get::<T>(agent)
+            .ok_or_else(|| format!("object {} has no {}", agent, short_name::<T>()))
+    }
+
+    /// Size of the frame the object's first part shows, zero for objects
+    /// without parts.
+    fn size(&self, agent: Entity) -> Vec2 {
+        let (Some(children), Some(gallery)) = (
+            self.world.get::<Children>(agent),
+            self.world.get::<Gallery>(agent),
+        ) else {
+            return Vec2::ZERO;
+        };
+
+        children
+            .iter()
+            .filter_map(|&child| self.world.get::<AgentPart>(child))
+            .find(|part| part.index == 0)
+            .map(|part| gallery.size(part.frame))
+            .unwrap_or(Vec2::ZERO)
+    }
+
+    fn classified(&mut self, family: i32, genus: i32, species: i32) -> Vec<Entity> {
+        self.world
+            .query::<(Entity, &Agent)>()
+            .iter(self.world)
+            .filter(|(_, agent)| {
+                matches(agent.classifier.family() as i32, family)
+                    && matches(agent.classifier.genus() as i32, genus)
+                    && matches(agent.classifier.species() as i32, species)
+            })
+            .map(|(entity, _)| entity)
+            .collect()
+    }
+}
+
+fn short_name<T>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+impl Host for WorldHost<'_> {
+    fn command(&mut self, context: &Context, name: &str, args: &[Operand]) -> Result<(), String> {
+        match name {
+            "mvto" | "mvby" => {
+                let x = args[0].to_integer()? as f32;
+                let y = args[1].to_integer()? as f32;
+                let mut transform = self.get_mut::<Transform>(Self::targ(context)?)?;
+
+                if name == "mvto" {
+                    transform.translation.x = x;
+                    transform.translation.y = -y;
+                } else {
+                    transform.translation.x += x;
+                    transform.translation.y -= y;
+                }
+            }
+            "kill" => {
+                let agent = args[0].to_agent()?.ok_or("can't kill a null object")?;
+                if let Ok(entity) = self.world.get_entity_mut(agent) {
+                    entity.despawn_recursive();
+                }
+            }
+            "tick" => {
+                let rate = args[0].to_integer()?.max(0) as u32;
+                let targ = Self::targ(context)?;
+                self.world
+                    .entity_mut(targ)
+                    .insert(ScriptTimer { rate, elapsed: 0 });
+            }
+            "pose" | "base" => {
+                let value = args[0].to_integer()?;
+                let value = u8::try_from(value).map_err(|_| format!("bad {} {}", name, value))?;
+                let mut pose = self.get_mut::<Pose>(Self::targ(context)?)?;
+
+                if name == "pose" {
+                    pose.pose = value;
+                } else {
+                    pose.base = value;
+                }
+            }
+            "anim" => {
+                let animation = Animation::parse(args[0].to_text()?)?;
+                let targ = Self::targ(context)?;
+                self.world.entity_mut(targ).insert(animation);
+            }
+            "mesg writ" | "mesg wrt+" => {
+                let agent = args[0].to_agent()?.ok_or("can't message a null object")?;
+                let mut event = ScriptEvent::new(agent, message_event(args[1].to_integer()?)?);
+                event.from = context.ownr;
+
+                // The delay of `mesg wrt+` isn't modelled yet, messages are
+                // delivered on the next tick.
+                if name == "mesg wrt+" {
+                    event.p1 = match &args[2] {
+                        Operand::Value(value) => *value,
+                        Operand::Text(_) => Value::default(),
+                    };
+                    event.p2 = match &args[3] {
+                        Operand::Value(value) => *value,
+                        Operand::Text(_) => Value::default(),
+                    };
+                }
+
+                self.world.send_event(event);
+            }
+            // Sounds aren't played yet.
+            "snde" | "sndc" | "sndl" | "sndq" | "stpc" => {}
+            "scrx" => {
+                let mut key = [0; 4];
+                for (value, arg) in key.iter_mut().zip(args) {
+                    *value = arg.to_integer()?;
+                }
+
+                let [family, genus, species, event] = key;
+                let key = match (
+                    u8::try_from(family),
+                    u8::try_from(genus),
+                    u16::try_from(species),
+                    u16::try_from(event),
+                ) {
+                    (Ok(family), Ok(genus), Ok(species), Ok(event)) => {
+                        ScriptKey::new(family, genus, species, event)
+                    }
+                    _ => return Err("script classifier is out of range".to_string()),
+                };
+
+                self.world.resource_mut::<Scriptorium>().remove(key);
+            }
+            "dbgm" => info!("{}", args[0].to_text()?),
+            "dbgv" => info!("{}", args[0].to_integer()?),
+            _ => return Err(format!("`{}` isn't supported yet", name)),
+        }
+
+        Ok(())
+    }
+
+    fn value(&mut self, context: &Context, name: &str, args: &[Value]) -> Result<Value, String> {
+        if name == "totl" {
+            let count = self
+                .classified(
+                    args[0].to_integer()?,
+                    args[1].to_integer()?,
+                    args[2].to_integer()?,
+                )
+                .len();
+            return Ok(Value::Integer(count as i32));
+        }
+
+        let targ = Self::targ(context)?;
+
+        let value = match name {
+            "attr" => self.get::<Agent>(targ)?.attributes.bits() as i32,
+            "actv" => self.get::<Agent>(targ)?.active as i32,
+            "fmly" => self.get::<Agent>(targ)?.classifier.family() as i32,
+            "gnus" => self.get::<Agent>(targ)?.classifier.genus() as i32,
+            "spcs" => self.get::<Agent>(targ)?.classifier.species() as i32,
+            "pose" => self.get::<Pose>(targ)?.pose as i32,
+            "tick" => self
+                .world
+                .get::<ScriptTimer>(targ)
+                .map_or(0, |timer| timer.rate) as i32,
+            "posl" => self.get::<Transform>(targ)?.translation.x as i32,
+            "post" => -self.get::<Transform>(targ)?.translation.y as i32,
+            "posr" => (self.get::<Transform>(targ)?.translation.x + self.size(targ).x) as i32,
+            "posb" => (-self.get::<Transform>(targ)?.translation.y + self.size(targ).y) as i32,
+            "wdth" => self.size(targ).x as i32,
+            "hght" => self.size(targ).y as i32,
+            _ => return Err(format!("`{}` isn't supported yet", name)),
+        };
+
+        Ok(Value::Integer(value))
+    }
+
+    fn set_value(&mut self, context: &Context, name: &str, value: Value) -> Result<(), String> {
+        let targ = Self::targ(context)?;
+        let value = value.to_integer()?;
+
+        match name {
+            "attr" => {
+                let attributes = Attributes::from_bits(value as u8);
+                let visibility = if attributes.invisible {
+                    Visibility::Hidden
+                } else {
+                    Visibility::Inherited
+                };
+
+                self.get_mut::<Agent>(targ)?.attributes = attributes;
+                self.world.entity_mut(targ).insert(visibility);
+            }
+            "actv" => self.get_mut::<Agent>(targ)?.active = value as u8,
+            _ => return Err(format!("setting `{}` isn't supported yet", name)),
+        }
+
+        Ok(())
+    }
+
+    fn obv(&mut self, agent: Entity, index: u8) -> Result<Value, String> {
+        Ok(self.get::<ObjectVariables>(agent)?.0[index as usize])
+    }
+
+    fn set_obv(&mut self, agent: Entity, index: u8, value: Value) -> Result<(), String> {
+        self.get_mut::<ObjectVariables>(agent)?.0[index as usize] = value;
+        Ok(())
+    }
+
+    fn enumerate(
+        &mut self,
+        _context: &Context,
+        kind: EnumKind,
+        family: i32,
+        genus: i32,
+        species: i32,
+    ) -> Result<Vec<Entity>, String> {
+        match kind {
+            EnumKind::All => Ok(self.classified(family, genus, species)),
+            EnumKind::Seen => Err("`esee` isn't supported yet".to_string()),
+            EnumKind::Touching => Err("`etch` isn't supported yet".to_string()),
+        }
+    }
+
+    fn animating(&mut self, agent: Entity) -> bool {
+        self.world
+            .get::<Animation>(agent)
+            .is_some_and(|animation| !animation.finished())
+    }
+}
@@ -0,0 +1,479 @@
+//! The scriptorium holds every event script in the world, keyed by the
+//! classifier of the objects it's for and the event that runs it.
+
+use std::{collections::HashMap, sync::Arc};
+
+use bevy::prelude::*;
+
+use super::{
+    lexer::{self, Token, TokenKind},
+    vm::Program,
+    Diagnostic, Span,
+};
+use crate::formats::{
+    sfc::{self, Classifier, Doc, Script},
+    WorldHandle,
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ScriptKey {
+    pub family: u8,
+    pub genus: u8,
+    pub species: u16,
+    pub event: u16,
+}
+
+impl ScriptKey {
+    pub fn new(family: u8, genus: u8, species: u16, event: u16) -> Self {
+        Self {
+            family,
+            genus,
+            species,
+            event,
+        }
+    }
+
+    /// The key of the script an object with `classifier` runs for `event`.
+    pub fn for_object(classifier: &Classifier, event: u16) -> Self {
+        Self::new(
+            classifier.family(),
+            classifier.genus(),
+            classifier.species(),
+            event,
+        )
+    }
+
+    /// Keys tried in turn when looking up a script, as the original game
+    /// does: the exact classifier, then any species of the genus, then any
+    /// genus of the family, then any object at all.
+    pub fn fallbacks(self) -> [Self; 4] {
+        [
+            self,
+            Self::new(self.family, self.genus, 0, self.event),
+            Self::new(self.family, 0, 0, self.event),
+            Self::new(0, 0, 0, self.event),
+        ]
+    }
+}
+
+impl From<&Classifier> for ScriptKey {
+    fn from(classifier: &Classifier) -> Self {
+        Self::for_object(classifier, classifier.event())
+    }
+}
+
+/// Scripts installed from an injected file with [`Scriptorium::inject`].
+#[derive(Debug, Default)]
+pub struct Injection {
+    /// Keys of the `scrp` blocks installed, in file order.
+    pub installed: Vec<ScriptKey>,
+    /// Keys removed with `scrx`, whether or not a script was installed.
+    pub removed: Vec<ScriptKey>,
+    /// The commands outside of any `scrp` block, for the caller to run once.
+    pub install: Option<Program>,
+}
+
+#[derive(Resource, Default)]
+pub struct Scriptorium {
+    scripts: HashMap<ScriptKey, Arc<Program>>,
+}
+
+impl Scriptorium {
+    pub fn len(&self) -> usize {
+        self.scripts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scripts.is_empty()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &ScriptKey> {
+        self.scripts.keys()
+    }
+
+    /// The script installed for exactly `key`.
+    pub fn get(&self, key: ScriptKey) -> Option<&Arc<Program>> {
+        self.scripts.get(&key)
+    }
+
+    /// The script an object runs for an event, falling back to scripts for
+    /// its genus, family and then every object.
+    pub fn find(&self, key: ScriptKey) -> Option<&Arc<Program>> {
+        key.fallbacks()
+            .into_iter()
+            .find_map(|key| self.scripts.get(&key))
+    }
+
+    /// Installs a compiled script like `scrp`, replacing any with the same
+    /// key.
+    pub fn install(&mut self, key: ScriptKey, program: Program) -> Option<Arc<Program>> {
+        self.scripts.insert(key, Arc::new(program))
+    }
+
+    /// Compiles and installs `source`. Nothing is replaced if it has errors.
+    pub fn install_source(&mut self, key: ScriptKey, source: &str) -> Result<(), Vec<Diagnostic>> {
+        self.install(key, Program::from_source(source)?);
+        Ok(())
+    }
+
+    /// Removes the script installed for exactly `key`, like `scrx`.
+    pub fn remove(&mut self, key: ScriptKey) -> Option<Arc<Program>> {
+        self.scripts.remove(&key)
+    }
+
+    /// Installs the scripts stored with a world's objects and scenery,
+    /// logging any that don't parse. Returns the number installed.
+    pub fn install_world(&mut self, doc: &Doc) -> usize {
+        let scripts = doc
+            .objects
+            .iter()
+            .flat_map(|object| object.scripts())
+            .chain(
+                doc.simple_object_pointer
+                    .iter()
+                    .flat_map(|simple_object| &simple_object.scripts),
+            );
+
+        let mut installed = 0;
+
+        for script in scripts {
+            match self.install_script(script) {
+                Ok(()) => installed += 1,
+                Err(diagnostics) => {
+                    for diagnostic in diagnostics {
+                        warn!(
+                            "script {:?}: {}",
+                            ScriptKey::from(script.classifier()),
+                            diagnostic.render(script.source())
+                        );
+                    }
+                }
+            }
+        }
+
+        installed
+    }
+
+    pub fn install_script(&mut self, script: &Script) -> Result<(), Vec<Diagnostic>> {
+        self.install_source(script.classifier().into(), script.source())
+    }
+
+    /// Installs the scripts in an injected file. Each `scrp f g s e` block
+    /// runs to its `endm`, `scrx f g s e` removes a script, and anything
+    /// after `rscr` is the removal script, which is skipped. The file is
+    /// checked before anything changes, so a file with errors installs
+    /// nothing.
+    pub fn inject(&mut self, source: &str) -> Result<Injection, Vec<Diagnostic>> {
+        let (tokens, mut diagnostics) = lexer::tokenize(source);
+
+        let mut blocks = Vec::new();
+        let mut removed = Vec::new();
+        // The install script, with everything else blanked out so that spans
+        // still point into `source`.
+        let mut install = source.to_string();
+        let mut has_install = false;
+
+        let mut index = 0;
+        while index < tokens.len() {
+            let token = &tokens[index];
+
+            match &token.kind {
+                TokenKind::Word(word) if word == "scrp" || word == "scrx" => {
+                    let key = match script_key(&tokens[index + 1..], token.span) {
+                        Ok(key) => key,
+                        Err(diagnostic) => {
+                            diagnostics.push(diagnostic);
+                            index += 1;
+                            while matches!(
+                                tokens.get(index),
+                                Some(Token {
+                                    kind: TokenKind::Integer(_),
+                                    ..
+                                })
+                            ) {
+                                blank(&mut install, tokens[index].span);
+                                index += 1;
+                            }
+                            blank(&mut install, token.span);
+                            continue;
+                        }
+                    };
+                    index += 5;
+
+                    let mut end = tokens.get(index - 1).map_or(token.span, |t| t.span).end;
+
+                    if word == "scrx" {
+                        removed.push(key);
+                    } else {
+                        let body_start = tokens.get(index).map_or(source.len(), |t| t.span.start);
+
+                        match tokens[index..].iter().position(is_endm) {
+                            Some(length) => {
+                                end = tokens[index + length].span.end;
+                                index += length + 1;
+                            }
+                            None => {
+                                diagnostics
+                                    .push(Diagnostic::new("`scrp` is missing `endm`", token.span));
+                                end = source.len();
+                                index = tokens.len();
+                            }
+                        }
+
+                        blocks.push((key, Span::new(body_start, end)));
+                    }
+
+                    blank(&mut install, Span::new(token.span.start, end));
+                }
+                TokenKind::Word(word) if word == "rscr" => {
+                    blank(&mut install, Span::new(token.span.start, source.len()));
+                    break;
+                }
+                _ => {
+                    has_install = true;
+                    index += 1;
+                }
+            }
+        }
+
+        let mut programs = Vec::new();
+
+        for (key, span) in blocks {
+            match Program::from_source(&source[span.start..span.end]) {
+                Ok(program) => programs.push((key, program)),
+                Err(errors) => diagnostics.extend(errors.into_iter().map(|diagnostic| {
+                    Diagnostic::new(
+                        diagnostic.message,
+                        Span::new(
+                            diagnostic.span.start + span.start,
+                            diagnostic.span.end + span.start,
+                        ),
+                    )
+                })),
+            }
+        }
+
+        let install = if has_install {
+            match Program::from_source(&install) {
+                Ok(program) => Some(program),
+                Err(errors) => {
+                    diagnostics.extend(errors);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        if !diagnostics.is_empty() {
+            diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+            return Err(diagnostics);
+        }
+
+        let mut injection = Injection {
+            install,
+            ..Default::default()
+        };
+
+        for key in removed {
+            self.remove(key);
+            injection.removed.push(key);
+        }
+
+        for (key, program) in programs {
+            self.install(key, program);
+            injection.installed.push(key);
+        }
+
+        Ok(injection)
+    }
+}
+
+fn is_endm(token: &Token) -> bool {
+    matches!(&token.kind, TokenKind::Word(word) if word == "endm")
+}
+
+/// Reads the four integers after `scrp` or `scrx`.
+fn script_key(tokens: &[Token], span: Span) -> Result<ScriptKey, Diagnostic> {
+    let mut values = [0; 4];
+
+    for (i, value) in values.iter_mut().enumerate() {
+        match tokens.get(i) {
+            Some(Token {
+                kind: TokenKind::Integer(integer),
+                ..
+            }) => *value = *integer,
+            Some(token) => {
+                return Err(Diagnostic::new(
+                    "expected an integer for the script classifier",
+                    token.span,
+                ))
+            }
+            None => {
+                return Err(Diagnostic::new(
+                    "expected a family, genus, species and event",
+                    span,
+                ))
+            }
+        }
+    }
+
+    let [family, genus, species, event] = values;
+    let span = span.to(tokens[3].span);
+
+    match (
+        u8::try_from(family),
+        u8::try_from(genus),
+        u16::try_from(species),
+        u16::try_from(event),
+    ) {
+        (Ok(family), Ok(genus), Ok(species), Ok(event)) => {
+            Ok(ScriptKey::new(family, genus, species, event))
+        }
+        _ => Err(Diagnostic::new("script classifier is out of range", span)),
+    }
+}
+
+/// Replaces the text in `span` with spaces, keeping line breaks.
+fn blank(text: &mut String, span: Span) {
+    let blanked: String = text[span.start..span.end]
+        .chars()
+        .map(|c| if c == '\n' { '\n' } else { ' ' })
+        .collect();
+    text.replace_range(span.start..span.end, &blanked);
+}
+
+/// Installs the scripts of the current world whenever it's loaded, over any
+/// installed before.
+pub fn install_world_scripts(
+    mut events: EventReader<AssetEvent<sfc::World>>,
+    world_handle: Res<WorldHandle>,
+    worlds: Res<Assets<sfc::World>>,
+    mut scriptorium: ResMut<Scriptorium>,
+) {
+    let reloaded = events.read().any(|event| match event {
+        AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } => {
+            *id == world_handle.0.id()
+        }
+        _ => false,
+    });
+
+    if !reloaded {
+        return;
+    }
+
+    let Some(world) = worlds.get(&world_handle.0) else {
+        return;
+    };
+
+    let installed = scriptorium.install_world(&world.doc);
+    info!("installed {} scripts from the world", installed);
+}
+
+#[test]
+fn test_scriptorium_find() {
+    let mut scriptorium = Scriptorium::default();
+    let program = || Program::from_source("endm").unwrap();
+
+    scriptorium.install(ScriptKey::new(2, 5, 0, 9), program());
+    scriptorium.install(ScriptKey::new(2, 5, 7, 9), program());
+    scriptorium.install(ScriptKey::new(0, 0, 0, 1), program());
+
+    // The key of the script found, rather than the script itself.
+    fn find(scriptorium: &Scriptorium, key: ScriptKey) -> Option<ScriptKey> {
+        let found = scriptorium.find(key)?;
+        scriptorium
+            .keys()
+            .find(|&&candidate| Arc::ptr_eq(scriptorium.get(candidate).unwrap(), found))
+            .copied()
+    }
+
+    assert_eq!(
+        find(&scriptorium, ScriptKey::new(2, 5, 7, 9)),
+        Some(ScriptKey::new(2, 5, 7, 9))
+    );
+    assert_eq!(
+        find(&scriptorium, ScriptKey::new(2, 5, 8, 9)),
+        Some(ScriptKey::new(2, 5, 0, 9))
+    );
+    assert_eq!(find(&scriptorium, ScriptKey::new(2, 6, 8, 9)), None);
+    assert_eq!(
+        find(&scriptorium, ScriptKey::new(3, 1, 1, 1)),
+        Some(ScriptKey::new(0, 0, 0, 1))
+    );
+
+    assert!(scriptorium.remove(ScriptKey::new(2, 5, 7, 9)).is_some());
+    assert_eq!(
+        find(&scriptorium, ScriptKey::new(2, 5, 7, 9)),
+        Some(ScriptKey::new(2, 5, 0, 9))
+    );
+
+    let classifier = Classifier::new(2, 5, 7, 9);
+    assert_eq!(ScriptKey::from(&classifier), ScriptKey::new(2, 5, 7, 9));
+}
+
+#[test]
+fn test_scriptorium_inject() {
+    let mut scriptorium = Scriptorium::default();
+    scriptorium
+        .install_source(ScriptKey::new(2, 5, 1, 2), "endm")
+        .unwrap();
+
+    let source = "inst\n\
+                  scrp 2 5 1 9\nsetv obv0 1\nendm\n\
+                  scrx 2 5 1 2\n\
+                  new: simp 2 5 1 toy_ 1 0 100\n\
+                  endm\n\
+                  rscr\nscrx 2 5 1 9\n";
+    let injection = scriptorium.inject(source).expect("file should inject");
+
+    assert_eq!(injection.installed, [ScriptKey::new(2, 5, 1, 9)]);
+    assert_eq!(injection.removed, [ScriptKey::new(2, 5, 1, 2)]);
+    assert!(injection.install.is_some());
+    assert_eq!(
+        scriptorium
+            .get(ScriptKey::new(2, 5, 1, 9))
+            .unwrap()
+            .source(),
+        "setv obv0 1\nendm"
+    );
+    assert!(scriptorium.get(ScriptKey::new(2, 5, 1, 2)).is_none());
+
+    let source = "scrp 2 5 1 3\nsetv obv0\nendm\nscrp 2 5 300\n";
+    let diagnostics = scriptorium.inject(source).expect_err("file has errors");
+    let rendered: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.render(source))
+        .collect();
+
+    assert_eq!(
+        rendered,
+        [
+            "3:1: unknown value `endm` for `setv`\nendm\n^^^^",
+            "4:1: expected a family, genus, species and event\nscrp 2 5 300\n^^^^",
+        ]
+    );
+    assert!(scriptorium.get(ScriptKey::new(2, 5, 1, 3)).is_none());
+}
+
+#[test]
+fn test_scriptorium_install_world() {
+    use crate::formats::sfc::ArchiveTable;
+
+    let buf = include_bytes!("../../assets/fixtures/synthetic.sfc");
+    let mut registry = Arc::new(std::sync::Mutex::new(ArchiveTable::empty()));
+    let (_, doc) = Doc::parse(buf, &mut registry).expect("synthetic.sfc should parse");
+
+    let mut scriptorium = Scriptorium::default();
+    let installed = scriptorium.install_world(&doc);
+
+    assert!(installed > 0);
+    assert!(!scriptorium.is_empty());
+
+    for object in &doc.objects {
+        for script in object.scripts() {
+            let key = ScriptKey::from(script.classifier());
+            assert!(scriptorium.find(key).is_some(), "{:?} installed", key);
+        }
+    }
+}
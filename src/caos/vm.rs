@@ -0,0 +1,826 @@
+//! Runs parsed scripts. A script is compiled into a flat list of operations
+//! so that a [`Process`] can stop after any instruction, when it runs out of
+//! its slice of the tick, waits, or waits for an animation to finish, and
+//! carry on from the same place on a later tick.
+//!
+//! Everything that touches the world goes through [`Host`], so the VM can be
+//! run against the ECS or a stand-in in tests.
+
+use std::{collections::HashMap, sync::Arc};
+
+use bevy::ecs::entity::Entity;
+use rand::Rng;
+
+use super::{
+    ast::{Block, CompareOp, Comparison, Condition, EnumKind, Expr, ExprKind, Logic, Script},
+    ast::{Statement, StatementKind},
+    Diagnostic, Span,
+};
+
+/// Instructions a script may run in `inst` mode before it is stopped, to
+/// catch scripts that would otherwise hang the game.
+pub const MAX_INSTANT_INSTRUCTIONS: u32 = 100_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Value {
+    Integer(i32),
+    Agent(Entity),
+}
+
+impl Default for Value {
+    fn default() -> Self {
+        Value::Integer(0)
+    }
+}
+
+impl Value {
+    /// An object, or 0 for none as CAOS has no separate null value.
+    pub fn agent(agent: Option<Entity>) -> Self {
+        agent.map_or(Value::Integer(0), Value::Agent)
+    }
+
+    pub fn to_integer(self) -> Result<i32, String> {
+        match self {
+            Value::Integer(value) => Ok(value),
+            Value::Agent(_) => Err("expected a number, found an object".to_string()),
+        }
+    }
+
+    pub fn to_agent(self) -> Result<Option<Entity>, String> {
+        match self {
+            Value::Agent(agent) => Ok(Some(agent)),
+            Value::Integer(0) => Ok(None),
+            Value::Integer(value) => Err(format!("expected an object, found {}", value)),
+        }
+    }
+}
+
+/// An argument passed to a host command.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Operand {
+    Value(Value),
+    /// A bracketed string or bare token, e.g. an animation or sound name.
+    Text(String),
+}
+
+impl Operand {
+    pub fn to_integer(&self) -> Result<i32, String> {
+        match self {
+            Operand::Value(value) => value.to_integer(),
+            Operand::Text(text) => Err(format!("expected a number, found `{}`", text)),
+        }
+    }
+
+    pub fn to_agent(&self) -> Result<Option<Entity>, String> {
+        match self {
+            Operand::Value(value) => value.to_agent(),
+            Operand::Text(text) => Err(format!("expected an object, found `{}`", text)),
+        }
+    }
+
+    pub fn to_text(&self) -> Result<&str, String> {
+        match self {
+            Operand::Text(text) => Ok(text),
+            Operand::Value(_) => Err("expected a string".to_string()),
+        }
+    }
+}
+
+/// The objects a script is running for and acting on.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Context {
+    /// The object the script belongs to.
+    pub ownr: Option<Entity>,
+    /// The object commands act on, `ownr` unless changed with `targ`.
+    pub targ: Option<Entity>,
+    /// The object that sent the message the script is running for.
+    pub from: Option<Entity>,
+    /// The object the owner's creature is paying attention to.
+    pub it: Option<Entity>,
+}
+
+/// Access to the world for the commands and values the VM doesn't handle
+/// itself. Errors are messages, the VM adds the location in the script.
+pub trait Host {
+    fn command(&mut self, context: &Context, name: &str, args: &[Operand]) -> Result<(), String>;
+
+    fn value(&mut self, context: &Context, name: &str, args: &[Value]) -> Result<Value, String>;
+
+    fn set_value(&mut self, context: &Context, name: &str, value: Value) -> Result<(), String>;
+
+    fn obv(&mut self, agent: Entity, index: u8) -> Result<Value, String>;
+
+    fn set_obv(&mut self, agent: Entity, index: u8, value: Value) -> Result<(), String>;
+
+    /// Objects matching a classifier, where 0 matches anything.
+    fn enumerate(
+        &mut self,
+        context: &Context,
+        kind: EnumKind,
+        family: i32,
+        genus: i32,
+        species: i32,
+    ) -> Result<Vec<Entity>, String>;
+
+    /// Whether `agent` is still playing an animation, for `over`.
+    fn animating(&mut self, agent: Entity) -> bool;
+}
+
+#[derive(Clone, Debug)]
+enum Op {
+    Command {
+        name: &'static str,
+        args: Vec<Expr>,
+    },
+    Jump(usize),
+    JumpUnless(Condition, usize),
+    Reps {
+        count: Expr,
+        end: usize,
+    },
+    Repe {
+        start: usize,
+    },
+    Until {
+        condition: Condition,
+        start: usize,
+    },
+    Enum {
+        kind: EnumKind,
+        family: Expr,
+        genus: Expr,
+        species: Expr,
+        end: usize,
+    },
+    Next {
+        start: usize,
+    },
+    Gsub(String),
+    /// Execution reaching a subroutine other than through `gsub` ends the
+    /// script.
+    Subr,
+}
+
+/// A script compiled for the VM, shared by every process running it.
+#[derive(Debug)]
+pub struct Program {
+    ops: Vec<(Op, Span)>,
+    labels: HashMap<String, usize>,
+    source: String,
+}
+
+impl Program {
+    pub fn compile(script: &Script, source: &str) -> Self {
+        let mut program = Self {
+            ops: Vec::new(),
+            labels: HashMap::new(),
+            source: source.to_string(),
+        };
+        program.block(&script.body);
+        program
+    }
+
+    /// Parses and compiles `source`.
+    pub fn from_source(source: &str) -> Result<Self, Vec<Diagnostic>> {
+        super::parse(source).map(|script| Self::compile(&script, source))
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    fn push(&mut self, op: Op, span: Span) -> usize {
+        self.ops.push((op, span));
+        self.ops.len() - 1
+    }
+
+    fn patch(&mut self, index: usize) {
+        let target = self.ops.len();
+        match &mut self.ops[index].0 {
+            Op::Jump(end)
+            | Op::JumpUnless(_, end)
+            | Op::Reps { end, .. }
+            | Op::Enum { end, .. } => *end = target,
+            _ => unreachable!(),
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        for statement in block {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        let span = statement.span;
+
+        match &statement.kind {
+            StatementKind::Command { name, args } => {
+                self.push(
+                    Op::Command {
+                        name,
+                        args: args.clone(),
+                    },
+                    span,
+                );
+            }
+            StatementKind::Doif {
+                branches,
+                otherwise,
+            } => {
+                let mut ends = Vec::new();
+
+                for (index, (condition, body)) in branches.iter().enumerate() {
+                    let skip = self.push(Op::JumpUnless(condition.clone(), 0), condition.span);
+                    self.block(body);
+
+                    if index + 1 < branches.len() || otherwise.is_some() {
+                        ends.push(self.push(Op::Jump(0), span));
+                    }
+                    self.patch(skip);
+                }
+
+                if let Some(otherwise) = otherwise {
+                    self.block(otherwise);
+                }
+
+                for end in ends {
+                    self.patch(end);
+                }
+            }
+            StatementKind::Reps { count, body } => {
+                let reps = self.push(
+                    Op::Reps {
+                        count: count.clone(),
+                        end: 0,
+                    },
+                    span,
+                );
+                self.block(body);
+                self.push(Op::Repe { start: reps + 1 }, span);
+                self.patch(reps);
+            }
+            StatementKind::Loop { body, until } => {
+                let start = self.ops.len();
+                self.block(body);
+                match until {
+                    Some(condition) => self.push(
+                        Op::Until {
+                            condition: condition.clone(),
+                            start,
+                        },
+                        condition.span,
+                    ),
+                    None => self.push(Op::Jump(start), span),
+                };
+            }
+            StatementKind::Enum {
+                kind,
+                family,
+                genus,
+                species,
+                body,
+            } => {
+                let enumerate = self.push(
+                    Op::Enum {
+                        kind: *kind,
+                        family: family.clone(),
+                        genus: genus.clone(),
+                        species: species.clone(),
+                        end: 0,
+                    },
+                    span,
+                );
+                self.block(body);
+                self.push(
+                    Op::Next {
+                        start: enumerate + 1,
+                    },
+                    span,
+                );
+                self.patch(enumerate);
+            }
+            StatementKind::Subr { label } => {
+                let index = self.push(Op::Subr, span);
+                self.labels.insert(label.clone(), index + 1);
+            }
+            StatementKind::Gsub { label } => {
+                self.push(Op::Gsub(label.clone()), span);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    /// Used up its instructions for this tick.
+    Running,
+    /// Waiting on `wait` or `over`.
+    Blocked,
+    Finished,
+}
+
+#[derive(Clone, Debug)]
+struct EnumFrame {
+    remaining: Vec<Entity>,
+}
+
+/// A script running for an object.
+#[derive(Clone, Debug)]
+pub struct Process {
+    program: Arc<Program>,
+    pc: usize,
+    pub context: Context,
+    pub p1: Value,
+    pub p2: Value,
+    vars: [Value; 10],
+    calls: Vec<usize>,
+    reps: Vec<i32>,
+    enums: Vec<EnumFrame>,
+    instant: bool,
+    wait: u32,
+    over: bool,
+    finished: bool,
+}
+
+impl Process {
+    pub fn new(program: Arc<Program>, ownr: Entity) -> Self {
+        Self {
+            program,
+            pc: 0,
+            context: Context {
+                ownr: Some(ownr),
+                targ: Some(ownr),
+                ..Default::default()
+            },
+            p1: Value::default(),
+            p2: Value::default(),
+            vars: Default::default(),
+            calls: Vec::new(),
+            reps: Vec::new(),
+            enums: Vec::new(),
+            instant: false,
+            wait: 0,
+            over: false,
+            finished: false,
+        }
+    }
+
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    pub fn var(&self, index: u8) -> Value {
+        self.vars[index as usize]
+    }
+
+    /// Runs until the script finishes, blocks, or has run `budget`
+    /// instructions. Instructions run in `inst` mode don't count against the
+    /// budget.
+    pub fn run(&mut self, host: &mut impl Host, budget: &mut u32) -> Result<Status, Diagnostic> {
+        if self.wait > 0 {
+            self.wait -= 1;
+            return Ok(Status::Blocked);
+        }
+
+        if self.over {
+            match self.context.targ {
+                Some(targ) if host.animating(targ) => return Ok(Status::Blocked),
+                _ => self.over = false,
+            }
+        }
+
+        let mut instant_instructions = 0;
+
+        loop {
+            if self.finished || self.pc >= self.program.ops.len() {
+                self.finished = true;
+                return Ok(Status::Finished);
+            }
+
+            if self.instant {
+                instant_instructions += 1;
+                if instant_instructions > MAX_INSTANT_INSTRUCTIONS {
+                    return Err(Diagnostic::new(
+                        "script ran too long without `slow`",
+                        self.program.ops[self.pc].1,
+                    ));
+                }
+            } else if *budget == 0 {
+                return Ok(Status::Running);
+            } else {
+                *budget -= 1;
+            }
+
+            let program = self.program.clone();
+            let (op, span) = &program.ops[self.pc];
+            self.pc += 1;
+
+            self.execute(op, host)
+                .map_err(|message| Diagnostic::new(message, *span))?;
+
+            if self.wait > 0 || self.over {
+                return Ok(Status::Blocked);
+            }
+        }
+    }
+
+    fn execute(&mut self, op: &Op, host: &mut impl Host) -> Result<(), String> {
+        match op {
+            Op::Command { name, args } => self.command(name, args, host)?,
+            Op::Jump(target) => self.pc = *target,
+            Op::JumpUnless(condition, target) => {
+                if !self.condition(condition, host)? {
+                    self.pc = *target;
+                }
+            }
+            Op::Reps { count, end } => {
+                let count = self.eval(count, host)?.to_integer()?;
+                if count > 0 {
+                    self.reps.push(count);
+                } else {
+                    self.pc = *end;
+                }
+            }
+            Op::Repe { start } => {
+                let count = self.reps.last_mut().ok_or("`repe` without `reps`")?;
+                *count -= 1;
+                if *count > 0 {
+                    self.pc = *start;
+                } else {
+                    self.reps.pop();
+                }
+            }
+            Op::Until { condition, start } => {
+                if !self.condition(condition, host)? {
+                    self.pc = *start;
+                }
+            }
+            Op::Enum {
+                kind,
+                family,
+                genus,
+                species,
+                end,
+            } => {
+                let family = self.eval(family, host)?.to_integer()?;
+                let genus = self.eval(genus, host)?.to_integer()?;
+                let species = self.eval(species, host)?.to_integer()?;
+
+                let mut remaining = host.enumerate(&self.context, *kind, family, genus, species)?;
+                remaining.reverse();
+
+                match remaining.pop() {
+                    Some(first) => {
+                        self.context.targ = Some(first);
+                        self.enums.push(EnumFrame { remaining });
+                    }
+                    None => self.pc = *end,
+                }
+            }
+            Op::Next { start } => {
+                let frame = self.enums.last_mut().ok_or("`next` without `enum`")?;
+                match frame.remaining.pop() {
+                    Some(agent) => {
+                        self.context.targ = Some(agent);
+                        self.pc = *start;
+                    }
+                    None => {
+                        self.enums.pop();
+                        self.context.targ = self.context.ownr;
+                    }
+                }
+            }
+            Op::Gsub(label) => {
+                let target = *self
+                    .program
+                    .labels
+                    .get(label)
+                    .ok_or_else(|| format!("no `subr` named `{}`", label))?;
+                self.calls.push(self.pc);
+                self.pc = target;
+            }
+            Op::Subr => self.finished = true,
+        }
+
+        Ok(())
+    }
+
+    fn command(&mut self, name: &str, args: &[Expr], host: &mut impl Host) -> Result<(), String> {
+        match name {
+            "inst" => self.instant = true,
+            "slow" => self.instant = false,
+            "stop" | "endm" => self.finished = true,
+            "over" => self.over = true,
+            "retn" => self.pc = self.calls.pop().ok_or("`retn` without `gsub`")?,
+            "wait" => {
+                let ticks = self.eval(&args[0], host)?.to_integer()?;
+                self.wait = ticks.max(0) as u32;
+            }
+            "targ" => self.context.targ = self.eval(&args[0], host)?.to_agent()?,
+            "setv" | "addv" | "subv" | "mulv" | "divv" | "modv" | "andv" | "orv" => {
+                let right = self.eval(&args[1], host)?;
+
+                let value = if name == "setv" {
+                    right
+                } else {
+                    let left = self.eval(&args[0], host)?.to_integer()?;
+                    let right = right.to_integer()?;
+
+                    Value::Integer(match name {
+                        "addv" => left.wrapping_add(right),
+                        "subv" => left.wrapping_sub(right),
+                        "mulv" => left.wrapping_mul(right),
+                        "divv" => left.checked_div(right).ok_or("division by zero")?,
+                        "modv" => left.checked_rem(right).ok_or("division by zero")?,
+                        "andv" => left & right,
+                        _ => left | right,
+                    })
+                };
+
+                self.assign(&args[0], value, host)?;
+            }
+            "negv" => {
+                let value = self.eval(&args[0], host)?.to_integer()?;
+                self.assign(&args[0], Value::Integer(value.wrapping_neg()), host)?;
+            }
+            "rndv" => {
+                let min = self.eval(&args[1], host)?.to_integer()?;
+                let max = self.eval(&args[2], host)?.to_integer()?;
+                self.assign(&args[0], Value::Integer(random(min, max)), host)?;
+            }
+            _ => {
+                let args = args
+                    .iter()
+                    .map(|arg| match &arg.kind {
+                        ExprKind::String(text) | ExprKind::Token(text) => {
+                            Ok(Operand::Text(text.clone()))
+                        }
+                        _ => self.eval(arg, host).map(Operand::Value),
+                    })
+                    .collect::<Result<Vec<_>, String>>()?;
+
+                host.command(&self.context, name, &args)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn targ(&self) -> Result<Entity, String> {
+        self.context
+            .targ
+            .ok_or_else(|| "no target object, use `targ` first".to_string())
+    }
+
+    fn eval(&mut self, expr: &Expr, host: &mut impl Host) -> Result<Value, String> {
+        match &expr.kind {
+            ExprKind::Integer(value) => Ok(Value::Integer(*value)),
+            ExprKind::String(text) | ExprKind::Token(text) => {
+                Err(format!("expected a value, found `{}`", text))
+            }
+            ExprKind::Var(index) => Ok(self.vars[*index as usize]),
+            ExprKind::Obv(index) => host.obv(self.targ()?, *index),
+            ExprKind::Value { name, args } => match *name {
+                "targ" => Ok(Value::agent(self.context.targ)),
+                "ownr" => Ok(Value::agent(self.context.ownr)),
+                "from" => Ok(Value::agent(self.context.from)),
+                "_it_" => Ok(Value::agent(self.context.it)),
+                "_p1_" => Ok(self.p1),
+                "_p2_" => Ok(self.p2),
+                "rand" => {
+                    let min = self.eval(&args[0], host)?.to_integer()?;
+                    let max = self.eval(&args[1], host)?.to_integer()?;
+                    Ok(Value::Integer(random(min, max)))
+                }
+                _ => {
+                    let args = args
+                        .iter()
+                        .map(|arg| self.eval(arg, host))
+                        .collect::<Result<Vec<_>, String>>()?;
+                    host.value(&self.context, name, &args)
+                }
+            },
+        }
+    }
+
+    fn assign(&mut self, target: &Expr, value: Value, host: &mut impl Host) -> Result<(), String> {
+        match &target.kind {
+            ExprKind::Var(index) => {
+                self.vars[*index as usize] = value;
+                Ok(())
+            }
+            ExprKind::Obv(index) => host.set_obv(self.targ()?, *index, value),
+            ExprKind::Value { name, .. } => host.set_value(&self.context, name, value),
+            _ => Err("expected a variable".to_string()),
+        }
+    }
+
+    fn condition(&mut self, condition: &Condition, host: &mut impl Host) -> Result<bool, String> {
+        // Every comparison is evaluated, CAOS doesn't short-circuit.
+        let mut result = self.comparison(&condition.first, host)?;
+
+        for (logic, comparison) in &condition.rest {
+            let next = self.comparison(comparison, host)?;
+            result = match logic {
+                Logic::And => result && next,
+                Logic::Or => result || next,
+            };
+        }
+
+        Ok(result)
+    }
+
+    fn comparison(
+        &mut self,
+        comparison: &Comparison,
+        host: &mut impl Host,
+    ) -> Result<bool, String> {
+        let left = self.eval(&comparison.left, host)?;
+        let right = self.eval(&comparison.right, host)?;
+
+        match comparison.op {
+            CompareOp::Eq => return Ok(left == right),
+            CompareOp::Ne => return Ok(left != right),
+            _ => {}
+        }
+
+        let left = left.to_integer()?;
+        let right = right.to_integer()?;
+
+        Ok(match comparison.op {
+            CompareOp::Lt => left < right,
+            CompareOp::Gt => left > right,
+            CompareOp::Le => left <= right,
+            CompareOp::Ge => left >= right,
+            CompareOp::BitsTrue => left & right == right,
+            CompareOp::BitsFalse => left & right == 0,
+            CompareOp::Eq | CompareOp::Ne => unreachable!(),
+        })
+    }
+}
+
+/// A random number between `min` and `max` inclusive, in either order.
+fn random(min: i32, max: i32) -> i32 {
+    rand::thread_rng().gen_range(min.min(max)..=min.max(max))
+}
+
+#[cfg(test)]
+#[derive(Default)]
+struct TestHost {
+    obvs: HashMap<(Entity, u8), Value>,
+    commands: Vec<String>,
+    agents: Vec<Entity>,
+    animating: bool,
+}
+
+#[cfg(test)]
+impl Host for TestHost {
+    fn command(&mut self, _context: &Context, name: &str, args: &[Operand]) -> Result<(), String> {
+        let args: Vec<_> = args
+            .iter()
+            .map(|arg| match arg {
+                Operand::Value(Value::Integer(value)) => value.to_string(),
+                Operand::Value(Value::Agent(agent)) => format!("{}", agent),
+                Operand::Text(text) => text.clone(),
+            })
+            .collect();
+        self.commands.push(format!("{} {}", name, args.join(" ")));
+        Ok(())
+    }
+
+    fn value(&mut self, _context: &Context, name: &str, _args: &[Value]) -> Result<Value, String> {
+        Err(format!("`{}` isn't supported", name))
+    }
+
+    fn set_value(&mut self, _context: &Context, name: &str, _value: Value) -> Result<(), String> {
+        Err(format!("`{}` isn't supported", name))
+    }
+
+    fn obv(&mut self, agent: Entity, index: u8) -> Result<Value, String> {
+        Ok(self.obvs.get(&(agent, index)).copied().unwrap_or_default())
+    }
+
+    fn set_obv(&mut self, agent: Entity, index: u8, value: Value) -> Result<(), String> {
+        self.obvs.insert((agent, index), value);
+        Ok(())
+    }
+
+    fn enumerate(
+        &mut self,
+        _context: &Context,
+        _kind: EnumKind,
+        _family: i32,
+        _genus: i32,
+        _species: i32,
+    ) -> Result<Vec<Entity>, String> {
+        Ok(self.agents.clone())
+    }
+
+    fn animating(&mut self, _agent: Entity) -> bool {
+        self.animating
+    }
+}
+
+#[cfg(test)]
+fn run_to_end(source: &str, host: &mut TestHost) -> Process {
+    let program = Program::from_source(source).expect("script should parse");
+    let mut process = Process::new(Arc::new(program), Entity::from_raw(1));
+    let mut budget = u32::MAX;
+    assert_eq!(process.run(host, &mut budget), Ok(Status::Finished));
+    process
+}
+
+#[test]
+fn test_run_script_flow() {
+    let mut host = TestHost::default();
+    let process = run_to_end(
+        "setv var0 0,reps 5,addv var0 2,repe,\
+         doif var0 eq 10 and var0 bt 2,setv var1 1,elif var0 gt 0,setv var1 2,else,setv var1 3,endi,\
+         loop,subv var0 3,untl var0 lt 0,\
+         gsub double,modv var0 4,stop,\
+         subr double,mulv var0 2,retn",
+        &mut host,
+    );
+
+    assert_eq!(process.var(1), Value::Integer(1));
+    assert_eq!(process.var(0), Value::Integer(-4 % 4));
+}
+
+#[test]
+fn test_run_script_host() {
+    let mut host = TestHost {
+        agents: vec![Entity::from_raw(2), Entity::from_raw(3)],
+        ..Default::default()
+    };
+
+    run_to_end(
+        "setv obv0 7,enum 2 0 0,addv obv0 1,mvby obv0 0,next,anim [012R],snde bees",
+        &mut host,
+    );
+
+    let ownr = Entity::from_raw(1);
+    assert_eq!(host.obvs[&(ownr, 0)], Value::Integer(7));
+    assert_eq!(host.obvs[&(Entity::from_raw(2), 0)], Value::Integer(1));
+    assert_eq!(
+        host.commands,
+        ["mvby 1 0", "mvby 1 0", "anim 012R", "snde bees"]
+    );
+}
+
+#[test]
+fn test_run_script_timeslicing() {
+    let mut host = TestHost::default();
+    let program = Program::from_source("setv var0 1,setv var0 2,wait 2,inst,setv var0 3,setv var0 4,setv var0 5,slow,setv var0 6,over,setv var0 7")
+        .expect("script should parse");
+    let mut process = Process::new(Arc::new(program), Entity::from_raw(1));
+
+    let mut budget = 1;
+    assert_eq!(process.run(&mut host, &mut budget), Ok(Status::Running));
+    assert_eq!((process.var(0), budget), (Value::Integer(1), 0));
+
+    let mut budget = 5;
+    assert_eq!(process.run(&mut host, &mut budget), Ok(Status::Blocked));
+    assert_eq!(process.var(0), Value::Integer(2));
+
+    for _ in 0..2 {
+        assert_eq!(process.run(&mut host, &mut 5), Ok(Status::Blocked));
+    }
+
+    // `inst` runs to `slow` without using the budget.
+    let mut budget = 2;
+    assert_eq!(process.run(&mut host, &mut budget), Ok(Status::Running));
+    assert_eq!(process.var(0), Value::Integer(6));
+
+    host.animating = true;
+    assert_eq!(process.run(&mut host, &mut 5), Ok(Status::Blocked));
+    assert_eq!(process.run(&mut host, &mut 5), Ok(Status::Blocked));
+    assert_eq!(process.var(0), Value::Integer(6));
+
+    host.animating = false;
+    assert_eq!(process.run(&mut host, &mut 5), Ok(Status::Finished));
+    assert_eq!(process.var(0), Value::Integer(7));
+}
+
+#[test]
+fn test_run_script_errors() {
+    let mut host = TestHost::default();
+    let source = "setv var0 1,divv var0 0";
+    let program = Program::from_source(source).expect("script should parse");
+    let mut process = Process::new(Arc::new(program), Entity::from_raw(1));
+
+    let err = process
+        .run(&mut host, &mut 10)
+        .expect_err("division by zero should fail");
+
+    assert_eq!(err.message, "division by zero");
+    assert_eq!(&source[err.span.start..err.span.end], "divv var0 0");
+
+    let program = Program::from_source("inst,loop,ever").expect("script should parse");
+    let mut process = Process::new(Arc::new(program), Entity::from_raw(1));
+
+    let err = process
+        .run(&mut host, &mut 10)
+        .expect_err("endless inst loop should be stopped");
+    assert_eq!(err.message, "script ran too long without `slow`");
+}
@@ -0,0 +1,504 @@
+//! Bacteria living in the rooms. Each room has a fixed number of slots which
+//! hold a bacterium or nothing. Bacteria go dormant when their room is too
+//! hot, cold or irradiated, breed (sometimes mutating) on organic nutrients,
+//! die of radiation and old age, drift through open doors, and now and then
+//! appear from nowhere in rooms with something to eat.
+//!
+//! Creatures catch them through [`RoomBacteria::infect`], and a bacterium in a
+//! host gives off its antigen and toxins through [`Bacterium::emission`].
+
+use std::{collections::HashMap, time::Duration};
+
+use bevy::{prelude::*, time::common_conditions::on_timer};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::{
+    door::RoomGraph,
+    environment::update_environment,
+    room::{Room, Simulata, MAX_AMOUNT_OPEN},
+};
+use crate::{
+    formats::sfc::{self, BacteriaFlags, BacteriaState, BACTERIA_PER_ROOM},
+    state::GameState,
+};
+
+/// Game time between bacteria updates.
+pub const BACTERIA_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Bacteria carry one of the eight antigens creatures make antibodies for.
+pub const NUMBER_OF_ANTIGENS: u8 = 8;
+
+/// Temperatures active bacteria put up with. Outside them they go dormant.
+const COMFORTABLE_TEMPERATURE: std::ops::RangeInclusive<u8> = 60..=200;
+
+/// Radiation a room rests at with no source. Only radiation above it harms
+/// bacteria.
+const HARMLESS_RADIATION: u8 = 127;
+
+/// Radiation above which bacteria go dormant.
+const DORMANT_RADIATION: u8 = 192;
+
+/// Chance each update that a dormant bacterium in a comfortable room wakes.
+const WAKE_CHANCE: f64 = 1.0 / 8.0;
+
+/// Chance each update that an active bacterium dies of old age.
+const OLD_AGE_CHANCE: f64 = 1.0 / 200.0;
+
+/// Chance each update that full radiation kills an active bacterium. Dormant
+/// bacteria are half as likely to die.
+const RADIATION_KILL_CHANCE: f64 = 1.0 / 16.0;
+
+/// Chance each update that an active bacterium breeds in a room full of
+/// organic nutrient.
+const BREED_CHANCE: f64 = 1.0 / 10.0;
+
+/// Chance that a bacterium born from another is different from its parent.
+const MUTATION_CHANCE: f64 = 1.0 / 20.0;
+
+/// Chance each update that an active bacterium leaves through a fully open
+/// door.
+const SPREAD_CHANCE: f64 = 1.0 / 20.0;
+
+/// Chance each update that a bacterium appears in a room full of organic
+/// nutrient.
+const SPAWN_CHANCE: f64 = 1.0 / 1000.0;
+
+pub struct BacteriaPlugin;
+
+impl Plugin for BacteriaPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BacteriaRng>();
+        app.register_type::<RoomBacteria>();
+        app.add_systems(
+            FixedUpdate,
+            update_bacteria
+                .after(update_environment)
+                .run_if(in_state(GameState::Running))
+                .run_if(on_timer(BACTERIA_INTERVAL)),
+        );
+    }
+}
+
+/// Where the bacteria get their luck from, kept apart so tests can seed it.
+#[derive(Resource)]
+pub struct BacteriaRng(pub StdRng);
+
+impl Default for BacteriaRng {
+    fn default() -> Self {
+        Self(StdRng::from_entropy())
+    }
+}
+
+#[derive(Reflect, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Bacterium {
+    pub state: BacteriaState,
+    pub antigen: u8,
+    /// How deadly the bacterium is to the creature it infects.
+    pub fatal_level: u8,
+    /// Chance out of 255 that the bacterium infects a creature it meets.
+    pub infect_level: u8,
+    /// How much of each of the four toxins it gives off in a host.
+    pub toxins: [u8; 4],
+    /// Its flags from the world file besides its state, which is kept in
+    /// `state`, so that they're written back unchanged.
+    pub flags: BacteriaFlags,
+}
+
+/// What a bacterium gives off into its host each update.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Emission {
+    pub antigen: u8,
+    pub toxins: [u8; 4],
+}
+
+impl Bacterium {
+    /// A new bacterium with everything about it left to chance.
+    pub fn random(rng: &mut impl Rng) -> Self {
+        Self {
+            state: BacteriaState::Active,
+            antigen: rng.gen_range(0..NUMBER_OF_ANTIGENS),
+            fatal_level: rng.gen(),
+            infect_level: rng.gen(),
+            toxins: rng.gen(),
+            flags: BacteriaFlags::default(),
+        }
+    }
+
+    pub fn is_present(&self) -> bool {
+        self.state != BacteriaState::NotPresent
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.state == BacteriaState::Active
+    }
+
+    /// Only active bacteria give anything off.
+    pub fn emission(&self) -> Option<Emission> {
+        self.is_active().then_some(Emission {
+            antigen: self.antigen,
+            toxins: self.toxins,
+        })
+    }
+
+    /// A copy of the bacterium that, by chance, may differ in one way.
+    fn offspring(&self, rng: &mut impl Rng) -> Self {
+        let mut child = *self;
+
+        if rng.gen_bool(MUTATION_CHANCE) {
+            let nudge = |value: u8, rng: &mut dyn rand::RngCore| {
+                value.saturating_add_signed(rng.gen_range(-16..=16))
+            };
+
+            match rng.gen_range(0..4) {
+                0 => child.antigen = rng.gen_range(0..NUMBER_OF_ANTIGENS),
+                1 => child.fatal_level = nudge(child.fatal_level, rng),
+                2 => child.infect_level = nudge(child.infect_level, rng),
+                _ => {
+                    let toxin = rng.gen_range(0..child.toxins.len());
+                    child.toxins[toxin] = nudge(child.toxins[toxin], rng);
+                }
+            }
+        }
+
+        child
+    }
+}
+
+impl From<&sfc::Bacteria> for Bacterium {
+    fn from(bacteria: &sfc::Bacteria) -> Self {
+        Self {
+            state: bacteria.flags.state(),
+            antigen: bacteria.antigen,
+            fatal_level: bacteria.fatal_level,
+            infect_level: bacteria.infect_level,
+            toxins: bacteria.toxins,
+            flags: bacteria.flags.with_state(BacteriaState::NotPresent),
+        }
+    }
+}
+
+impl From<&Bacterium> for sfc::Bacteria {
+    fn from(bacterium: &Bacterium) -> Self {
+        Self {
+            flags: bacterium.flags.with_state(bacterium.state),
+            antigen: bacterium.antigen,
+            fatal_level: bacterium.fatal_level,
+            infect_level: bacterium.infect_level,
+            toxins: bacterium.toxins,
+        }
+    }
+}
+
+/// The bacteria slots of a room.
+#[derive(Component, Reflect, Clone, Debug, PartialEq)]
+pub struct RoomBacteria(pub Vec<Bacterium>);
+
+impl Default for RoomBacteria {
+    fn default() -> Self {
+        Self(vec![Bacterium::default(); BACTERIA_PER_ROOM])
+    }
+}
+
+impl RoomBacteria {
+    pub fn from_room(room: &sfc::RoomPointer) -> Self {
+        Self(room.bacterium.iter().map(Bacterium::from).collect())
+    }
+
+    pub fn write_to(&self, room: &mut sfc::RoomPointer) {
+        room.bacterium = self.0.iter().map(sfc::Bacteria::from).collect();
+    }
+
+    pub fn count(&self) -> usize {
+        self.0
+            .iter()
+            .filter(|bacterium| bacterium.is_present())
+            .count()
+    }
+
+    /// Puts a bacterium in a free slot, or gives it back if the room is full.
+    pub fn add(&mut self, bacterium: Bacterium) -> Result<(), Bacterium> {
+        match self.0.iter_mut().find(|slot| !slot.is_present()) {
+            Some(slot) => {
+                *slot = bacterium;
+                Ok(())
+            }
+            None => Err(bacterium),
+        }
+    }
+
+    /// A creature in the room meets one of its active bacteria, which may
+    /// infect it. An infecting bacterium leaves the room for its new host.
+    pub fn infect(&mut self, rng: &mut impl Rng) -> Option<Bacterium> {
+        let active: Vec<usize> = (0..self.0.len())
+            .filter(|&slot| self.0[slot].is_active())
+            .collect();
+
+        if active.is_empty() {
+            return None;
+        }
+
+        let slot = active[rng.gen_range(0..active.len())];
+
+        if rng.gen_range(0..u8::MAX) >= self.0[slot].infect_level {
+            return None;
+        }
+
+        Some(std::mem::take(&mut self.0[slot]))
+    }
+
+    /// Wakes, sends to sleep, breeds and kills the bacteria of a room with
+    /// the given conditions.
+    pub fn update(&mut self, simulata: &Simulata, rng: &mut impl Rng) {
+        let comfortable = COMFORTABLE_TEMPERATURE.contains(&simulata.temperature)
+            && simulata.radiation <= DORMANT_RADIATION;
+        let radiation = simulata.radiation.saturating_sub(HARMLESS_RADIATION) as f64
+            / (u8::MAX - HARMLESS_RADIATION) as f64;
+        let nutrient = simulata.organic_nutrient as f64 / u8::MAX as f64;
+        let mut born = Vec::new();
+
+        for bacterium in self.0.iter_mut() {
+            match bacterium.state {
+                BacteriaState::NotPresent => continue,
+                BacteriaState::Dormant => {
+                    if rng.gen_bool(radiation * RADIATION_KILL_CHANCE / 2.0) {
+                        *bacterium = Bacterium::default();
+                    } else if comfortable && rng.gen_bool(WAKE_CHANCE) {
+                        bacterium.state = BacteriaState::Active;
+                    }
+                }
+                BacteriaState::Active => {
+                    if rng.gen_bool(OLD_AGE_CHANCE + radiation * RADIATION_KILL_CHANCE) {
+                        *bacterium = Bacterium::default();
+                    } else if !comfortable {
+                        bacterium.state = BacteriaState::Dormant;
+                    } else if rng.gen_bool(nutrient * BREED_CHANCE) {
+                        born.push(bacterium.offspring(rng));
+                    }
+                }
+            }
+        }
+
+        if rng.gen_bool(nutrient * SPAWN_CHANCE) {
+            born.push(Bacterium::random(rng));
+        }
+
+        for bacterium in born {
+            if self.add(bacterium).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+pub fn update_bacteria(
+    mut rooms: Query<(Entity, &Room, &Simulata, &mut RoomBacteria)>,
+    graph: Res<RoomGraph>,
+    mut rng: ResMut<BacteriaRng>,
+) {
+    let rng = &mut rng.0;
+    let mut entities = HashMap::new();
+    let mut leaving = Vec::new();
+
+    for (entity, room, simulata, mut bacteria) in rooms.iter_mut() {
+        entities.insert(room.room_id, entity);
+        bacteria.update(simulata, rng);
+
+        for neighbour in graph.neighbours(room.room_id) {
+            let open = neighbour.amount_open as f64 / MAX_AMOUNT_OPEN as f64;
+
+            for (slot, bacterium) in bacteria.0.iter().enumerate() {
+                if bacterium.is_active() && rng.gen_bool(open * SPREAD_CHANCE) {
+                    leaving.push((entity, slot, neighbour.room_id));
+                }
+            }
+        }
+    }
+
+    for (from, slot, to) in leaving {
+        let Some(&to) = entities.get(&to) else {
+            continue;
+        };
+        let Ok([(_, _, _, mut here), (_, _, _, mut there)]) = rooms.get_many_mut([from, to]) else {
+            continue;
+        };
+
+        // A bacterium that already left by another door stays gone, and a
+        // full room turns it back.
+        if here.0[slot].is_active() && there.add(here.0[slot]).is_ok() {
+            here.0[slot] = Bacterium::default();
+        }
+    }
+}
+
+#[test]
+fn test_bacteria_conditions() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let bacterium = Bacterium {
+        state: BacteriaState::Active,
+        infect_level: 255,
+        ..Default::default()
+    };
+
+    // Too hot: everything goes dormant, and without nutrients nothing breeds.
+    let mut bacteria = RoomBacteria::default();
+    for _ in 0..10 {
+        bacteria.add(bacterium).unwrap();
+    }
+    let hot = Simulata {
+        temperature: 250,
+        ..Default::default()
+    };
+    bacteria.update(&hot, &mut rng);
+    assert!(bacteria
+        .0
+        .iter()
+        .filter(|b| b.is_present())
+        .all(|b| !b.is_active()));
+    assert_eq!(
+        bacteria.infect(&mut rng),
+        None,
+        "dormant bacteria don't infect"
+    );
+
+    // A comfortable room full of food wakes them up and they breed.
+    let comfortable = Simulata {
+        temperature: 127,
+        organic_nutrient: 255,
+        ..Default::default()
+    };
+    for _ in 0..50 {
+        bacteria.update(&comfortable, &mut rng);
+    }
+    assert!(bacteria.count() > 10);
+
+    let count = bacteria.count();
+    let caught = bacteria
+        .infect(&mut rng)
+        .expect("an infectious bacterium catches the creature");
+    assert_eq!(bacteria.count(), count - 1, "and leaves the room");
+    assert_eq!(
+        caught.emission(),
+        Some(Emission {
+            antigen: caught.antigen,
+            toxins: caught.toxins,
+        })
+    );
+
+    // Radiation kills them all off in the end.
+    let irradiated = Simulata {
+        radiation: 255,
+        ..Default::default()
+    };
+    for _ in 0..500 {
+        bacteria.update(&irradiated, &mut rng);
+    }
+    assert_eq!(bacteria.count(), 0);
+}
+
+#[test]
+fn test_bacteria_spread() {
+    use super::door::{Door, DoorSide};
+    use crate::wrap::WrappedSpace;
+    use bevy::ecs::system::RunSystemOnce;
+
+    let mut world = World::new();
+    world.insert_resource(BacteriaRng(StdRng::seed_from_u64(2)));
+
+    let mut spawn = |room_id: u32, x: f32, bacteria: RoomBacteria| {
+        let room = Room {
+            room_id,
+            rect: Rect::new(x, 0.0, x + 100.0, -100.0),
+            ..Default::default()
+        };
+        let simulata = Simulata {
+            temperature: 127,
+            ..Default::default()
+        };
+        world.spawn((room, simulata, bacteria)).id()
+    };
+
+    let bacterium = Bacterium {
+        state: BacteriaState::Active,
+        ..Default::default()
+    };
+    let mut crowded = RoomBacteria::default();
+    for _ in 0..50 {
+        crowded.add(bacterium).unwrap();
+    }
+    let full = spawn(0, 0.0, crowded);
+    let empty = spawn(1, 100.0, RoomBacteria::default());
+    let sealed = spawn(2, 200.0, RoomBacteria::default());
+
+    let door = |rooms: (u32, u32), amount_open| {
+        Door::between(
+            WrappedSpace::WORLD,
+            (rooms.0, Rect::new(0.0, 0.0, 100.0, -100.0)),
+            (rooms.1, Rect::new(100.0, 0.0, 200.0, -100.0)),
+            DoorSide::Right,
+            amount_open,
+        )
+    };
+    let open = world.spawn(door((0, 1), MAX_AMOUNT_OPEN)).id();
+    let closed = world.spawn(door((1, 2), 0)).id();
+
+    let graph = RoomGraph::new(
+        WrappedSpace::WORLD,
+        world.query::<&Room>().iter(&world),
+        [
+            (open, world.get::<Door>(open).unwrap()),
+            (closed, world.get::<Door>(closed).unwrap()),
+        ],
+    );
+    world.insert_resource(graph);
+
+    for _ in 0..20 {
+        world.run_system_once(update_bacteria).unwrap();
+    }
+
+    let count = |entity| world.get::<RoomBacteria>(entity).unwrap().count();
+    assert!(count(empty) > 0, "bacteria drift through the open door");
+    assert!(count(full) < 50);
+    assert_eq!(count(sealed), 0, "but not through a closed one");
+}
+
+#[test]
+fn test_bacteria_round_trip() {
+    use crate::formats::sfc::{ArchiveTable, Doc};
+    use std::sync::{Arc, Mutex};
+
+    let buf = include_bytes!("../../assets/fixtures/synthetic.sfc");
+    let mut registry = Arc::new(Mutex::new(ArchiveTable::empty()));
+    let (_, doc) = Doc::parse(buf, &mut registry).expect("synthetic.sfc should parse");
+    let mut pointer = sfc::RoomPointer::clone(&doc.map.rooms.rooms[0]);
+
+    let mut bacteria = RoomBacteria::from_room(&pointer);
+    assert_eq!(bacteria.0.len(), BACTERIA_PER_ROOM);
+
+    let bacterium = Bacterium {
+        state: BacteriaState::Dormant,
+        antigen: 3,
+        fatal_level: 40,
+        infect_level: 90,
+        toxins: [1, 2, 3, 4],
+        ..Default::default()
+    };
+    bacteria.0[7] = bacterium;
+
+    // The first bacterium's flags have a bit set besides its state.
+    let flags = pointer.bacterium[0].flags;
+    assert_eq!(bacteria.0[0].state, BacteriaState::Active);
+    assert_ne!(
+        flags,
+        BacteriaFlags::default().with_state(BacteriaState::Active)
+    );
+    bacteria.0[0].state = BacteriaState::Dormant;
+
+    bacteria.write_to(&mut pointer);
+
+    assert_eq!(RoomBacteria::from_room(&pointer), bacteria);
+    assert_eq!(pointer.bacterium[7].antigen, 3);
+    assert_eq!(
+        pointer.bacterium[0].flags,
+        flags.with_state(BacteriaState::Dormant)
+    );
+}
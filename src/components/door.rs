@@ -0,0 +1,509 @@
+//! Doors between rooms, and the graph of which rooms lead into which.
+
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap, HashSet},
+    sync::Arc,
+};
+
+use bevy::{
+    color::palettes::tailwind::{GREEN_300, RED_300},
+    prelude::*,
+};
+
+use super::{
+    room::{Room, MAX_AMOUNT_OPEN},
+    CreaturesGizmos,
+};
+use crate::{formats::sfc::RoomPointer, geometry::WorldGeometry, wrap::WrappedSpace};
+
+pub struct DoorPlugin;
+
+impl Plugin for DoorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RoomGraph>();
+        app.add_systems(Update, update_room_graph);
+        app.register_type::<Door>();
+        app.register_type::<DoorSide>();
+    }
+}
+
+/// The side of a room a door is on, in the order `RoomPointer::doors` lists
+/// them.
+#[derive(Reflect, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DoorSide {
+    Left,
+    Top,
+    Right,
+    Bottom,
+}
+
+impl DoorSide {
+    pub const ALL: [DoorSide; 4] = [
+        DoorSide::Left,
+        DoorSide::Top,
+        DoorSide::Right,
+        DoorSide::Bottom,
+    ];
+
+    /// The side of the neighbouring room the same door is on.
+    pub fn opposite(&self) -> DoorSide {
+        match self {
+            DoorSide::Left => DoorSide::Right,
+            DoorSide::Top => DoorSide::Bottom,
+            DoorSide::Right => DoorSide::Left,
+            DoorSide::Bottom => DoorSide::Top,
+        }
+    }
+}
+
+/// A door between two rooms. `side` is the side of the first room it's on.
+#[derive(Component, Reflect, Clone, Debug, PartialEq)]
+pub struct Door {
+    pub rooms: [u32; 2],
+    pub side: DoorSide,
+    pub amount_open: u8,
+    /// Ends of the edge the rooms share, in world space. The far room may be
+    /// on the other side of the wrap.
+    pub edge: [Vec2; 2],
+}
+
+impl Door {
+    /// The door between `room` and `neighbour` on the given side of `room`.
+    pub fn between(
+        space: WrappedSpace,
+        room: (u32, Rect),
+        neighbour: (u32, Rect),
+        side: DoorSide,
+        amount_open: u8,
+    ) -> Self {
+        let (room_id, rect) = room;
+        let (neighbour_id, neighbour_rect) = neighbour;
+
+        // Bring the neighbour to the side of the wrap nearest the room.
+        let shift = space.offset(rect.center(), neighbour_rect.center()).x
+            - (neighbour_rect.center().x - rect.center().x);
+        let neighbour_rect = Rect::from_corners(
+            neighbour_rect.min + Vec2::new(shift, 0.0),
+            neighbour_rect.max + Vec2::new(shift, 0.0),
+        );
+
+        let overlap = |low: f32, high: f32, other_low: f32, other_high: f32| {
+            let (start, end) = (low.max(other_low), high.min(other_high));
+            if start <= end {
+                (start, end)
+            } else {
+                (low, high)
+            }
+        };
+
+        let edge = match side {
+            DoorSide::Left | DoorSide::Right => {
+                let x = if side == DoorSide::Left {
+                    rect.min.x
+                } else {
+                    rect.max.x
+                };
+                let (bottom, top) = overlap(
+                    rect.min.y,
+                    rect.max.y,
+                    neighbour_rect.min.y,
+                    neighbour_rect.max.y,
+                );
+                [Vec2::new(x, top), Vec2::new(x, bottom)]
+            }
+            DoorSide::Top | DoorSide::Bottom => {
+                let y = if side == DoorSide::Top {
+                    rect.max.y
+                } else {
+                    rect.min.y
+                };
+                let (left, right) = overlap(
+                    rect.min.x,
+                    rect.max.x,
+                    neighbour_rect.min.x,
+                    neighbour_rect.max.x,
+                );
+                [Vec2::new(left, y), Vec2::new(right, y)]
+            }
+        };
+
+        Self {
+            rooms: [room_id, neighbour_id],
+            side,
+            amount_open,
+            edge,
+        }
+    }
+
+    /// The room on the other side of the door from `room_id`.
+    pub fn other(&self, room_id: u32) -> u32 {
+        if self.rooms[0] == room_id {
+            self.rooms[1]
+        } else {
+            self.rooms[0]
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.amount_open > 0
+    }
+}
+
+/// Makes a door for each pair of rooms that list each other. Rooms list the
+/// doors they share, so a door is only made from the first room of a pair.
+pub fn doors_from_rooms(space: WrappedSpace, rooms: &[Arc<RoomPointer>]) -> Vec<Door> {
+    let rects: HashMap<u32, Rect> = rooms
+        .iter()
+        .rev()
+        .map(|room| (room.room_id, room.rect.clone().into()))
+        .collect();
+
+    let mut seen = HashSet::new();
+    let mut doors = Vec::new();
+
+    for room in rooms {
+        let rect = rects[&room.room_id];
+
+        for (side, array) in DoorSide::ALL.into_iter().zip(&room.doors.doors) {
+            for door in &array.doors {
+                let pair = (
+                    room.room_id.min(door.room_id),
+                    room.room_id.max(door.room_id),
+                );
+
+                let Some(&neighbour_rect) = rects.get(&door.room_id) else {
+                    continue;
+                };
+
+                if !seen.insert(pair) {
+                    continue;
+                }
+
+                doors.push(Door::between(
+                    space,
+                    (room.room_id, rect),
+                    (door.room_id, neighbour_rect),
+                    side,
+                    door.amount_open,
+                ));
+            }
+        }
+    }
+
+    doors
+}
+
+/// A way out of a room in the [`RoomGraph`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Neighbour {
+    pub room_id: u32,
+    pub door: Entity,
+    pub amount_open: u8,
+}
+
+/// Which rooms lead into which, rebuilt whenever rooms or doors change.
+#[derive(Resource, Default, Debug)]
+pub struct RoomGraph {
+    space: WrappedSpace,
+    centers: HashMap<u32, Vec2>,
+    neighbours: HashMap<u32, Vec<Neighbour>>,
+}
+
+impl RoomGraph {
+    pub fn new<'a>(
+        space: WrappedSpace,
+        rooms: impl IntoIterator<Item = &'a Room>,
+        doors: impl IntoIterator<Item = (Entity, &'a Door)>,
+    ) -> Self {
+        let mut graph = Self {
+            space,
+            ..Default::default()
+        };
+
+        for room in rooms {
+            graph
+                .centers
+                .entry(room.room_id)
+                .or_insert(room.rect.center());
+        }
+
+        for (entity, door) in doors {
+            let [first, second] = door.rooms;
+
+            for (from, to) in [(first, second), (second, first)] {
+                graph.neighbours.entry(from).or_default().push(Neighbour {
+                    room_id: to,
+                    door: entity,
+                    amount_open: door.amount_open,
+                });
+            }
+        }
+
+        graph
+    }
+
+    pub fn space(&self) -> WrappedSpace {
+        self.space
+    }
+
+    pub fn contains(&self, room_id: u32) -> bool {
+        self.centers.contains_key(&room_id)
+    }
+
+    /// The rooms a room has doors into, open or not.
+    pub fn neighbours(&self, room_id: u32) -> &[Neighbour] {
+        self.neighbours
+            .get(&room_id)
+            .map_or(&[], |neighbours| neighbours.as_slice())
+    }
+
+    /// The door between two rooms, if they're neighbours.
+    pub fn door_between(&self, room_id: u32, other: u32) -> Option<Entity> {
+        self.neighbours(room_id)
+            .iter()
+            .find(|neighbour| neighbour.room_id == other)
+            .map(|neighbour| neighbour.door)
+    }
+
+    /// The shortest way from one room to another through open doors, as the
+    /// rooms passed through including both ends. Distances are measured
+    /// between room centres the short way around the wrap.
+    pub fn shortest_path(&self, from: u32, to: u32) -> Option<Vec<u32>> {
+        if !self.contains(from) || !self.contains(to) {
+            return None;
+        }
+
+        let mut distances = HashMap::from([(from, 0.0)]);
+        let mut previous = HashMap::new();
+        let mut queue = BinaryHeap::from([Visit {
+            distance: 0.0,
+            room_id: from,
+        }]);
+
+        while let Some(Visit { distance, room_id }) = queue.pop() {
+            if room_id == to {
+                let mut path = vec![to];
+                while let Some(&room_id) = previous.get(path.last().unwrap()) {
+                    path.push(room_id);
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            if distance > distances[&room_id] {
+                continue;
+            }
+
+            for neighbour in self.neighbours(room_id) {
+                if neighbour.amount_open == 0 {
+                    continue;
+                }
+
+                let Some(&center) = self.centers.get(&neighbour.room_id) else {
+                    continue;
+                };
+
+                let next = distance + self.space.distance(self.centers[&room_id], center);
+
+                if distances
+                    .get(&neighbour.room_id)
+                    .is_none_or(|&known| next < known)
+                {
+                    distances.insert(neighbour.room_id, next);
+                    previous.insert(neighbour.room_id, room_id);
+                    queue.push(Visit {
+                        distance: next,
+                        room_id: neighbour.room_id,
+                    });
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// A room waiting in the shortest path queue, nearest first.
+#[derive(PartialEq)]
+struct Visit {
+    distance: f32,
+    room_id: u32,
+}
+
+impl Eq for Visit {}
+
+impl Ord for Visit {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .distance
+            .total_cmp(&self.distance)
+            .then(self.room_id.cmp(&other.room_id))
+    }
+}
+
+impl PartialOrd for Visit {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+type ChangedRoomsOrDoors = Or<(Changed<Room>, Changed<Door>)>;
+
+fn update_room_graph(
+    mut graph: ResMut<RoomGraph>,
+    geometry: Res<WorldGeometry>,
+    rooms: Query<&Room>,
+    doors: Query<(Entity, &Door)>,
+    changed: Query<(), ChangedRoomsOrDoors>,
+    mut removed_rooms: RemovedComponents<Room>,
+    mut removed_doors: RemovedComponents<Door>,
+) {
+    let removed = removed_rooms.read().count() + removed_doors.read().count() > 0;
+
+    if changed.is_empty() && !removed && !geometry.is_changed() {
+        return;
+    }
+
+    *graph = RoomGraph::new(geometry.space(), rooms.iter(), doors.iter());
+}
+
+pub fn render_gizmo_doors(mut gizmos: Gizmos<CreaturesGizmos>, doors: Query<&Door>) {
+    for door in doors.iter() {
+        let open = door.amount_open as f32 / MAX_AMOUNT_OPEN as f32;
+        let color = RED_300.mix(&GREEN_300, open);
+        let [start, end] = door.edge;
+
+        gizmos.line(start.extend(0.3), end.extend(0.3), color);
+    }
+}
+
+#[test]
+fn test_door_edge() {
+    let space = WrappedSpace::WORLD;
+    let width = space.width();
+
+    let room = (1, Rect::new(0.0, 0.0, 100.0, -100.0));
+    let neighbour = (2, Rect::new(100.0, -50.0, 300.0, -300.0));
+    let door = Door::between(space, room, neighbour, DoorSide::Right, 255);
+
+    assert_eq!(
+        door.edge,
+        [Vec2::new(100.0, -50.0), Vec2::new(100.0, -100.0)]
+    );
+    assert_eq!(door.other(1), 2);
+
+    // A room at the far end of the world meets the first room at the wrap.
+    let far = (3, Rect::new(width - 100.0, 0.0, width, -60.0));
+    let door = Door::between(space, room, far, DoorSide::Left, 255);
+
+    assert_eq!(door.edge, [Vec2::new(0.0, 0.0), Vec2::new(0.0, -60.0)]);
+}
+
+#[test]
+fn test_room_graph_shortest_path() {
+    let width = WrappedSpace::WORLD.width();
+
+    let room = |room_id, left, right| Room {
+        room_id,
+        rect: Rect::new(left, 0.0, right, -100.0),
+        ..Default::default()
+    };
+    let rooms = [
+        room(0, 0.0, 100.0),
+        room(1, width / 2.0, width / 2.0 + 100.0),
+        room(2, width - 200.0, width - 100.0),
+        room(3, width - 100.0, width),
+        room(4, 100.0, 200.0),
+    ];
+
+    let door = |rooms: [u32; 2], amount_open| Door {
+        rooms,
+        side: DoorSide::Left,
+        amount_open,
+        edge: [Vec2::ZERO; 2],
+    };
+
+    let doors = [
+        door([0, 1], 255),
+        door([1, 2], 255),
+        door([0, 3], 255),
+        door([3, 2], 255),
+        door([0, 4], 0),
+    ];
+    let entities: Vec<_> = (0..doors.len() as u32).map(Entity::from_raw).collect();
+
+    let graph = RoomGraph::new(
+        WrappedSpace::WORLD,
+        &rooms,
+        entities.iter().copied().zip(&doors),
+    );
+
+    // Through the wrap rather than across the whole world.
+    assert_eq!(graph.shortest_path(0, 2), Some(vec![0, 3, 2]));
+    assert_eq!(graph.shortest_path(2, 0), Some(vec![2, 3, 0]));
+    assert_eq!(graph.shortest_path(0, 0), Some(vec![0]));
+
+    // Closed doors can't be passed, but are still neighbours.
+    assert_eq!(graph.shortest_path(0, 4), None);
+    assert_eq!(graph.door_between(4, 0), Some(entities[4]));
+    assert_eq!(
+        graph
+            .neighbours(0)
+            .iter()
+            .map(|neighbour| neighbour.room_id)
+            .collect::<Vec<_>>(),
+        [1, 3, 4]
+    );
+    assert_eq!(graph.shortest_path(0, 9), None);
+
+    // A world that doesn't wrap has to be crossed the long way.
+    let flat = RoomGraph::new(
+        WrappedSpace::new(width, false),
+        &rooms,
+        entities.iter().copied().zip(&doors),
+    );
+    assert_eq!(flat.shortest_path(0, 2), Some(vec![0, 1, 2]));
+}
+
+#[test]
+fn test_doors_from_rooms() {
+    use crate::formats::sfc::{CRect, DoorPointerArray, DoorPointerArrayItem, Doors};
+
+    let room = |room_id, left, right, side: usize, neighbour| {
+        let mut doors = vec![DoorPointerArray::default(); 4];
+        doors[side] = DoorPointerArray::new(vec![DoorPointerArrayItem::new(neighbour, 200)]);
+
+        RoomPointer {
+            room_id,
+            rect: CRect {
+                left,
+                top: 0,
+                right,
+                bottom: 100,
+            },
+            doors: Doors { doors },
+            ..Default::default()
+        }
+    };
+
+    // Each room lists the door on its own side, and a door to a room that
+    // isn't in the world is dropped.
+    let rooms = [
+        room(1, 0, 100, 2, 2),
+        room(2, 100, 300, 0, 1),
+        room(3, 300, 400, 2, 9),
+    ]
+    .map(Arc::new);
+    let doors = doors_from_rooms(WrappedSpace::WORLD, &rooms);
+
+    assert_eq!(
+        doors,
+        [Door {
+            rooms: [1, 2],
+            side: DoorSide::Right,
+            amount_open: 200,
+            edge: [Vec2::new(100.0, 0.0), Vec2::new(100.0, -100.0)],
+        }]
+    );
+}
@@ -0,0 +1,263 @@
+//! The room ecology: heat, pressure, light and radiation sources pull each
+//! room toward a level, open doors let the levels even out between
+//! neighbouring rooms, and pressure differences through doors make wind.
+//!
+//! The ecology runs on `FixedUpdate`, which follows `Time<Virtual>`, so it
+//! stops while the game is paused.
+
+use std::{collections::HashMap, time::Duration};
+
+use bevy::{prelude::*, time::common_conditions::on_timer};
+
+use super::{
+    door::RoomGraph,
+    room::{Room, Simulata, MAX_AMOUNT_OPEN, WIND_PER_P},
+};
+use crate::state::GameState;
+
+/// Game time between ecology updates.
+pub const ENVIRONMENT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Level every quantity rests at with no source, halfway up the range.
+const NEUTRAL_LEVEL: i32 = 127;
+
+/// Fraction of the distance to its target a room moves each update.
+const SOURCE_RATE: f32 = 1.0 / 16.0;
+
+/// Fraction of the difference between two rooms that flows through a fully
+/// open door each update.
+const DIFFUSION_RATE: f32 = 1.0 / 4.0;
+
+pub struct EnvironmentPlugin;
+
+impl Plugin for EnvironmentPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SourceDeltas>();
+        app.register_type::<SourceDeltas>();
+        app.add_systems(
+            FixedUpdate,
+            update_environment
+                .run_if(in_state(GameState::Running))
+                .run_if(on_timer(ENVIRONMENT_INTERVAL)),
+        );
+    }
+}
+
+/// Added to every room's heat, light and radiation targets, from the
+/// `*_SOURCE_DELTA` tables for the current season and time of day.
+#[derive(Resource, Reflect, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SourceDeltas {
+    pub heat: i8,
+    pub light: i8,
+    pub radiation: i8,
+}
+
+/// The four levels of a room while they're being updated.
+#[derive(Clone, Copy, Debug, Default)]
+struct Levels {
+    temperature: f32,
+    pressure: f32,
+    light: f32,
+    radiation: f32,
+}
+
+pub fn update_environment(
+    mut rooms: Query<(Entity, &Room, &mut Simulata)>,
+    graph: Res<RoomGraph>,
+    deltas: Res<SourceDeltas>,
+) {
+    let mut levels: HashMap<Entity, (Levels, Vec2)> = HashMap::new();
+    let mut by_id: HashMap<u32, Vec<Entity>> = HashMap::new();
+
+    for (entity, room, simulata) in rooms.iter() {
+        let current = Levels {
+            temperature: simulata.temperature as f32,
+            pressure: simulata.pressure as f32,
+            light: simulata.light_level as f32,
+            radiation: simulata.radiation as f32,
+        };
+        levels.insert(entity, (current, room.rect.center()));
+        by_id.entry(room.room_id).or_default().push(entity);
+    }
+
+    for (entity, room, mut simulata) in rooms.iter_mut() {
+        let (current, center) = levels[&entity];
+        let mut next = current;
+
+        next.temperature += pull(current.temperature, simulata.heat_source, deltas.heat);
+        next.pressure += pull(current.pressure, simulata.pressure_source, 0);
+        next.light += pull(current.light, simulata.light_source, deltas.light);
+        next.radiation += pull(
+            current.radiation,
+            simulata.radiation_source,
+            deltas.radiation,
+        );
+
+        let mut wind = Vec2::ZERO;
+
+        // Doors name the rooms they lead to by id, so a door leads into
+        // every room with that id.
+        let levels = &levels;
+        let neighbours = graph.neighbours(room.room_id).iter().flat_map(|door| {
+            by_id
+                .get(&door.room_id)
+                .into_iter()
+                .flatten()
+                .map(move |neighbour| (door, levels[neighbour]))
+        });
+
+        for (door, (neighbour, neighbour_center)) in neighbours {
+            let open = door.amount_open as f32 / MAX_AMOUNT_OPEN as f32;
+            let flow = |ours: f32, theirs: f32| (theirs - ours) * open * DIFFUSION_RATE / 2.0;
+
+            next.temperature += flow(current.temperature, neighbour.temperature);
+            next.pressure += flow(current.pressure, neighbour.pressure);
+            next.light += flow(current.light, neighbour.light);
+            next.radiation += flow(current.radiation, neighbour.radiation);
+
+            // Air blows out of the room toward lower pressure.
+            let toward = graph.space().direction(center, neighbour_center);
+            wind += toward * (current.pressure - neighbour.pressure) * open * WIND_PER_P as f32;
+        }
+
+        simulata.new_temperature = level(next.temperature);
+        simulata.new_pressure = level(next.pressure);
+        simulata.temperature = simulata.new_temperature;
+        simulata.pressure = simulata.new_pressure;
+        simulata.light_level = level(next.light);
+        simulata.radiation = level(next.radiation);
+        simulata.wind = wind;
+    }
+}
+
+/// Change toward the level a room's source and the season pull it to, at
+/// least a whole step so that rooms reach their target.
+fn pull(value: f32, source: i32, delta: i8) -> f32 {
+    let target = (NEUTRAL_LEVEL + source + delta as i32).clamp(0, u8::MAX as i32) as f32;
+    let change = (target - value) * SOURCE_RATE;
+
+    if change.abs() < 1.0 {
+        (target - value).clamp(-1.0, 1.0)
+    } else {
+        change
+    }
+}
+
+fn level(value: f32) -> u8 {
+    value.round().clamp(0.0, u8::MAX as f32) as u8
+}
+
+/// A hot, high pressure room with id 0 and a cold, low pressure one to its
+/// right with id 1, joined by a door open by `amount_open`.
+#[cfg(test)]
+fn hot_and_cold_rooms(world: &mut World, amount_open: u8) -> (Entity, Entity) {
+    use super::door::{Door, DoorSide};
+    use crate::wrap::WrappedSpace;
+
+    let mut spawn = |room_id: u32, x: f32, temperature: u8, pressure: u8| {
+        let room = Room {
+            room_id,
+            rect: Rect::new(x, 0.0, x + 100.0, -100.0),
+            ..Default::default()
+        };
+        let simulata = Simulata {
+            temperature,
+            pressure,
+            ..Default::default()
+        };
+        world.spawn((room, simulata)).id()
+    };
+    let hot = spawn(0, 0.0, 200, 180);
+    let cold = spawn(1, 100.0, 60, 100);
+
+    let door = world
+        .spawn(Door::between(
+            WrappedSpace::WORLD,
+            (0, Rect::new(0.0, 0.0, 100.0, -100.0)),
+            (1, Rect::new(100.0, 0.0, 200.0, -100.0)),
+            DoorSide::Right,
+            amount_open,
+        ))
+        .id();
+    let graph = RoomGraph::new(
+        WrappedSpace::WORLD,
+        world.query::<&Room>().iter(world),
+        [(door, world.get::<Door>(door).unwrap())],
+    );
+    world.insert_resource(graph);
+
+    (hot, cold)
+}
+
+#[test]
+fn test_environment_diffusion() {
+    use bevy::ecs::system::RunSystemOnce;
+
+    let mut world = World::new();
+    world.init_resource::<SourceDeltas>();
+    let (hot, cold) = hot_and_cold_rooms(&mut world, MAX_AMOUNT_OPEN);
+
+    world.run_system_once(update_environment).unwrap();
+
+    let hot_room = world.get::<Simulata>(hot).unwrap();
+    let cold_room = world.get::<Simulata>(cold).unwrap();
+
+    assert!(hot_room.temperature < 200 && cold_room.temperature > 60);
+    assert!(
+        hot_room.wind.x > 0.0,
+        "wind blows toward the low pressure room"
+    );
+    assert!(cold_room.wind.x > 0.0, "and the same way on the other side");
+
+    for _ in 0..200 {
+        world.run_system_once(update_environment).unwrap();
+    }
+
+    let hot_room = world.get::<Simulata>(hot).unwrap();
+    let cold_room = world.get::<Simulata>(cold).unwrap();
+
+    assert!(hot_room.temperature.abs_diff(cold_room.temperature) <= 2);
+    assert!(hot_room.pressure.abs_diff(127) <= 2);
+    assert!(hot_room.wind.length() < 2.0);
+}
+
+#[test]
+fn test_environment_closed_door() {
+    use bevy::ecs::system::RunSystemOnce;
+
+    let mut world = World::new();
+    world.insert_resource(SourceDeltas {
+        heat: -10,
+        ..Default::default()
+    });
+    let (hot, cold) = hot_and_cold_rooms(&mut world, 0);
+    world.get_mut::<Simulata>(hot).unwrap().heat_source = 73;
+
+    for _ in 0..200 {
+        world.run_system_once(update_environment).unwrap();
+    }
+
+    let hot_room = world.get::<Simulata>(hot).unwrap();
+    let cold_room = world.get::<Simulata>(cold).unwrap();
+
+    // Each room settles at its own target with no flow between them.
+    assert_eq!(hot_room.temperature, 190);
+    assert_eq!(cold_room.temperature, 117);
+    assert_eq!(hot_room.wind, Vec2::ZERO);
+}
+
+#[test]
+fn test_environment_shared_room_id() {
+    use bevy::ecs::system::RunSystemOnce;
+
+    let mut world = World::new();
+    world.init_resource::<SourceDeltas>();
+    let (hot, cold) = hot_and_cold_rooms(&mut world, 0);
+    world.get_mut::<Room>(cold).unwrap().room_id = 0;
+
+    world.run_system_once(update_environment).unwrap();
+
+    // Each room moves from its own level, not the other's.
+    assert_eq!(world.get::<Simulata>(hot).unwrap().temperature, 195);
+    assert_eq!(world.get::<Simulata>(cold).unwrap().temperature, 64);
+}
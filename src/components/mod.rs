@@ -2,18 +2,50 @@ use bevy::prelude::*;
 pub use debug::{add_debug_text_bg, update_debug_text, CreaturesGizmos, DebugText};
 
 pub mod debug;
-pub mod utils;
 
+pub mod bacteria;
+pub mod door;
+pub mod environment;
+pub mod object;
+pub mod physics;
 pub mod room;
+pub mod room_index;
+pub mod save;
 
-use crate::components::room::RoomPlugin;
+use crate::components::{
+    bacteria::BacteriaPlugin,
+    door::DoorPlugin,
+    environment::EnvironmentPlugin,
+    object::{ObjectPlugin, ObjectRenderPlugin},
+    physics::PhysicsPlugin,
+    room::{RoomPlugin, RoomRenderPlugin},
+    room_index::RoomIndexPlugin,
+    save::SavePlugin,
+};
 
 pub struct GameComponentsPlugin;
 
 impl Plugin for GameComponentsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            RoomPlugin,
+            RoomIndexPlugin,
+            DoorPlugin,
+            ObjectPlugin,
+            PhysicsPlugin,
+            EnvironmentPlugin,
+            BacteriaPlugin,
+            SavePlugin,
+        ));
+    }
+}
+
+pub struct GameComponentsRenderPlugin;
+
+impl Plugin for GameComponentsRenderPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Update, (update_debug_text, add_debug_text_bg).chain());
 
-        app.add_plugins((RoomPlugin,));
+        app.add_plugins((RoomRenderPlugin, ObjectRenderPlugin));
     }
 }
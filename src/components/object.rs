@@ -0,0 +1,351 @@
+use super::physics::Physics;
+use crate::{
+    caos::runtime::{ObjectVariables, ScriptTimer},
+    formats::{
+        s16::S16Atlas,
+        sfc::{self, Attributes, CGallery, Classifier, MovementStatus, WorldObject},
+        WorldHandle,
+    },
+    state::GameState,
+};
+use bevy::{prelude::*, sprite::Anchor};
+
+/// World units of depth between adjacent planes, so that the highest planes
+/// used by C2 (around 10000) stay well inside the camera's clip range.
+const PLANE_DEPTH: f32 = 0.0001;
+
+pub struct ObjectPlugin;
+
+impl Plugin for ObjectPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup);
+        app.add_systems(Update, (spawn_objects, update_pose_frames));
+        app.add_systems(FixedUpdate, animate.run_if(in_state(GameState::Running)));
+        app.register_type::<Agent>();
+        app.register_type::<Plane>();
+        app.register_type::<AgentPart>();
+        app.register_type::<Pose>();
+        app.register_type::<Animation>();
+    }
+}
+
+/// Draws objects with sprites from their galleries.
+pub struct ObjectRenderPlugin;
+
+impl Plugin for ObjectRenderPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, update_part_sprites.after(update_pose_frames));
+    }
+}
+
+fn setup(mut commands: Commands) {
+    commands.spawn((
+        Name::new("Objects"),
+        ObjectContainer,
+        Transform::default(),
+        Visibility::default(),
+    ));
+}
+
+#[derive(Component)]
+pub struct ObjectContainer;
+
+/// An object from the world's object list or scenery.
+#[derive(Component, Reflect, Debug)]
+pub struct Agent {
+    pub id: i32,
+    pub class_name: &'static str,
+    pub classifier: Classifier,
+    pub attributes: Attributes,
+    pub movement_status: MovementStatus,
+    pub active: u8,
+}
+
+/// Drawing order of an object or part, higher planes are in front.
+#[derive(Component, Reflect, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Plane(pub i32);
+
+impl Plane {
+    pub fn depth(&self) -> f32 {
+        self.0 as f32 * PLANE_DEPTH
+    }
+}
+
+/// A sprite drawn for a compound object, the first part is the object itself.
+#[derive(Component, Reflect, Debug)]
+pub struct AgentPart {
+    pub index: usize,
+    /// Frame of the object's gallery the part shows.
+    pub frame: usize,
+}
+
+#[derive(Component)]
+pub struct Scenery;
+
+/// Sprite file an object's frames are drawn from, and the size of each frame
+/// as the world file records it.
+#[derive(Component, Reflect, Debug, Clone)]
+pub struct Gallery {
+    /// Name of the sprite file, without the extension.
+    pub file_name: String,
+    pub sizes: Vec<Vec2>,
+}
+
+impl Gallery {
+    pub fn from_gallery(gallery: &CGallery) -> Self {
+        Self {
+            file_name: gallery.file_name().to_string(),
+            sizes: gallery
+                .images()
+                .iter()
+                .map(|image| image.size().as_vec2())
+                .collect(),
+        }
+    }
+
+    /// Size of a frame, zero for frames past the end of the gallery.
+    pub fn size(&self, frame: usize) -> Vec2 {
+        self.sizes.get(frame).copied().unwrap_or(Vec2::ZERO)
+    }
+}
+
+/// The frame shown by an object's first part is `base + pose`.
+#[derive(Component, Reflect, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Pose {
+    pub base: u8,
+    pub pose: u8,
+}
+
+impl Pose {
+    pub fn frame(&self) -> usize {
+        self.base as usize + self.pose as usize
+    }
+}
+
+/// Poses played one per tick, set with `anim`.
+#[derive(Component, Reflect, Debug, Clone, PartialEq, Eq, Default)]
+pub struct Animation {
+    pub poses: Vec<u8>,
+    pub index: usize,
+    pub repeat: bool,
+}
+
+impl Animation {
+    /// Reads an animation string such as `012R`, a digit per pose with an
+    /// optional `R` to repeat from the start.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (text, repeat) = match text.strip_suffix(['R', 'r']) {
+            Some(text) => (text, true),
+            None => (text, false),
+        };
+
+        let poses = text
+            .chars()
+            .map(|c| {
+                c.to_digit(10)
+                    .map(|digit| digit as u8)
+                    .ok_or_else(|| format!("bad animation string `{}`", text))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            poses,
+            index: 0,
+            repeat,
+        })
+    }
+
+    pub fn finished(&self) -> bool {
+        !self.repeat && self.index >= self.poses.len()
+    }
+}
+
+fn animate(mut animated: Query<(&mut Animation, &mut Pose)>) {
+    for (mut animation, mut pose) in animated.iter_mut() {
+        if animation.finished() || animation.poses.is_empty() {
+            continue;
+        }
+
+        if animation.index >= animation.poses.len() {
+            animation.index = 0;
+        }
+
+        pose.pose = animation.poses[animation.index];
+        animation.index += 1;
+    }
+}
+
+pub fn update_pose_frames(
+    agents: Query<(&Pose, &Children), Changed<Pose>>,
+    mut parts: Query<&mut AgentPart>,
+) {
+    for (pose, children) in agents.iter() {
+        for &child in children {
+            let Ok(mut part) = parts.get_mut(child) else {
+                continue;
+            };
+
+            if part.index == 0 && part.frame != pose.frame() {
+                part.frame = pose.frame();
+            }
+        }
+    }
+}
+
+fn update_part_sprites(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    galleries: Query<&Gallery>,
+    mut parts: Query<(Entity, &AgentPart, &Parent, Option<&mut Sprite>), Changed<AgentPart>>,
+) {
+    for (entity, part, parent, sprite) in parts.iter_mut() {
+        let Ok(gallery) = galleries.get(parent.get()) else {
+            continue;
+        };
+
+        let atlas = S16Atlas::load(&asset_server, &format!("sprites/{}.s16", gallery.file_name));
+
+        match sprite {
+            Some(mut sprite) => {
+                sprite.image = atlas.image.clone();
+                sprite.texture_atlas = Some(atlas.texture_atlas(part.frame));
+            }
+            None => {
+                commands.entity(entity).insert(Sprite {
+                    anchor: Anchor::TopLeft,
+                    ..Sprite::from_atlas_image(atlas.image.clone(), atlas.texture_atlas(part.frame))
+                });
+            }
+        }
+    }
+}
+
+/// Spawns the objects and scenery of the current world once it has loaded,
+/// replacing those from a previous load when the file is hot-reloaded.
+fn spawn_objects(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<sfc::World>>,
+    world_handle: Res<WorldHandle>,
+    worlds: Res<Assets<sfc::World>>,
+    parent: Single<Entity, With<ObjectContainer>>,
+    agents: Query<Entity, With<Agent>>,
+) {
+    let reloaded = events.read().any(|event| match event {
+        AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } => {
+            *id == world_handle.0.id()
+        }
+        _ => false,
+    });
+
+    if !reloaded {
+        return;
+    }
+
+    let Some(world) = worlds.get(&world_handle.0) else {
+        return;
+    };
+
+    for agent in agents.iter() {
+        commands.entity(agent).despawn_recursive();
+    }
+
+    let parent = *parent;
+
+    for object in &world.doc.objects {
+        let agent = spawn_object(&mut commands, object);
+        commands.entity(parent).add_child(agent);
+    }
+
+    for simple_object in &world.doc.simple_object_pointer {
+        let object = WorldObject::Simple(simple_object.clone());
+        let agent = spawn_object(&mut commands, &object);
+        commands.entity(agent).insert(Scenery);
+        commands.entity(parent).add_child(agent);
+    }
+}
+
+fn spawn_object(commands: &mut Commands, object: &WorldObject) -> Entity {
+    let attributes = object.attributes().clone();
+    let visibility = if attributes.invisible {
+        Visibility::Hidden
+    } else {
+        Visibility::Inherited
+    };
+
+    let name = format!("{}:{}", object.class_name(), object.id());
+
+    let mut agent = commands.spawn((
+        Agent {
+            id: object.id(),
+            class_name: object.class_name(),
+            classifier: object.classifier().clone(),
+            attributes,
+            movement_status: object.movement_status().clone(),
+            active: object.active(),
+        },
+        Transform::default(),
+        visibility,
+        Name::new(name.clone()),
+    ));
+
+    agent.insert((
+        ObjectVariables::from_objvars(object.vars()),
+        ScriptTimer {
+            rate: object.timer_rate(),
+            elapsed: 0,
+        },
+    ));
+
+    let parts = object.parts();
+    let Some((main, _)) = parts.first() else {
+        return agent.id();
+    };
+
+    let main_plane = Plane(main.plane);
+
+    agent.insert((
+        main_plane,
+        Transform::from_xyz(
+            main.world_x as f32,
+            -main.world_y as f32,
+            main_plane.depth(),
+        ),
+    ));
+
+    let gallery = Gallery::from_gallery(object.gallery());
+
+    // Only objects with parts have a position, and so a body to move.
+    agent.insert((
+        Physics {
+            size: gallery.size(main.frame()),
+            ..Physics::from_object(object)
+        },
+        gallery,
+        Pose {
+            base: main.base_index,
+            pose: main.image_index,
+        },
+    ));
+
+    agent.with_children(|children| {
+        for (index, (part, offset)) in parts.iter().enumerate() {
+            let plane = Plane(part.plane);
+
+            children.spawn((
+                AgentPart {
+                    index,
+                    frame: part.frame(),
+                },
+                plane,
+                Visibility::default(),
+                Transform::from_xyz(
+                    offset.x as f32,
+                    -offset.y as f32,
+                    plane.depth() - main_plane.depth(),
+                ),
+            ));
+        }
+    });
+
+    agent.id()
+}
@@ -0,0 +1,439 @@
+//! Objects falling, bouncing and sliding around the rooms.
+//!
+//! Each tick an object's foot, the middle of its bottom edge, moves by its
+//! velocity. Room walls and ceilings bounce it back unless there's an open
+//! door wide enough for it, and room floors catch it: it bounces, or settles
+//! and slides down the slope of the floor. Everything runs on `FixedUpdate`
+//! and only depends on the rooms, doors and the object itself, so the same
+//! world always plays out the same way.
+
+use bevy::prelude::*;
+
+use super::{
+    door::{Door, DoorSide},
+    object::{update_pose_frames, Agent, AgentPart, Gallery},
+    room_index::RoomIndex,
+};
+use crate::{
+    caos::runtime::run_scripts,
+    formats::sfc::{CRect, WorldObject},
+    state::GameState,
+};
+
+/// Bounces slower than this, or than a tick of gravity, settle instead.
+const SETTLE_SPEED: f32 = 1.0;
+
+/// Fraction of its speed an object sliding along a floor loses each tick.
+const FLOOR_FRICTION: f32 = 0.1;
+
+/// Slower than this and a sliding object stops.
+const STOP_SPEED: f32 = 0.05;
+
+pub struct PhysicsPlugin;
+
+impl Plugin for PhysicsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Physics>();
+        app.add_systems(
+            FixedUpdate,
+            update_physics
+                .after(run_scripts)
+                .run_if(in_state(GameState::Running)),
+        );
+        app.add_systems(Update, update_physics_size.after(update_pose_frames));
+    }
+}
+
+/// How an object moves. Speeds are in world units per tick, y up.
+#[derive(Component, Reflect, Clone, Debug, PartialEq)]
+pub struct Physics {
+    pub velocity: Vec2,
+    /// Added to the object's downward speed each tick it suffers gravity.
+    pub gravity: f32,
+    /// Percentage of its speed the object keeps when it bounces.
+    pub restitution: f32,
+    /// Percentage of its speed the object loses to the air each tick.
+    pub aerodynamic: f32,
+    /// Doors narrower than this are walls to the object.
+    pub min_door_size: f32,
+    /// The object's foot stays inside this rect, when it has one.
+    pub limit: Option<Rect>,
+    /// Size of the frame the object's first part shows, from its gallery.
+    pub size: Vec2,
+    pub wall_last_collided: Option<DoorSide>,
+    /// Whether the object is lying on a floor.
+    pub resting: bool,
+}
+
+impl Default for Physics {
+    fn default() -> Self {
+        Self {
+            velocity: Vec2::ZERO,
+            gravity: 1.0,
+            restitution: 50.0,
+            aerodynamic: 0.0,
+            min_door_size: 0.0,
+            limit: None,
+            size: Vec2::ZERO,
+            wall_last_collided: None,
+            resting: false,
+        }
+    }
+}
+
+impl Physics {
+    pub fn from_object(object: &WorldObject) -> Self {
+        let velocity = object.velocity();
+        let limit = object.limit();
+
+        Self {
+            // The file is y down like the rest of C2.
+            velocity: Vec2::new(velocity.x as f32, -velocity.y as f32),
+            gravity: object.acceleration_due_to_gravity() as f32,
+            restitution: object.restitution() as f32,
+            aerodynamic: object.aerodynamic() as f32,
+            min_door_size: object.min_door_size() as f32,
+            limit: (*limit != CRect::default()).then(|| limit.clone().into()),
+            size: Vec2::ZERO,
+            wall_last_collided: DoorSide::ALL
+                .get(object.wall_last_collided() as usize)
+                .copied(),
+            resting: false,
+        }
+    }
+
+    /// Offset of the object's foot from its top left corner.
+    pub fn foot(&self) -> Vec2 {
+        Vec2::new(self.size.x / 2.0, -self.size.y)
+    }
+
+    fn bounce(&self, speed: f32) -> f32 {
+        -speed * (self.restitution / 100.0).clamp(0.0, 1.0)
+    }
+}
+
+/// The rooms and doors objects move through.
+#[derive(Clone, Debug)]
+pub struct PhysicsWorld<'a> {
+    pub rooms: &'a RoomIndex,
+    pub doors: Vec<&'a Door>,
+}
+
+impl<'a> PhysicsWorld<'a> {
+    pub fn new(rooms: &'a RoomIndex, doors: impl IntoIterator<Item = &'a Door>) -> Self {
+        Self {
+            rooms,
+            doors: doors.into_iter().collect(),
+        }
+    }
+
+    /// Whether something at `along` (y for the left and right sides, x for
+    /// the top and bottom) can leave `room_id` through its `side`.
+    fn can_pass(&self, room_id: u32, side: DoorSide, along: f32, min_door_size: f32) -> bool {
+        self.doors.iter().any(|door| {
+            let on_side = match door.rooms {
+                [first, _] if first == room_id => door.side == side,
+                [_, second] if second == room_id => door.side.opposite() == side,
+                _ => false,
+            };
+            let [start, end] = door.edge;
+
+            let within = match side {
+                DoorSide::Left | DoorSide::Right => {
+                    start.y.min(end.y) <= along && along <= start.y.max(end.y)
+                }
+                DoorSide::Top | DoorSide::Bottom => {
+                    self.rooms.space().x_within(along, start.x, end.x).is_some()
+                }
+            };
+
+            on_side && within && door.is_open() && start.distance(end) >= min_door_size
+        })
+    }
+
+    /// Moves a body a tick on from `foot`, returning where its foot ends up.
+    pub fn step(&self, body: &mut Physics, foot: Vec2, gravity: bool) -> Vec2 {
+        let mut velocity = body.velocity;
+
+        if gravity {
+            velocity.y -= body.gravity;
+        }
+        velocity *= 1.0 - (body.aerodynamic / 100.0).clamp(0.0, 1.0);
+
+        let room = self.rooms.room_at(foot);
+        // Work in the room's own span of the world, in case it crosses the
+        // wrap.
+        let foot = room.and_then(|room| room.local(foot)).unwrap_or(foot);
+        let mut next = foot + velocity;
+
+        if let Some(room) = room {
+            let rect = room.rect;
+            let can_pass =
+                |side, along| self.can_pass(room.room_id, side, along, body.min_door_size);
+
+            // Gravity may have pulled the foot under the floor by now, which
+            // the floor sorts out below.
+            let height = next.y.clamp(rect.min.y, rect.max.y);
+
+            if next.x < rect.min.x && !can_pass(DoorSide::Left, height) {
+                next.x = rect.min.x;
+                velocity.x = body.bounce(velocity.x);
+                body.wall_last_collided = Some(DoorSide::Left);
+            } else if next.x > rect.max.x && !can_pass(DoorSide::Right, height) {
+                next.x = rect.max.x;
+                velocity.x = body.bounce(velocity.x);
+                body.wall_last_collided = Some(DoorSide::Right);
+            }
+
+            if next.y > rect.max.y && !can_pass(DoorSide::Top, next.x) {
+                next.y = rect.max.y;
+                velocity.y = body.bounce(velocity.y);
+                body.wall_last_collided = Some(DoorSide::Top);
+            }
+
+            let (floor, slope) = room.floor_at(next.x);
+            // Resting bodies follow the floor down slopes rather than
+            // hopping off them.
+            let on_floor = next.y <= floor || (body.resting && next.y - floor <= body.gravity);
+            let through = floor <= rect.min.y && can_pass(DoorSide::Bottom, next.x);
+
+            if on_floor && !through {
+                next.y = floor;
+                let bounce = body.bounce(velocity.y);
+
+                if bounce > body.gravity.max(SETTLE_SPEED) {
+                    velocity.y = bounce;
+                    body.resting = false;
+                    body.wall_last_collided = Some(DoorSide::Bottom);
+                } else {
+                    // Gravity along the slope pulls the body downhill.
+                    velocity.y = 0.0;
+                    velocity.x -= body.gravity * slope / (1.0 + slope * slope);
+                    velocity.x *= 1.0 - FLOOR_FRICTION;
+                    if velocity.x.abs() < STOP_SPEED {
+                        velocity.x = 0.0;
+                    }
+                    body.resting = true;
+                }
+            } else {
+                body.resting = false;
+            }
+        } else {
+            body.resting = false;
+        }
+
+        if let Some(limit) = body.limit {
+            if next.x < limit.min.x || next.x > limit.max.x {
+                next.x = next.x.clamp(limit.min.x, limit.max.x);
+                velocity.x = body.bounce(velocity.x);
+            }
+
+            if next.y > limit.max.y {
+                next.y = limit.max.y;
+                velocity.y = body.bounce(velocity.y);
+            } else if next.y < limit.min.y {
+                next.y = limit.min.y;
+                velocity.y = 0.0;
+                body.resting = true;
+            }
+        }
+
+        next.x = self.rooms.space().wrap_x(next.x);
+        body.velocity = velocity;
+        next
+    }
+}
+
+pub fn update_physics(
+    rooms: Res<RoomIndex>,
+    doors: Query<&Door>,
+    mut bodies: Query<(&Agent, &mut Physics, &mut Transform)>,
+) {
+    let world = PhysicsWorld::new(&rooms, doors.iter());
+
+    for (agent, mut physics, mut transform) in bodies.iter_mut() {
+        let gravity = agent.attributes.suffers_gravity;
+
+        if !gravity && physics.velocity == Vec2::ZERO {
+            continue;
+        }
+
+        let foot = transform.translation.truncate() + physics.foot();
+        let next = world.step(&mut physics, foot, gravity) - physics.foot();
+
+        transform.translation.x = next.x;
+        transform.translation.y = next.y;
+    }
+}
+
+/// Keeps the size of each object's body in step with the frame its first
+/// part shows.
+pub fn update_physics_size(
+    parts: Query<(&AgentPart, &Parent), Changed<AgentPart>>,
+    mut bodies: Query<(&Gallery, &mut Physics)>,
+) {
+    for (part, parent) in parts.iter() {
+        if part.index != 0 {
+            continue;
+        }
+
+        let Ok((gallery, mut physics)) = bodies.get_mut(parent.get()) else {
+            continue;
+        };
+
+        let size = gallery.size(part.frame);
+        if physics.size != size {
+            physics.size = size;
+        }
+    }
+}
+
+#[cfg(test)]
+fn run(world: &PhysicsWorld, body: &mut Physics, mut foot: Vec2, ticks: usize) -> Vec<Vec2> {
+    (0..ticks)
+        .map(|_| {
+            foot = world.step(body, foot, true);
+            foot
+        })
+        .collect()
+}
+
+#[test]
+fn test_physics_fall_and_bounce() {
+    use super::room::Room;
+    use crate::wrap::WrappedSpace;
+
+    let room = Room {
+        rect: Rect::new(0.0, 0.0, 200.0, -200.0),
+        ..Default::default()
+    };
+    let index = RoomIndex::new(WrappedSpace::WORLD, [(Entity::from_raw(0), &room)]);
+    let world = PhysicsWorld::new(&index, []);
+
+    let mut body = Physics::default();
+    let path = run(&world, &mut body, Vec2::new(100.0, -50.0), 100);
+
+    let landed = path.iter().position(|foot| foot.y == -200.0).unwrap();
+    assert!(
+        path[landed + 1].y > -200.0,
+        "it bounces off the floor the first time"
+    );
+    assert_eq!(path.last(), Some(&Vec2::new(100.0, -200.0)));
+    assert!(body.resting);
+    assert_eq!(body.velocity, Vec2::ZERO);
+
+    // The same start plays out the same way every time.
+    let mut again = Physics::default();
+    assert_eq!(run(&world, &mut again, Vec2::new(100.0, -50.0), 100), path);
+}
+
+#[test]
+fn test_physics_slide_down_floor() {
+    use super::room::Room;
+    use crate::wrap::WrappedSpace;
+
+    // The floor falls from 100 above the bottom of the room on the left to
+    // the bottom on the right.
+    let room = Room {
+        rect: Rect::new(0.0, 0.0, 200.0, -200.0),
+        ground: vec![Vec2::new(0.0, 100.0), Vec2::new(200.0, 0.0)],
+        ..Default::default()
+    };
+    let index = RoomIndex::new(WrappedSpace::WORLD, [(Entity::from_raw(0), &room)]);
+    let world = PhysicsWorld::new(&index, []);
+
+    let mut body = Physics {
+        restitution: 0.0,
+        ..Default::default()
+    };
+    let path = run(&world, &mut body, Vec2::new(50.0, -100.0), 200);
+    let end = *path.last().unwrap();
+
+    assert!(end.x > 100.0, "it slides downhill, ending at {}", end);
+    for foot in &path[path.len() - 10..] {
+        assert_eq!(foot.y, index.rooms()[0].floor_at(foot.x).0);
+    }
+}
+
+#[test]
+fn test_physics_walls_and_doors() {
+    use super::room::Room;
+    use crate::wrap::WrappedSpace;
+
+    let left = Room {
+        room_id: 0,
+        rect: Rect::new(0.0, 0.0, 200.0, -200.0),
+        ..Default::default()
+    };
+    let right = Room {
+        room_id: 1,
+        rect: Rect::new(200.0, 0.0, 400.0, -200.0),
+        ..Default::default()
+    };
+
+    let rects = (left.rect, right.rect);
+    let door = |amount_open| {
+        Door::between(
+            WrappedSpace::WORLD,
+            (0, rects.0),
+            (1, rects.1),
+            DoorSide::Right,
+            amount_open,
+        )
+    };
+    let start = Vec2::new(150.0, -200.0);
+    let thrown = |min_door_size| Physics {
+        velocity: Vec2::new(10.0, 0.0),
+        min_door_size,
+        ..Default::default()
+    };
+
+    let index = RoomIndex::new(
+        WrappedSpace::WORLD,
+        [(Entity::from_raw(0), &left), (Entity::from_raw(1), &right)],
+    );
+
+    // Through an open door into the next room.
+    let (open, closed) = (door(255), door(0));
+    let world = PhysicsWorld::new(&index, [&open]);
+    let mut body = thrown(0.0);
+    let path = run(&world, &mut body, start, 20);
+    assert!(path.last().unwrap().x > 200.0);
+    assert_eq!(index.room_at(*path.last().unwrap()).unwrap().room_id, 1);
+
+    // Too big for the door, or it's shut, and the wall bounces it back.
+    for (world, mut body) in [
+        (world.clone(), thrown(250.0)),
+        (PhysicsWorld::new(&index, [&closed]), thrown(0.0)),
+    ] {
+        let path = run(&world, &mut body, start, 20);
+        assert!(path.iter().all(|foot| foot.x <= 200.0));
+        assert_eq!(body.wall_last_collided, Some(DoorSide::Right));
+        assert!(body.velocity.x <= 0.0);
+    }
+}
+
+#[test]
+fn test_physics_limit() {
+    use super::room::Room;
+    use crate::wrap::WrappedSpace;
+
+    let room = Room {
+        rect: Rect::new(0.0, 0.0, 400.0, -400.0),
+        ..Default::default()
+    };
+    let index = RoomIndex::new(WrappedSpace::WORLD, [(Entity::from_raw(0), &room)]);
+    let world = PhysicsWorld::new(&index, []);
+
+    let mut body = Physics {
+        velocity: Vec2::new(-5.0, 0.0),
+        limit: Some(Rect::new(100.0, -100.0, 300.0, -150.0)),
+        ..Default::default()
+    };
+    let path = run(&world, &mut body, Vec2::new(200.0, -120.0), 100);
+
+    assert!(path
+        .iter()
+        .all(|foot| (100.0..=300.0).contains(&foot.x) && (-150.0..=-100.0).contains(&foot.y)));
+    assert_eq!(path.last().unwrap().y, -150.0, "it rests on the limit");
+}
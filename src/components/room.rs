@@ -1,15 +1,18 @@
 use super::{
+    bacteria::RoomBacteria,
     debug::{draw_rect, draw_surface, DebugTextInfo},
-    utils::{intersect_wrapped_rect, normalize_rect, point_in_wrapped_rect, world_wrap},
+    door::{doors_from_rooms, render_gizmo_doors, Door},
+    room_index::RoomIndex,
     CreaturesGizmos, DebugText,
 };
-use crate::formats::sfc::ClassRegistry;
 use crate::{
     camera::main_camera::{mouse_pos_to_world, MainCamera},
-    constants::WORLD_WIDTH,
-    display::{get_viewport_rect, tileset::RenderTile},
-    formats,
-    formats::sfc::{DropStatus, RoomType},
+    display::get_viewport_rect,
+    formats::{
+        sfc::{self, CPoint, CString, DropStatus, RoomType},
+        WorldHandle,
+    },
+    geometry::{load_geometry, WorldGeometry},
 };
 use bevy::color::palettes::tailwind::RED_300;
 use bevy::render::view::RenderLayers;
@@ -19,7 +22,6 @@ use bevy::{
     prelude::*,
 };
 use ops::FloatPow;
-use std::sync::{Arc, Mutex};
 
 pub const NUMBER_OF_TIMES_OF_DAY: usize = 5;
 pub const MAX_AMOUNT_OPEN: u8 = 255;
@@ -56,15 +58,39 @@ pub struct RoomPlugin;
 impl Plugin for RoomPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Startup, setup);
-        app.add_systems(Update, (render_gizmo_rooms).chain());
-        app.init_gizmo_group::<CreaturesGizmos>();
+        app.add_systems(Update, spawn_rooms.after(load_geometry));
         app.register_type::<Room>();
         app.register_type::<Simulata>();
-        app.register_type::<RenderTile>();
+        app.register_type::<Ambience>();
     }
 }
 
-fn setup(
+/// Outlines rooms and labels them with debug text.
+pub struct RoomRenderPlugin;
+
+impl Plugin for RoomRenderPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_debug);
+        app.add_systems(
+            Update,
+            (add_room_debug_text, render_gizmo_rooms, render_gizmo_doors)
+                .chain()
+                .after(spawn_rooms),
+        );
+        app.init_gizmo_group::<CreaturesGizmos>();
+    }
+}
+
+fn setup(mut commands: Commands) {
+    commands.spawn((
+        Name::new("Rooms"),
+        RoomContainer,
+        Transform::default(),
+        Visibility::default(),
+    ));
+}
+
+fn setup_debug(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     mut config_store: ResMut<GizmoConfigStore>,
@@ -73,32 +99,58 @@ fn setup(
     config.line_width = 2.0;
     config.render_layers = RenderLayers::from_layers(&[2]);
 
-    // load doc
-    let buf = include_bytes!("../../assets/test.sfc");
-    let mut registry = Arc::new(Mutex::new(ClassRegistry::empty()));
-    let (_, doc) = formats::sfc::Doc::parse(buf, &mut registry).unwrap();
     let font = asset_server.load("fonts/MS Sans Serif.ttf");
 
-    let debug_text_width = 150.0;
-    let debug_text_height = 180.0;
-
     commands.spawn(DebugText::new(
         "Cursor".to_string(),
         "".to_string(),
         &font,
-        debug_text_width,
-        debug_text_height,
+        DEBUG_TEXT_WIDTH,
+        DEBUG_TEXT_HEIGHT,
     ));
+}
+
+const DEBUG_TEXT_WIDTH: f32 = 150.0;
+const DEBUG_TEXT_HEIGHT: f32 = 180.0;
+
+#[derive(Component)]
+pub struct RoomContainer;
+
+type RoomsOrDoors = Or<(With<Room>, With<Door>)>;
+
+/// Spawns the rooms of the current world once it has loaded, replacing any
+/// rooms from a previous load when the file is hot-reloaded.
+fn spawn_rooms(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<sfc::World>>,
+    world_handle: Res<WorldHandle>,
+    worlds: Res<Assets<sfc::World>>,
+    geometry: Res<WorldGeometry>,
+    parent: Single<Entity, With<RoomContainer>>,
+    rooms: Query<Entity, RoomsOrDoors>,
+) {
+    let reloaded = events.read().any(|event| match event {
+        AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } => {
+            *id == world_handle.0.id()
+        }
+        _ => false,
+    });
 
-    let parent = commands
-        .spawn((
-            Name::new("Rooms"),
-            Transform::default(),
-            Visibility::default(),
-        ))
-        .id();
+    if !reloaded {
+        return;
+    }
 
-    for room in doc.map.rooms.rooms {
+    let Some(world) = worlds.get(&world_handle.0) else {
+        return;
+    };
+
+    for room in rooms.iter() {
+        commands.entity(room).despawn_recursive();
+    }
+
+    let parent = *parent;
+
+    for room in &world.doc.map.rooms.rooms {
         let room_rect: Rect = room.rect.clone().into();
 
         let room_id = commands
@@ -108,32 +160,67 @@ fn setup(
                     room_id: room.room_id,
                     room_type: room.room_type.clone(),
                     ground: Vec::from(&room.surface_points),
-                    visited: false,
+                    visited: room.visited != 0,
                 },
+                Simulata::from_room(room),
+                Ambience::from_room(room),
+                RoomBacteria::from_room(room),
                 Transform::from_xyz(room_rect.min.x, room_rect.min.y, 0.00001),
                 Anchor::TopLeft,
                 Visibility::Visible,
                 Name::new(format!("Room:{}", room.room_id)),
             ))
-            .with_child(DebugText::new(
-                format!("r{}", room.room_id),
-                format!("room id: {}", room.room_id),
-                &font,
-                debug_text_width,
-                room_rect.height(),
-            ))
             .id();
 
         commands.entity(parent).add_child(room_id);
     }
+
+    for door in doors_from_rooms(geometry.space(), &world.doc.map.rooms.rooms) {
+        let [first, second] = door.rooms;
+        let door_id = commands
+            .spawn((door, Name::new(format!("Door:{}-{}", first, second))))
+            .id();
+
+        commands.entity(parent).add_child(door_id);
+    }
+}
+
+fn add_room_debug_text(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    rooms: Query<(Entity, &Room), Added<Room>>,
+) {
+    let font = asset_server.load("fonts/MS Sans Serif.ttf");
+
+    for (entity, room) in rooms.iter() {
+        commands.entity(entity).with_child(DebugText::new(
+            format!("r{}", room.room_id),
+            format!("room id: {}", room.room_id),
+            &font,
+            DEBUG_TEXT_WIDTH,
+            room.rect.height(),
+        ));
+    }
 }
 
 // Ambience
-#[derive(Component, Reflect)]
+#[derive(Component, Reflect, Default)]
 pub struct Ambience {
     pub music_track: String,
 }
 
+impl Ambience {
+    pub fn from_room(room: &sfc::RoomPointer) -> Self {
+        Ambience {
+            music_track: room.music_track.as_str().to_string(),
+        }
+    }
+
+    pub fn write_to(&self, room: &mut sfc::RoomPointer) {
+        room.music_track = CString::new(self.music_track.clone());
+    }
+}
+
 // Simulata
 #[derive(Component, Reflect)]
 pub struct Simulata {
@@ -187,6 +274,49 @@ impl Default for Simulata {
 }
 
 impl Simulata {
+    pub fn from_room(room: &sfc::RoomPointer) -> Self {
+        Simulata {
+            id: room.room_id as i32,
+            room_type: room.room_type.clone(),
+            floor_value: room.floor_value,
+            inorganic_nutrient: room.inorganic_nutrient,
+            organic_nutrient: room.organic_nutrient,
+            new_temperature: room.temperature,
+            temperature: room.temperature,
+            heat_source: room.heat_source,
+            new_pressure: room.pressure,
+            pressure: room.pressure,
+            // World space is y up, the file is y down like the rest of C2.
+            wind: Vec2::new(room.wind.x as f32, -room.wind.y as f32),
+            pressure_source: room.pressure_source,
+            light_level: room.light,
+            light_source: room.light_source,
+            radiation: room.radiation,
+            radiation_source: room.radiation_source,
+            drop_status: room.drop_status.clone(),
+        }
+    }
+
+    pub fn write_to(&self, room: &mut sfc::RoomPointer) {
+        room.room_type = self.room_type.clone();
+        room.floor_value = self.floor_value;
+        room.inorganic_nutrient = self.inorganic_nutrient;
+        room.organic_nutrient = self.organic_nutrient;
+        room.temperature = self.temperature;
+        room.heat_source = self.heat_source;
+        room.pressure = self.pressure;
+        room.pressure_source = self.pressure_source;
+        room.wind = CPoint {
+            x: self.wind.x.round() as i32,
+            y: -self.wind.y.round() as i32,
+        };
+        room.light = self.light_level;
+        room.light_source = self.light_source;
+        room.radiation = self.radiation;
+        room.radiation_source = self.radiation_source;
+        room.drop_status = self.drop_status.clone();
+    }
+
     pub fn _get_temperature(&self) -> u8 {
         // Add wind chill [10 unit wind speed == 1 'C].
 
@@ -228,22 +358,11 @@ impl Default for Room {
     }
 }
 
-pub fn room_number<'a, I>(x: i32, y: i32, rooms: I) -> i32
-where
-    I: IntoIterator<Item = &'a Room>,
-{
-    let x = world_wrap(x);
-    let pt = Vec2::new(x as f32, y as f32);
-    let mut index = -1;
-
-    for room in rooms {
-        if point_in_wrapped_rect(room.rect, pt) {
-            index = room.room_id as i32;
-            break;
-        }
-    }
-
+/// Id of the room at a point, or -1 when it's outside every room.
+pub fn room_number(x: i32, y: i32, index: &RoomIndex) -> i32 {
     index
+        .room_at(Vec2::new(x as f32, y as f32))
+        .map_or(-1, |room| room.room_id as i32)
 }
 
 // Debug Stuff
@@ -252,8 +371,10 @@ pub fn render_gizmo_rooms(
     mut gizmos: Gizmos<CreaturesGizmos>,
     mut main_camera: Query<(&Camera, &GlobalTransform, &OrthographicProjection), With<MainCamera>>,
     window_query: Query<&Window>,
-    mut rooms_query: Query<(&Room, &mut Transform), With<Room>>,
+    rooms: Query<&Room>,
+    mut room_transforms: Query<&mut Transform, With<Room>>,
     mut debug_cursor_text: Query<&mut DebugTextInfo, With<DebugTextInfo>>,
+    index: Res<RoomIndex>,
 ) {
     let mut main_camera_lens =
         main_camera.transmute_lens_filtered::<(&Camera, &GlobalTransform), With<MainCamera>>();
@@ -269,70 +390,38 @@ pub fn render_gizmo_rooms(
     let mut in_wrapped_rect = false;
     let mut current_room: Option<&Room> = None;
 
-    let mut rooms: Vec<&Room> = vec![];
-    let mut room_transforms: Vec<Mut<Transform>> = vec![];
-
-    rooms_query.iter_mut().for_each(|(room, transform)| {
-        rooms.push(room);
-        room_transforms.push(transform);
-    });
-
-    for (index, room) in rooms.iter().enumerate() {
-        let room_rect: Rect = room.rect;
-
-        let half_width = (WORLD_WIDTH / 2.0) as i32;
+    let space = index.space();
 
-        let normalize = {
-            let min_x: i32 = viewport_rect.min.x as i32 - room_rect.min.x as i32;
-
-            min_x >= half_width || min_x < -half_width
+    for indexed in index.rooms_in(viewport_rect) {
+        let Ok(room) = rooms.get(indexed.entity) else {
+            continue;
         };
 
-        let normalized_viewport_rect = if normalize {
-            normalize_rect(viewport_rect.as_irect())
-        } else {
-            viewport_rect.as_irect()
-        };
+        // Draw the copy of the room on the same side of the seam as the
+        // view.
+        let wrap_rect = space.shift_near(room.rect, viewport_rect.center().x);
 
-        let normalized_room_rect = if normalize {
-            normalize_rect(room_rect.as_irect())
-        } else {
-            room_rect.as_irect()
-        };
-
-        if intersect_wrapped_rect(&normalized_viewport_rect, &normalized_room_rect) {
-            if room_transforms[index].translation.x > viewport_rect.max.x {
-                // room should be visible, but it's on the other side of the screen
-                room_transforms[index].translation.x -= WORLD_WIDTH;
-            }
-
-            if room_transforms[index].translation.x + room_rect.width() < viewport_rect.min.x {
-                room_transforms[index].translation.x += WORLD_WIDTH;
+        if let Ok(mut transform) = room_transforms.get_mut(indexed.entity) {
+            if transform.translation.x != wrap_rect.min.x {
+                transform.translation.x = wrap_rect.min.x;
             }
+        }
 
-            let ul = Vec2::new(
-                room_transforms[index].translation.x,
-                room_transforms[index].translation.y,
-            );
-            let rb = ul + Vec2::new(room_rect.width(), room_rect.height());
-            let wrap_rect = Rect::from_corners(ul, rb);
-
-            draw_surface(&mut gizmos, wrap_rect, &room.ground, YELLOW_300);
+        draw_surface(&mut gizmos, wrap_rect, &room.ground, YELLOW_300);
 
-            if point_in_wrapped_rect(wrap_rect, world_position) {
-                draw_rect(&mut gizmos, wrap_rect, 0.1, 0.0, RED_300);
-                in_wrapped_rect = true;
+        if space.contains(wrap_rect, world_position) {
+            draw_rect(&mut gizmos, wrap_rect, 0.1, 0.0, RED_300);
+            in_wrapped_rect = true;
 
-                if current_room.is_some() {
-                    if current_room.unwrap().room_id != room.room_id {
-                        current_room = Some(room);
-                    }
-                } else {
+            if current_room.is_some() {
+                if current_room.unwrap().room_id != room.room_id {
                     current_room = Some(room);
                 }
             } else {
-                draw_rect(&mut gizmos, wrap_rect, 0.0, 0.0, GREEN_300);
+                current_room = Some(room);
             }
+        } else {
+            draw_rect(&mut gizmos, wrap_rect, 0.0, 0.0, GREEN_300);
         }
     }
 
@@ -350,11 +439,7 @@ pub fn render_gizmo_rooms(
                 "wx: {} wy: {}\nrn: {} rid: {} type: {}\nroomrect: {:?}\nin_wrapped_rect?: {:?}",
                 world_position.x as i32,
                 world_position.y as i32,
-                room_number(
-                    world_position.x as i32,
-                    world_position.y as i32,
-                    rooms.clone(),
-                ),
+                room_number(world_position.x as i32, world_position.y as i32, &index),
                 current_room.room_id,
                 current_room.room_type,
                 current_room.rect,
@@ -0,0 +1,366 @@
+//! A grid over the world of which rooms are where, for finding the room at a
+//! point without looking at every room.
+
+use std::collections::{BTreeSet, HashMap};
+
+use bevy::prelude::*;
+
+use super::room::Room;
+use crate::{geometry::WorldGeometry, wrap::WrappedSpace};
+
+/// Width and height of a grid cell in world units.
+const CELL_SIZE: f32 = 512.0;
+
+pub struct RoomIndexPlugin;
+
+impl Plugin for RoomIndexPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RoomIndex>();
+        app.add_systems(Update, update_room_index);
+    }
+}
+
+/// A room in the [`RoomIndex`], with its floor in world space.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndexedRoom {
+    pub entity: Entity,
+    pub room_id: u32,
+    pub rect: Rect,
+    pub floor: Vec<Vec2>,
+    space: WrappedSpace,
+}
+
+impl IndexedRoom {
+    pub fn new(space: WrappedSpace, entity: Entity, room: &Room) -> Self {
+        Self {
+            space,
+            entity,
+            room_id: room.room_id,
+            rect: room.rect,
+            floor: room
+                .ground
+                .iter()
+                .map(|point| room.rect.min + *point)
+                .collect(),
+        }
+    }
+
+    /// `x` moved around the wrap to where it falls within the room, if it
+    /// does at all.
+    pub fn local_x(&self, x: f32) -> Option<f32> {
+        self.space.x_within(x, self.rect.min.x, self.rect.max.x)
+    }
+
+    /// `point` moved around the wrap into the room, if it's inside it.
+    pub fn local(&self, point: Vec2) -> Option<Vec2> {
+        let x = self.local_x(point.x)?;
+        (self.rect.min.y <= point.y && point.y <= self.rect.max.y).then_some(Vec2::new(x, point.y))
+    }
+
+    pub fn contains(&self, point: Vec2) -> bool {
+        self.local(point).is_some()
+    }
+
+    /// Height and slope of the floor at `x`, which should be in the room's
+    /// own span. Rooms without a floor, and the parts of a room its floor
+    /// doesn't reach, have a flat floor along the bottom of the room.
+    pub fn floor_at(&self, x: f32) -> (f32, f32) {
+        self.floor
+            .windows(2)
+            .find(|segment| segment[0].x <= x && x <= segment[1].x && segment[0].x < segment[1].x)
+            .map_or((self.rect.min.y, 0.0), |segment| {
+                let (start, end) = (segment[0], segment[1]);
+                let slope = (end.y - start.y) / (end.x - start.x);
+                (start.y + (x - start.x) * slope, slope)
+            })
+    }
+}
+
+/// Every room, bucketed into a grid that wraps around the world. Rebuilt
+/// whenever rooms change.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct RoomIndex {
+    space: WrappedSpace,
+    rooms: Vec<IndexedRoom>,
+    cells: HashMap<IVec2, Vec<usize>>,
+    /// The lowest row with any rooms in it.
+    lowest_row: Option<i32>,
+}
+
+fn cell_of(value: f32) -> i32 {
+    (value / CELL_SIZE).floor() as i32
+}
+
+impl RoomIndex {
+    pub fn new<'a>(
+        space: WrappedSpace,
+        rooms: impl IntoIterator<Item = (Entity, &'a Room)>,
+    ) -> Self {
+        let mut index = Self {
+            space,
+            ..Default::default()
+        };
+
+        for (entity, room) in rooms {
+            let room = IndexedRoom::new(space, entity, room);
+            let slot = index.rooms.len();
+
+            for (column, row) in index.cells_over(room.rect) {
+                index
+                    .cells
+                    .entry(IVec2::new(column, row))
+                    .or_default()
+                    .push(slot);
+            }
+
+            let row = cell_of(room.rect.min.y);
+            index.lowest_row = Some(index.lowest_row.map_or(row, |lowest| lowest.min(row)));
+            index.rooms.push(room);
+        }
+
+        index
+    }
+
+    pub fn space(&self) -> WrappedSpace {
+        self.space
+    }
+
+    /// The grid column of an `x` between 0 and the world's width. The last
+    /// column may be narrower than the rest.
+    fn column_in_world(&self, x: f32) -> i32 {
+        let columns = (self.space.width() / CELL_SIZE).ceil() as i32;
+        cell_of(x).min(columns - 1)
+    }
+
+    fn column_of(&self, x: f32) -> i32 {
+        if self.space.wraps() {
+            self.column_in_world(self.space.wrap_x(x))
+        } else {
+            cell_of(x)
+        }
+    }
+
+    /// The cells a rect covers, with columns wrapped around the world if it
+    /// wraps.
+    fn cells_over(&self, rect: Rect) -> impl Iterator<Item = (i32, i32)> {
+        let width = self.space.width();
+        let mut columns = BTreeSet::new();
+
+        if self.space.wraps() {
+            for shift in [-width, 0.0, width] {
+                let (left, right) = (
+                    (rect.min.x + shift).max(0.0),
+                    (rect.max.x + shift).min(width),
+                );
+
+                if left <= right {
+                    columns.extend(self.column_in_world(left)..=self.column_in_world(right));
+                }
+            }
+        } else {
+            columns.extend(cell_of(rect.min.x)..=cell_of(rect.max.x));
+        }
+
+        let rows = cell_of(rect.min.y)..=cell_of(rect.max.y);
+        columns
+            .into_iter()
+            .flat_map(move |column| rows.clone().map(move |row| (column, row)))
+    }
+
+    pub fn len(&self) -> usize {
+        self.rooms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rooms.is_empty()
+    }
+
+    pub fn rooms(&self) -> &[IndexedRoom] {
+        &self.rooms
+    }
+
+    fn cell(&self, column: i32, row: i32) -> &[usize] {
+        self.cells
+            .get(&IVec2::new(column, row))
+            .map_or(&[], |slots| slots.as_slice())
+    }
+
+    /// The room a point is in. Where rooms overlap, the first one added wins.
+    pub fn room_at(&self, point: Vec2) -> Option<&IndexedRoom> {
+        self.cell(self.column_of(point.x), cell_of(point.y))
+            .iter()
+            .map(|&slot| &self.rooms[slot])
+            .find(|room| room.contains(point))
+    }
+
+    /// The rooms that overlap a rect, in the order they were added.
+    pub fn rooms_in(&self, rect: Rect) -> Vec<&IndexedRoom> {
+        let slots: BTreeSet<usize> = self
+            .cells_over(rect)
+            .flat_map(|(column, row)| self.cell(column, row).iter().copied())
+            .collect();
+
+        slots
+            .into_iter()
+            .map(|slot| &self.rooms[slot])
+            .filter(|room| self.space.intersects(room.rect, rect))
+            .collect()
+    }
+
+    /// The highest floor at or below `point`, and the room it belongs to.
+    pub fn nearest_floor_below(&self, point: Vec2) -> Option<(&IndexedRoom, f32)> {
+        let lowest = self.lowest_row?;
+        let column = self.column_of(point.x);
+        let mut best: Option<(&IndexedRoom, f32)> = None;
+
+        for row in (lowest..=cell_of(point.y)).rev() {
+            // Nothing in this row or below can beat a floor above its top.
+            if best.is_some_and(|(_, height)| height >= (row + 1) as f32 * CELL_SIZE) {
+                break;
+            }
+
+            for &slot in self.cell(column, row) {
+                let room = &self.rooms[slot];
+                let Some(x) = room.local_x(point.x) else {
+                    continue;
+                };
+
+                let (height, _) = room.floor_at(x);
+                if height <= point.y && best.is_none_or(|(_, best)| height > best) {
+                    best = Some((room, height));
+                }
+            }
+        }
+
+        best
+    }
+}
+
+fn update_room_index(
+    mut index: ResMut<RoomIndex>,
+    geometry: Res<WorldGeometry>,
+    rooms: Query<(Entity, &Room)>,
+    changed: Query<(), Changed<Room>>,
+    mut removed: RemovedComponents<Room>,
+) {
+    if changed.is_empty() && removed.read().count() == 0 && !geometry.is_changed() {
+        return;
+    }
+
+    *index = RoomIndex::new(geometry.space(), rooms.iter());
+}
+
+#[test]
+fn test_room_index_room_at() {
+    let width = WrappedSpace::WORLD.width();
+    let room = |room_id, left, top, right, bottom| Room {
+        room_id,
+        rect: Rect::new(left, top, right, bottom),
+        ..Default::default()
+    };
+
+    let rooms = [
+        room(1, 100.0, -100.0, 600.0, -400.0),
+        room(2, 600.0, -100.0, 1400.0, -400.0),
+        // Across the wrap.
+        room(3, width - 200.0, -100.0, width + 200.0, -300.0),
+        // Overlapping the first, which was added before it.
+        room(4, 500.0, -300.0, 700.0, -1000.0),
+    ];
+    let index = RoomIndex::new(
+        WrappedSpace::WORLD,
+        rooms
+            .iter()
+            .enumerate()
+            .map(|(index, room)| (Entity::from_raw(index as u32), room)),
+    );
+
+    let room_at = |x, y| index.room_at(Vec2::new(x, y)).map(|room| room.room_id);
+    assert_eq!(room_at(300.0, -200.0), Some(1));
+    assert_eq!(room_at(1000.0, -200.0), Some(2));
+    assert_eq!(room_at(550.0, -350.0), Some(1));
+    assert_eq!(room_at(650.0, -800.0), Some(4));
+    assert_eq!(room_at(width - 100.0, -200.0), Some(3));
+    assert_eq!(room_at(50.0, -200.0), Some(3));
+    assert_eq!(room_at(50.0 - width, -200.0), Some(3));
+    assert_eq!(room_at(50.0 + width, -200.0), Some(3));
+    assert_eq!(room_at(300.0, 100.0), None);
+
+    let in_rect = |left, top, right, bottom| -> Vec<u32> {
+        index
+            .rooms_in(Rect::new(left, top, right, bottom))
+            .iter()
+            .map(|room| room.room_id)
+            .collect()
+    };
+    assert_eq!(in_rect(550.0, -150.0, 650.0, -350.0), [1, 2, 4]);
+    assert_eq!(in_rect(-50.0, -150.0, 150.0, -200.0), [1, 3]);
+    assert_eq!(in_rect(2000.0, 0.0, 2100.0, -50.0), Vec::<u32>::new());
+}
+
+#[test]
+fn test_room_index_nearest_floor_below() {
+    let rooms = [
+        Room {
+            room_id: 1,
+            rect: Rect::new(0.0, 0.0, 400.0, -200.0),
+            // Rises from the bottom of the room on the left to 100 above it
+            // on the right.
+            ground: vec![Vec2::new(0.0, 0.0), Vec2::new(400.0, 100.0)],
+            ..Default::default()
+        },
+        Room {
+            room_id: 2,
+            rect: Rect::new(0.0, -1200.0, 400.0, -1500.0),
+            ..Default::default()
+        },
+    ];
+    let index = RoomIndex::new(
+        WrappedSpace::WORLD,
+        rooms
+            .iter()
+            .enumerate()
+            .map(|(index, room)| (Entity::from_raw(index as u32), room)),
+    );
+
+    let below = |x, y| {
+        index
+            .nearest_floor_below(Vec2::new(x, y))
+            .map(|(room, height)| (room.room_id, height))
+    };
+    assert_eq!(below(200.0, 0.0), Some((1, -150.0)));
+    assert_eq!(below(200.0, -160.0), Some((2, -1500.0)));
+    assert_eq!(below(200.0, -5000.0), None);
+    assert_eq!(below(500.0, 0.0), None);
+}
+
+#[test]
+fn test_room_index_without_wrap() {
+    let width = WrappedSpace::WORLD.width();
+    let rooms = [
+        Room {
+            room_id: 1,
+            rect: Rect::new(width - 200.0, 0.0, width + 200.0, -100.0),
+            ..Default::default()
+        },
+        // Off the left edge of the world, which nothing wraps onto.
+        Room {
+            room_id: 2,
+            rect: Rect::new(-600.0, 0.0, -100.0, -100.0),
+            ..Default::default()
+        },
+    ];
+    let index = RoomIndex::new(
+        WrappedSpace::new(width, false),
+        rooms
+            .iter()
+            .enumerate()
+            .map(|(index, room)| (Entity::from_raw(index as u32), room)),
+    );
+
+    let room_at = |x, y| index.room_at(Vec2::new(x, y)).map(|room| room.room_id);
+    assert_eq!(room_at(width + 100.0, -50.0), Some(1));
+    assert_eq!(room_at(100.0, -50.0), None);
+    assert_eq!(room_at(-300.0, -50.0), Some(2));
+    assert_eq!(room_at(width - 300.0, -50.0), None);
+}
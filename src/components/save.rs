@@ -0,0 +1,202 @@
+//! Writing the running world back out as a .sfc file.
+
+use std::{path::PathBuf, sync::Arc};
+
+use bevy::prelude::*;
+
+use super::{
+    bacteria::RoomBacteria,
+    room::{Ambience, Room, Simulata},
+};
+use crate::{
+    formats::{
+        sfc::{self, Doc, RoomPointer},
+        WorldHandle,
+    },
+    time::WorldCalendar,
+};
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SaveWorld>();
+        app.add_systems(Update, save_world);
+    }
+}
+
+/// Asks for the world to be saved to `path`.
+#[derive(Event, Clone, Debug)]
+pub struct SaveWorld {
+    pub path: PathBuf,
+}
+
+/// Copies the state of the rooms in the ECS into the rooms of `doc` with the
+/// same id and rect. Each room in `doc` is written once, as hand-made worlds
+/// can repeat rooms. A room listed more than once by reference stays shared
+/// between its places.
+pub fn write_rooms<'a>(
+    doc: &mut Doc,
+    rooms: impl IntoIterator<Item = (&'a Room, &'a Simulata, &'a Ambience, &'a RoomBacteria)>,
+) {
+    let pointers = &mut doc.map.rooms.rooms;
+    let mut written = vec![false; pointers.len()];
+
+    for (room, simulata, ambience, bacteria) in rooms {
+        let Some(index) = pointers.iter().enumerate().position(|(index, pointer)| {
+            !written[index]
+                && pointer.room_id == room.room_id
+                && Rect::from(pointer.rect.clone()) == room.rect
+        }) else {
+            continue;
+        };
+
+        let shared = pointers[index].clone();
+        let mut pointer = RoomPointer::clone(&shared);
+
+        simulata.write_to(&mut pointer);
+        ambience.write_to(&mut pointer);
+        bacteria.write_to(&mut pointer);
+
+        if room.visited != (pointer.visited != 0) {
+            pointer.visited = room.visited as u32;
+        }
+
+        let pointer = Arc::new(pointer);
+        for (index, other) in pointers.iter_mut().enumerate() {
+            if Arc::ptr_eq(other, &shared) {
+                *other = pointer.clone();
+                written[index] = true;
+            }
+        }
+    }
+}
+
+/// Saves the world as it was loaded with the current state of its rooms and
+/// calendar.
+fn save_world(
+    mut events: EventReader<SaveWorld>,
+    world_handle: Res<WorldHandle>,
+    worlds: Res<Assets<sfc::World>>,
+    rooms: Query<(&Room, &Simulata, &Ambience, &RoomBacteria)>,
+    calendar: Res<WorldCalendar>,
+) {
+    for event in events.read() {
+        let Some(world) = worlds.get(&world_handle.0) else {
+            warn!("can't save to {}, no world is loaded", event.path.display());
+            continue;
+        };
+
+        let mut doc = world.doc.clone();
+        write_rooms(&mut doc, rooms.iter());
+        calendar.write_to(&mut doc.map.flags);
+
+        match std::fs::write(&event.path, doc.to_bytes()) {
+            Ok(()) => info!("saved the world to {}", event.path.display()),
+            Err(err) => error!(
+                "couldn't save the world to {}: {}",
+                event.path.display(),
+                err
+            ),
+        }
+    }
+}
+
+/// Room components as they're spawned from each room in `doc`.
+#[cfg(test)]
+fn room_components(doc: &Doc) -> Vec<(Room, Simulata, Ambience, RoomBacteria)> {
+    doc.map
+        .rooms
+        .rooms
+        .iter()
+        .map(|pointer| {
+            let room = Room {
+                room_id: pointer.room_id,
+                rect: pointer.rect.clone().into(),
+                visited: pointer.visited != 0,
+                ..Default::default()
+            };
+            (
+                room,
+                Simulata::from_room(pointer),
+                Ambience::from_room(pointer),
+                RoomBacteria::from_room(pointer),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_write_rooms_round_trip() {
+    use crate::formats::sfc::{ArchiveTable, CPoint};
+    use std::sync::Mutex;
+
+    let buf = include_bytes!("../../assets/fixtures/synthetic.sfc");
+    let mut registry = Arc::new(Mutex::new(ArchiveTable::empty()));
+    let (_, doc) = Doc::parse(buf, &mut registry).expect("synthetic.sfc should parse");
+    let components = room_components(&doc);
+
+    // Loading and saving without changes gives back the same file.
+    let mut saved = doc.clone();
+    write_rooms(
+        &mut saved,
+        components
+            .iter()
+            .map(|(room, simulata, ambience, bacteria)| (room, simulata, ambience, bacteria)),
+    );
+    assert_eq!(saved.to_bytes(), buf.to_vec());
+
+    let mut changed = components;
+    let (room, simulata, ambience, bacteria) = &mut changed[0];
+    room.visited = true;
+    simulata.temperature = 201;
+    simulata.heat_source = -12;
+    simulata.wind = Vec2::new(3.0, 4.0);
+    ambience.music_track = "Beach".to_string();
+    bacteria.0[0].antigen = 5;
+
+    write_rooms(
+        &mut saved,
+        changed
+            .iter()
+            .map(|(room, simulata, ambience, bacteria)| (room, simulata, ambience, bacteria)),
+    );
+
+    let bytes = saved.to_bytes();
+    let mut registry = Arc::new(Mutex::new(ArchiveTable::empty()));
+    let (_, reloaded) = Doc::parse(&bytes, &mut registry).expect("saved world should parse");
+    let pointer = &reloaded.map.rooms.rooms[0];
+
+    assert_eq!(pointer.temperature, 201);
+    assert_eq!(pointer.heat_source, -12);
+    assert_eq!(pointer.wind, CPoint { x: 3, y: -4 });
+    assert_eq!(pointer.music_track.as_str(), "Beach");
+    assert_eq!(pointer.visited, 1);
+    assert_eq!(pointer.bacterium[0].antigen, 5);
+    assert_eq!(Simulata::from_room(pointer).wind, Vec2::new(3.0, 4.0));
+}
+
+/// Saving a real world without changes gives back the same file. Worlds
+/// aren't committed, so copy one to `assets/test.sfc` and run with
+/// `--ignored`.
+#[test]
+#[ignore = "needs a Creatures 2 world at assets/test.sfc"]
+fn test_write_real_rooms_round_trip() {
+    use crate::formats::sfc::ArchiveTable;
+    use std::sync::Mutex;
+
+    let buf = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/test.sfc"))
+        .expect("assets/test.sfc should be a Creatures 2 world");
+    let mut registry = Arc::new(Mutex::new(ArchiveTable::empty()));
+    let (rest, doc) = Doc::parse(&buf, &mut registry).expect("test.sfc should parse");
+    let components = room_components(&doc);
+
+    let mut saved = doc.clone();
+    write_rooms(
+        &mut saved,
+        components
+            .iter()
+            .map(|(room, simulata, ambience, bacteria)| (room, simulata, ambience, bacteria)),
+    );
+    assert_eq!(saved.to_bytes(), buf[..buf.len() - rest.len()]);
+}
@@ -1,7 +1,12 @@
 use bevy::prelude::*;
 
+/// Size of a background tile in a Creatures 2 world, used when the world
+/// file's tile gallery doesn't say.
 pub const TILE_SIZE: Vec2 = Vec2::new(144.0, 150.0);
-pub const WRAP_AROUND_WIDTH: f32 = 58.0;
-pub const WRAP_AROUND_HEIGHT: f32 = 16.0;
-pub const WORLD_WIDTH: f32 = WRAP_AROUND_WIDTH * TILE_SIZE.x;
-pub const _WORLD_HEIGHT: f32 = WRAP_AROUND_HEIGHT * TILE_SIZE.y;
+
+/// Backgrounds are stored a column of tiles at a time, always this many tiles
+/// tall.
+pub const TILE_ROWS: u32 = 16;
+
+/// Columns of tiles in a standard Creatures 2 background.
+pub const TILE_COLUMNS: u32 = 58;
@@ -2,16 +2,26 @@ pub mod tileset;
 
 use crate::state::GameState;
 use bevy::prelude::*;
-use tileset::BackgroundTiles;
+use tileset::{BackgroundTiles, RenderTile, TileAtlas, TilePool, TileView};
 
 pub struct GameDisplayPlugin;
 
 impl Plugin for GameDisplayPlugin {
     fn build(&self, app: &mut App) {
+        app.init_resource::<TileAtlas>();
+        app.init_resource::<TilePool>();
+        app.register_type::<TileView>();
+        app.register_type::<RenderTile>();
         app.add_systems(Startup, setup);
         app.add_systems(
             Update,
-            tileset::render_background_wrapped.run_if(in_state(GameState::Running)),
+            (
+                tileset::update_tile_views,
+                tileset::load_tile_atlas,
+                tileset::render_background_tiles,
+            )
+                .chain()
+                .run_if(in_state(GameState::Running)),
         );
     }
 }
@@ -36,10 +46,7 @@ pub fn get_viewport_rect(
     ortho: &OrthographicProjection,
 ) -> Option<Rect> {
     // Get the viewport size
-    let viewport_size = match camera.logical_viewport_size() {
-        Some(size) => size,
-        None => return None,
-    };
+    let viewport_size = camera.logical_viewport_size()?;
 
     // let position = camera_transform.translation().xy();
 
@@ -1,174 +1,364 @@
-use bevy::{prelude::*, sprite::Anchor};
+//! The background, drawn from the world's background tiles.
+//!
+//! Every camera has its own tiles, shown a chunk of tiles at a time. Tiles
+//! that leave the view are hidden and handed back to a pool for the next
+//! chunk to come into view, so a frame in which no camera crosses a chunk
+//! boundary does no more than compare each camera's chunks with last
+//! frame's.
 
-use crate::camera::Cameras;
-use crate::components::utils::world_wrap_tile;
-use crate::constants::{TILE_SIZE, WORLD_WIDTH, WRAP_AROUND_HEIGHT};
+use bevy::{prelude::*, utils::HashMap};
 
 use super::get_viewport_rect;
+use crate::{formats::s16::S16Atlas, geometry::WorldGeometry};
+
+/// Width and height of a chunk, in tiles.
+pub const CHUNK_TILES: i32 = 4;
+
+/// Distance around the view that's drawn too, so tiles are in place before
+/// they scroll into view.
+const VIEW_MARGIN: f32 = 200.0;
 
 #[derive(Component, Reflect)]
 pub struct BackgroundTiles;
 
-#[derive(Component, Reflect, Debug)]
+/// The part of the world a camera shows, kept up to date from the camera by
+/// [`update_tile_views`].
+#[derive(Component, Reflect, Clone, Copy, Debug, Default, PartialEq)]
+pub struct TileView {
+    pub viewport: Rect,
+}
+
+/// Which tile a tile entity is showing, and for which camera.
+#[derive(Component, Reflect, Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct RenderTile {
-    pub camera_id: u32,
+    pub camera: Entity,
     pub index: u32,
-    pub is_wrapping: bool,
+    /// Which copy of the world the tile is drawn in: 0 for the world itself,
+    /// -1 for the copy off its left edge and 1 for the copy off its right.
+    pub side: i32,
+}
+
+/// The background's tiles, packed into one image, so that every tile is drawn
+/// from the same texture.
+#[derive(Resource, Default, Debug)]
+pub struct TileAtlas(pub Option<S16Atlas>);
+
+/// Tile entities, both those showing a tile and those waiting to be reused.
+#[derive(Resource, Default, Debug)]
+pub struct TilePool {
+    shown: HashMap<RenderTile, Entity>,
+    free: Vec<Entity>,
+    /// The chunks each camera's tiles cover.
+    chunks: HashMap<Entity, IRect>,
+}
+
+impl TilePool {
+    pub fn shown(&self) -> usize {
+        self.shown.len()
+    }
+
+    pub fn free(&self) -> usize {
+        self.free.len()
+    }
+
+    /// Hides the tiles `keep` rejects, ready to be reused.
+    fn release(&mut self, commands: &mut Commands, keep: impl Fn(&RenderTile) -> bool) {
+        let mut released = Vec::new();
+
+        self.shown.retain(|tile, &mut entity| {
+            let kept = keep(tile);
+            if !kept {
+                released.push(entity);
+            }
+            kept
+        });
+
+        for &entity in &released {
+            commands.entity(entity).insert(Visibility::Hidden);
+        }
+
+        self.free.extend(released);
+    }
 }
 
-pub fn render_background_wrapped(
+/// The chunks of tiles around a view. A tile at `x` spans right from
+/// `x * width`, but one at `y` spans down from `y * height`, hence rounding
+/// up in `y`.
+pub fn visible_chunks(geometry: &WorldGeometry, viewport: Rect) -> IRect {
+    let area = viewport.inflate(VIEW_MARGIN);
+    let tiles = IRect::new(
+        (area.min.x / geometry.tile_size.x).floor() as i32,
+        (area.min.y / geometry.tile_size.y).ceil() as i32,
+        (area.max.x / geometry.tile_size.x).floor() as i32,
+        (area.max.y / geometry.tile_size.y).ceil() as i32,
+    );
+
+    IRect {
+        min: tiles.min.div_euclid(IVec2::splat(CHUNK_TILES)),
+        max: tiles.max.div_euclid(IVec2::splat(CHUNK_TILES)),
+    }
+}
+
+/// The tiles in a range of chunks, with where each is drawn. `x` counts
+/// tile columns from the left of the world and `y` tile rows up from the top,
+/// so rows in the world are at or below zero.
+fn tiles_in(
+    geometry: &WorldGeometry,
+    camera: Entity,
+    chunks: IRect,
+) -> impl Iterator<Item = (RenderTile, IVec2)> + '_ {
+    let columns = chunks.min.x * CHUNK_TILES..(chunks.max.x + 1) * CHUNK_TILES;
+    let rows = chunks.min.y * CHUNK_TILES..(chunks.max.y + 1) * CHUNK_TILES;
+
+    rows.flat_map(move |y| columns.clone().map(move |x| IVec2::new(x, y)))
+        .filter_map(move |position| {
+            let row = u32::try_from(-position.y)
+                .ok()
+                .filter(|&row| row < geometry.rows)?;
+            let column = geometry.tile_column(position.x)?;
+            let tile = RenderTile {
+                camera,
+                index: geometry.tile_index(column, row),
+                side: position.x.div_euclid(geometry.columns as i32),
+            };
+
+            Some((tile, position))
+        })
+}
+
+/// Keeps each camera's [`TileView`] up to date, adding one to new cameras.
+pub fn update_tile_views(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    camera_query: Query<Cameras>,
-    mut rendered_tiles: Query<(&mut Transform, &mut Sprite, &RenderTile)>,
-    background_tiles_query: Single<Entity, With<BackgroundTiles>>,
+    mut cameras: Query<(
+        Entity,
+        &Camera,
+        &GlobalTransform,
+        &OrthographicProjection,
+        Option<&mut TileView>,
+    )>,
 ) {
-    let background_tiles = *background_tiles_query;
-
-    for (entity, camera, camera_transform, ortho, main_camera, creature_camera) in
-        camera_query.iter()
-    {
-        let viewport = get_viewport_rect(camera, camera_transform, ortho).unwrap();
-
-        let margin = viewport.inflate(200.0);
-        let margin = Rect::new(
-            margin.min.x / TILE_SIZE.x,
-            margin.min.y / TILE_SIZE.y,
-            margin.max.x / TILE_SIZE.x,
-            margin.max.y / TILE_SIZE.y,
-        )
-        .as_irect();
-
-        for j in margin.min.y..=margin.max.y {
-            for i in margin.min.x..=margin.max.x {
-                let x = i;
-                let y = j;
-
-                let tile_x = world_wrap_tile(x);
-                let tile_y = 0 - y;
-
-                if tile_y >= WRAP_AROUND_HEIGHT as i32 {
-                    continue;
-                }
+    for (entity, camera, transform, ortho, view) in cameras.iter_mut() {
+        let Some(viewport) = get_viewport_rect(camera, transform, ortho) else {
+            continue;
+        };
 
-                if tile_y < 0 {
-                    continue;
-                }
+        match view {
+            Some(mut view) => {
+                view.set_if_neq(TileView { viewport });
+            }
+            None => {
+                commands.entity(entity).insert(TileView { viewport });
+            }
+        }
+    }
+}
 
-                let is_wrapping_left = x < 0;
-                let is_wrapping_right = x >= WORLD_WIDTH as i32;
-                let is_wrapping = is_wrapping_left || is_wrapping_right;
-
-                let tile_index = tile_x * WRAP_AROUND_HEIGHT as u32 + tile_y as u32;
-
-                let image: Handle<Image> =
-                    asset_server.load(format!("sprites/Back.s16#{}", tile_index));
-
-                let camera_name = if main_camera.is_some() {
-                    "Main"
-                } else if creature_camera.is_some() {
-                    "Creature"
-                } else {
-                    "Unknown"
-                };
-
-                let current_tiles = rendered_tiles
-                    .iter_mut()
-                    .filter(|(_, _, rt)| {
-                        rt.camera_id == entity.index() && rt.index == tile_index && !rt.is_wrapping
-                    })
-                    .collect::<Vec<_>>();
-
-                if current_tiles.is_empty() {
-                    let mut tile =
-                        commands.spawn(spawn_tile(entity.index(), &image, tile_index, x, y, false));
-
-                    let tile_id = tile
-                        .insert(Name::new(format!("Tile:{}:{}", camera_name, tile_index)))
-                        .id();
-
-                    commands.entity(background_tiles).add_child(tile_id);
-                } else {
-                    for (mut transform, mut _sprite, rt) in current_tiles {
-                        position_tile(x, y, &mut transform.translation);
-                    }
-                }
+/// Loads the background's atlas, the first time it's needed.
+pub fn load_tile_atlas(asset_server: Res<AssetServer>, mut atlas: ResMut<TileAtlas>) {
+    if atlas.0.is_none() {
+        atlas.0 = Some(S16Atlas::load(&asset_server, "sprites/Back.s16"));
+    }
+}
 
-                if is_wrapping {
-                    let current_tiles_wrapped = rendered_tiles
-                        .iter_mut()
-                        .filter(|(_, _, rt)| {
-                            rt.camera_id == entity.index()
-                                && rt.index == tile_index
-                                && rt.is_wrapping
-                        })
-                        .collect::<Vec<_>>();
-
-                    if current_tiles_wrapped.is_empty() {
-                        let mut wrapping_tile = commands.spawn(spawn_tile(
-                            entity.index(),
-                            &image,
-                            tile_index,
-                            0 - tile_x as i32,
-                            y,
-                            true,
-                        ));
-
-                        let tile_id = wrapping_tile
-                            .insert(Name::new(format!(
-                                "WrapTile:{}:{}",
-                                camera_name, tile_index
-                            )))
-                            .id();
-
-                        commands.entity(background_tiles).add_child(tile_id);
-                    } else {
-                        for (mut transform, mut _sprite, rt) in current_tiles_wrapped {
-                            position_tile(tile_x as i32, y, &mut transform.translation);
-                        }
-                    }
-                }
+pub fn render_background_tiles(
+    mut commands: Commands,
+    mut pool: ResMut<TilePool>,
+    views: Query<(Entity, &TileView)>,
+    mut removed: RemovedComponents<TileView>,
+    geometry: Res<WorldGeometry>,
+    atlas: Res<TileAtlas>,
+    background_tiles: Single<Entity, With<BackgroundTiles>>,
+) {
+    // Tiles for another world, or for cameras that are gone, go back to the
+    // pool.
+    if geometry.is_changed() || atlas.is_changed() {
+        pool.release(&mut commands, |_| false);
+        pool.chunks.clear();
+    }
+
+    for camera in removed.read() {
+        pool.release(&mut commands, |tile| tile.camera != camera);
+        pool.chunks.remove(&camera);
+    }
+
+    let Some(atlas) = &atlas.0 else {
+        return;
+    };
+
+    for (camera, view) in views.iter() {
+        let chunks = visible_chunks(&geometry, view.viewport);
+
+        if pool.chunks.get(&camera) == Some(&chunks) {
+            continue;
+        }
+        pool.chunks.insert(camera, chunks);
+
+        let wanted: HashMap<RenderTile, IVec2> = tiles_in(&geometry, camera, chunks).collect();
+
+        pool.release(&mut commands, |tile| {
+            tile.camera != camera || wanted.contains_key(tile)
+        });
+
+        for (tile, position) in wanted {
+            if pool.shown.contains_key(&tile) {
+                continue;
             }
+
+            let bundle = (
+                Sprite::from_atlas_image(
+                    atlas.image.clone(),
+                    atlas.texture_atlas(tile.index as usize),
+                ),
+                Transform::from_translation(tile_translation(
+                    geometry.tile_size,
+                    position.x,
+                    position.y,
+                )),
+                Visibility::Inherited,
+                tile,
+                Name::new(format!("Tile:{}:{}:{}", camera, tile.index, tile.side)),
+            );
+
+            let entity = match pool.free.pop() {
+                Some(entity) => {
+                    commands.entity(entity).insert(bundle);
+                    entity
+                }
+                None => {
+                    let entity = commands.spawn(bundle).id();
+                    commands.entity(*background_tiles).add_child(entity);
+                    entity
+                }
+            };
+
+            pool.shown.insert(tile, entity);
         }
     }
 }
 
-fn position_tile(tile_x: i32, tile_y: i32, translation: &mut Vec3) {
-    let world_x = tile_x as f32 * TILE_SIZE.x;
-    let world_y = tile_y as f32 * TILE_SIZE.y;
-    let offset = Vec3::new(TILE_SIZE.x / 2.0, -(TILE_SIZE.y / 2.0), 0.0);
-    let position = offset + Vec3::new(world_x, world_y, -0.01);
+/// Centre of the tile drawn at `tile_x`, `tile_y`.
+fn tile_translation(tile_size: Vec2, tile_x: i32, tile_y: i32) -> Vec3 {
+    let world_x = tile_x as f32 * tile_size.x;
+    let world_y = tile_y as f32 * tile_size.y;
+    let offset = Vec3::new(tile_size.x / 2.0, -(tile_size.y / 2.0), 0.0);
 
-    if *translation != position {
-        *translation = position;
-    }
+    offset + Vec3::new(world_x, world_y, -0.01)
 }
 
-fn spawn_tile(
-    camera_id: u32,
-    image: &Handle<Image>,
-    tile_index: u32,
-    tile_x: i32,
-    tile_y: i32,
-    is_wrapping: bool,
-) -> impl Bundle {
-    let mut position = Vec3::new(0.0, 0.0, 0.0);
-    position_tile(tile_x, tile_y, &mut position);
-
-    (
-        Sprite {
-            image: image.clone(),
-            ..Default::default()
-        },
-        Transform {
-            translation: position,
-            ..Default::default()
-        },
-        RenderTile {
-            index: tile_index,
-            camera_id,
-            is_wrapping,
-        },
-        Anchor::TopLeft,
-    )
+#[test]
+fn test_tiles_follow_view() {
+    let mut world = World::new();
+    let geometry = WorldGeometry::default();
+    world.insert_resource(geometry);
+    world.insert_resource(TileAtlas(Some(S16Atlas {
+        image: Handle::default(),
+        layout: Handle::default(),
+    })));
+    world.init_resource::<TilePool>();
+    world.spawn(BackgroundTiles);
+
+    let view = |left: f32| TileView {
+        viewport: Rect::new(left, -100.0, left + 640.0, -580.0),
+    };
+    let camera = world.spawn(view(1000.0)).id();
+
+    // A schedule rather than `run_system_once`, so that change detection
+    // sees the world as unchanged between updates.
+    let mut schedule = Schedule::default();
+    schedule.add_systems(render_background_tiles);
+
+    let mut update = |world: &mut World, left: f32| {
+        *world.get_mut::<TileView>(camera).unwrap() = view(left);
+        schedule.run(world);
+        let tiles: Vec<Entity> = world
+            .query_filtered::<Entity, With<RenderTile>>()
+            .iter(world)
+            .collect();
+        let pool = world.resource::<TilePool>();
+        (pool.shown(), pool.free(), tiles.len())
+    };
+
+    let (shown, free, spawned) = update(&mut world, 1000.0);
+    assert!(shown > 0);
+    assert_eq!((free, spawned), (0, shown));
+
+    // Moving within the same chunks changes nothing.
+    let before = world.resource::<TilePool>().shown.clone();
+    update(&mut world, 1010.0);
+    assert_eq!(world.resource::<TilePool>().shown, before);
+
+    // Moving a whole number of chunks away reuses the tiles rather than
+    // spawning more.
+    let chunk_width = CHUNK_TILES as f32 * geometry.tile_size.x;
+    let (after, free, respawned) = update(&mut world, 1000.0 + 5.0 * chunk_width);
+    assert_eq!((after, free), (shown, 0));
+    assert_eq!(respawned, spawned);
+}
+
+#[test]
+fn test_tiles_across_wrap() {
+    use bevy::ecs::system::RunSystemOnce;
+
+    let background = |geometry: WorldGeometry| {
+        let mut world = World::new();
+        world.insert_resource(geometry);
+        world.insert_resource(TileAtlas(Some(S16Atlas {
+            image: Handle::default(),
+            layout: Handle::default(),
+        })));
+        world.init_resource::<TilePool>();
+        world.spawn(BackgroundTiles);
+        world
+    };
+
+    let geometry = WorldGeometry::default();
+    let mut world = background(geometry);
+
+    // A view over the left edge of the world.
+    world.spawn(TileView {
+        viewport: Rect::new(-300.0, -100.0, 300.0, -500.0),
+    });
+    world.run_system_once(render_background_tiles).unwrap();
+
+    let pool = world.resource::<TilePool>();
+    let sides: Vec<i32> = pool.shown.keys().map(|tile| tile.side).collect();
+    assert!(sides.contains(&-1) && sides.contains(&0));
+
+    // The last column of the world is drawn again off its left edge.
+    let last_column = geometry.tile_index(geometry.columns - 1, 0);
+    assert!(pool
+        .shown
+        .keys()
+        .any(|tile| tile.index == last_column && tile.side == -1));
+
+    // A world that doesn't wrap has nothing past its edge.
+    let mut world = background(WorldGeometry {
+        wraps: false,
+        ..geometry
+    });
+    world.spawn(TileView {
+        viewport: Rect::new(-300.0, -100.0, 300.0, -500.0),
+    });
+    world.run_system_once(render_background_tiles).unwrap();
+
+    let pool = world.resource::<TilePool>();
+    assert!(pool.shown.keys().all(|tile| tile.side == 0));
+}
+
+#[test]
+fn test_tall_tiles_in_view() {
+    // Tiles taller than the margin, and a view within the top row of them.
+    let geometry = WorldGeometry {
+        tile_size: Vec2::new(144.0, 600.0),
+        ..Default::default()
+    };
+    let viewport = Rect::new(0.0, -250.0, 640.0, -350.0);
+
+    let chunks = visible_chunks(&geometry, viewport);
+    assert_eq!((chunks.min.y, chunks.max.y), (0, 0));
+
+    let top_left = geometry.tile_index(0, 0);
+    assert!(
+        tiles_in(&geometry, Entity::PLACEHOLDER, chunks).any(|(tile, _)| tile.index == top_left)
+    );
 }
@@ -1,103 +1,103 @@
 pub mod s16;
 pub mod sfc;
+pub mod spr;
 
 use bevy::{asset::LoadedFolder, prelude::*};
-use s16::{S16AssetLoader, S16Image};
+use s16::{C16AssetLoader, S16AssetLoader, S16Image};
+use sfc::{RoomPointer, SfcAssetLoader, SimpleObject, WorldObject};
+use spr::{Palette, PaletteAssetLoader, SprAssetLoader, SprImage};
 
-use crate::camera::main_camera::MainCamera;
 use crate::state::GameState;
 
-pub struct GameFormatsPlugin;
+/// Loads the world file at `world`, relative to the assets folder.
+pub struct GameFormatsPlugin {
+    pub world: String,
+}
+
+impl Default for GameFormatsPlugin {
+    /// The world chosen with `--world`, or the default one.
+    fn default() -> Self {
+        Self {
+            world: world_path(),
+        }
+    }
+}
+
+/// Loads the sprite galleries objects and backgrounds are drawn from. The
+/// world waits for them before it starts running.
+pub struct GameSpritesPlugin;
 
 #[derive(Resource, Default)]
 struct SpriteFolder(Handle<LoadedFolder>);
 
+/// The world being played, chosen with `--world <path>` relative to the
+/// assets folder.
+#[derive(Resource, Default)]
+pub struct WorldHandle(pub Handle<sfc::World>);
+
+/// A real Creatures 2 world, which isn't committed. Tests use the synthetic
+/// world in `fixtures/` instead.
+const DEFAULT_WORLD: &str = "test.sfc";
+
 impl Plugin for GameFormatsPlugin {
     fn build(&self, app: &mut App) {
-        app.init_asset_loader::<S16AssetLoader>();
-        app.init_asset::<S16Image>();
+        app.init_asset_loader::<SfcAssetLoader>();
+        app.init_asset::<sfc::World>();
+        app.init_asset::<RoomPointer>();
+        app.init_asset::<WorldObject>();
+        app.init_asset::<SimpleObject>();
 
-        app.add_systems(Startup, setup);
+        let world = self.world.clone();
         app.add_systems(
-            Update,
-            (
-                check_sprite_loading.run_if(in_state(GameState::Loading)),
-                keyboard_scrolling.run_if(in_state(GameState::Running)),
-            ),
+            Startup,
+            move |mut commands: Commands, asset_server: Res<AssetServer>| {
+                commands.insert_resource(WorldHandle(asset_server.load(&world)));
+            },
         );
-
-        app.insert_resource(ScrollSpeed { speed: 200.0 });
+        app.add_systems(Update, check_loading.run_if(in_state(GameState::Loading)));
     }
 }
 
-fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
-    commands.insert_resource(SpriteFolder(asset_server.load_folder("sprites")));
-}
+impl Plugin for GameSpritesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset_loader::<S16AssetLoader>();
+        app.init_asset_loader::<C16AssetLoader>();
+        app.init_asset::<S16Image>();
+        app.init_asset_loader::<SprAssetLoader>();
+        app.init_asset_loader::<PaletteAssetLoader>();
+        app.init_asset::<SprImage>();
+        app.init_asset::<Palette>();
 
-fn check_sprite_loading(
-    mut next_state: ResMut<NextState<GameState>>,
-    sprite_folder: Res<SpriteFolder>,
-    mut events: EventReader<AssetEvent<LoadedFolder>>,
-) {
-    for event in events.read() {
-        if event.is_loaded_with_dependencies(&sprite_folder.0) {
-            next_state.set(GameState::Running);
-        }
+        app.add_systems(Startup, setup_sprites);
     }
 }
 
-#[derive(Resource, Default)]
-struct ScrollSpeed {
-    speed: f32,
+fn setup_sprites(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(SpriteFolder(asset_server.load_folder("sprites")));
 }
 
-fn keyboard_scrolling(
-    time: Res<Time>,
-    keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut query: Query<(&mut Transform, &mut OrthographicProjection, &Camera), With<MainCamera>>,
-    scroll_speed: Res<ScrollSpeed>,
-) {
-    for (mut transform, mut ortho, _) in query.iter_mut() {
-        let mut direction = Vec3::ZERO;
-
-        if keyboard_input.pressed(KeyCode::KeyA) {
-            direction -= Vec3::new(1.0, 0.0, 0.0);
-        }
-
-        if keyboard_input.pressed(KeyCode::KeyD) {
-            direction += Vec3::new(1.0, 0.0, 0.0);
-        }
-
-        if keyboard_input.pressed(KeyCode::KeyW) {
-            direction += Vec3::new(0.0, 1.0, 0.0);
-        }
-
-        if keyboard_input.pressed(KeyCode::KeyS) {
-            direction -= Vec3::new(0.0, 1.0, 0.0);
-        }
-
-        if keyboard_input.pressed(KeyCode::KeyZ) {
-            ortho.scale = ortho.scale.lerp(ortho.scale + 2.0, time.delta_secs());
-        }
-
-        if keyboard_input.pressed(KeyCode::KeyX) {
-            ortho.scale = ortho.scale.lerp(ortho.scale - 2.0, time.delta_secs());
-        }
-
-        // ortho.scale = ortho.scale.clamp(0.80, 2.0);
+fn world_path() -> String {
+    std::env::args()
+        .skip_while(|arg| arg != "--world")
+        .nth(1)
+        .unwrap_or_else(|| DEFAULT_WORLD.to_string())
+}
 
-        direction *= ortho.scale;
+fn check_loading(
+    mut next_state: ResMut<NextState<GameState>>,
+    asset_server: Res<AssetServer>,
+    sprite_folder: Option<Res<SpriteFolder>>,
+    world: Res<WorldHandle>,
+) {
+    // A world that fails to load has already been reported by the asset
+    // server, carry on with an empty world rather than stay on loading.
+    let world_done = asset_server.is_loaded_with_dependencies(&world.0)
+        || asset_server.load_state(&world.0).is_failed();
 
-        let z = transform.translation.z;
-        transform.translation += time.delta_secs() * direction * scroll_speed.speed;
-        // Important! We need to restore the Z values when moving the camera around.
-        // Bevy has a specific camera setup and this can mess with how our layers are shown.
-        transform.translation.z = z;
+    let sprites_done = sprite_folder
+        .is_none_or(|sprite_folder| asset_server.is_loaded_with_dependencies(&sprite_folder.0));
 
-        transform.translation = Vec3 {
-            x: transform.translation.x.round(),
-            y: transform.translation.y.round(),
-            z: transform.translation.z.round(),
-        };
+    if sprites_done && world_done {
+        next_state.set(GameState::Running);
     }
 }
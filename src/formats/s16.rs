@@ -1,48 +1,147 @@
+pub mod codec;
+
 use bevy::{
-    asset::{io::Reader, AssetLoader, LoadContext, RenderAssetUsages},
+    asset::{io::Reader, AssetLoader, AssetPath, LoadContext, RenderAssetUsages},
+    image::TextureFormatPixelInfo,
     prelude::*,
     render::render_resource::{Extent3d, TextureDimension, TextureFormat},
 };
 
-use nom::{
-    multi::count,
-    number::complete::{le_u16, le_u32},
-    sequence::tuple,
-    IResult,
-};
+use codec::{Rgba8Options, S16Error, S16File, S16Format, S16Frame};
+use serde::{Deserialize, Serialize};
 use std::fmt::Display;
 
 #[derive(Debug, Default)]
 #[allow(dead_code)]
 pub struct S16ImageInfo {
-    pub offset: u32,
     pub width: u16,
     pub height: u16,
     pub image: Handle<Image>,
 }
 
-#[derive(Debug, Default)]
-pub enum S16ImageFormat {
-    #[default]
-    Rgb555 = 0,
-    Rgb565 = 1,
-}
-
 #[derive(Debug, Asset, TypePath, Default)]
 #[allow(dead_code)]
 pub struct S16Image {
     pub name: String,
     pub images: Vec<S16ImageInfo>,
-    pub format: S16ImageFormat,
+    pub format: S16Format,
     pub image_count: u16,
+    /// Every frame packed into one image, unless the loader settings turn
+    /// it off.
+    pub atlas: Option<S16Atlas>,
+}
+
+/// A sprite's frames packed into one image, labeled `atlas`, with a layout,
+/// labeled `layout`, whose texture indices are frame numbers. A `Sprite`
+/// showing frame 3 of `Back.s16` is then
+///
+/// ```ignore
+/// let atlas = S16Atlas::load(&asset_server, "sprites/Back.s16");
+/// Sprite::from_atlas_image(atlas.image.clone(), atlas.texture_atlas(3))
+/// ```
+#[derive(Debug, Clone)]
+pub struct S16Atlas {
+    pub image: Handle<Image>,
+    pub layout: Handle<TextureAtlasLayout>,
+}
+
+impl S16Atlas {
+    /// The atlas of the sprite file at `path`, from the labels of the same
+    /// [`S16Image`] that loading the file any other way gives.
+    pub fn load(asset_server: &AssetServer, path: &str) -> Self {
+        let path = AssetPath::parse(path).into_owned();
+
+        Self {
+            image: asset_server.load(path.clone().with_label("atlas")),
+            layout: asset_server.load(path.with_label("layout")),
+        }
+    }
+
+    /// Where frame `index` is in the atlas.
+    pub fn texture_atlas(&self, index: usize) -> TextureAtlas {
+        TextureAtlas {
+            layout: self.layout.clone(),
+            index,
+        }
+    }
+}
+
+/// Transparent pixels between frames in an atlas, so filtering doesn't blend
+/// in the edges of neighbouring frames.
+const ATLAS_PADDING: u32 = 1;
+
+/// How the S16 and C16 loaders turn pixels into images, set in an asset's
+/// `.meta` file.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct S16LoaderSettings {
+    /// Pixels with this value in the file are transparent, black by default.
+    /// With `None`, every pixel is opaque.
+    pub colour_key: Option<u16>,
+    /// Fill the low bits of each channel from its high bits, so white is
+    /// 255, rather than leave them clear.
+    pub expand: bool,
+    /// Multiply colours by alpha, for sprites drawn with premultiplied
+    /// blending.
+    pub premultiplied: bool,
+    /// Also pack the frames into an [`S16Atlas`], which is how sprites are
+    /// drawn, so it's on unless a `.meta` file turns it off.
+    pub atlas: bool,
+    pub texture: S16TextureFormat,
+}
+
+/// The kind of texture the loaders make of each frame.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum S16TextureFormat {
+    /// 8 bit RGBA, made as the other settings say.
+    #[default]
+    Rgba8,
+    /// Two byte RGB565 pixels, half the size of RGBA, with 555 files
+    /// converted and the colour key black. wgpu has no packed 16 bit colour
+    /// format, so these are `R16Uint` textures for a shader to unpack, which
+    /// `Sprite` can't draw. Only `colour_key` applies to them.
+    Rgb565,
+}
+
+impl S16TextureFormat {
+    fn texture_format(self) -> TextureFormat {
+        match self {
+            S16TextureFormat::Rgba8 => TextureFormat::Rgba8UnormSrgb,
+            S16TextureFormat::Rgb565 => TextureFormat::R16Uint,
+        }
+    }
+
+    fn frame_data(
+        self,
+        frame: &S16Frame,
+        format: S16Format,
+        settings: &S16LoaderSettings,
+    ) -> Vec<u8> {
+        match self {
+            S16TextureFormat::Rgba8 => frame.to_rgba8_with(format, (*settings).into()),
+            S16TextureFormat::Rgb565 => frame.to_rgb565(format, settings.colour_key),
+        }
+    }
+}
+
+impl Default for S16LoaderSettings {
+    fn default() -> Self {
+        let options = Rgba8Options::default();
+        Self {
+            colour_key: options.colour_key,
+            expand: options.expand,
+            premultiplied: options.premultiplied,
+            atlas: true,
+            texture: S16TextureFormat::default(),
+        }
+    }
 }
 
-impl From<u32> for S16ImageFormat {
-    fn from(value: u32) -> Self {
-        match value {
-            0 => S16ImageFormat::Rgb555,
-            1 => S16ImageFormat::Rgb565,
-            _ => panic!("Invalid S16 image format"),
+impl From<S16LoaderSettings> for Rgba8Options {
+    fn from(settings: S16LoaderSettings) -> Self {
+        Self {
+            colour_key: settings.colour_key,
+            expand: settings.expand,
+            premultiplied: settings.premultiplied,
         }
     }
 }
@@ -50,18 +149,23 @@ impl From<u32> for S16ImageFormat {
 #[derive(Default)]
 pub struct S16AssetLoader;
 
+/// Loads run-length encoded C16 sprites into the same [`S16Image`] as
+/// S16 files, with frames labeled the same way, e.g. `Norn.c16#3`.
+#[derive(Default)]
+pub struct C16AssetLoader;
+
 #[non_exhaustive]
 #[derive(Debug)]
 pub enum S16AssetLoaderError {
     Io(std::io::Error),
-    Parse(nom::Err<String>),
+    Decode(S16Error),
 }
 
 impl std::error::Error for S16AssetLoaderError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
             S16AssetLoaderError::Io(e) => Some(e),
-            S16AssetLoaderError::Parse(e) => Some(e),
+            S16AssetLoaderError::Decode(e) => Some(e),
         }
     }
 }
@@ -73,12 +177,27 @@ impl Display for S16AssetLoaderError {
 }
 
 impl S16Image {
-    pub fn from_bytes<'a>(
-        buffer: &'a [u8],
+    /// Decodes a sprite file, adding each frame as an image labeled with its
+    /// index, e.g. `Back.s16#3`.
+    pub fn from_bytes(
+        buffer: &[u8],
+        settings: &S16LoaderSettings,
         load_context: &mut LoadContext<'_>,
-    ) -> IResult<&'a [u8], S16Image> {
-        let mut images = vec![];
+    ) -> Result<S16Image, S16Error> {
+        Ok(Self::from_file(
+            S16File::decode(buffer)?,
+            settings,
+            load_context,
+        ))
+    }
 
+    /// The frames of an S16 or C16 file, labeled as [`S16Image::from_bytes`]
+    /// labels them.
+    pub fn from_file(
+        file: S16File,
+        settings: &S16LoaderSettings,
+        load_context: &mut LoadContext<'_>,
+    ) -> S16Image {
         let name = load_context
             .path()
             .file_name()
@@ -86,89 +205,139 @@ impl S16Image {
             .to_string_lossy()
             .to_string();
 
-        let (bytes, (format, number_of_images)) = tuple((le_u32, le_u16))(buffer)?;
-        let (bytes, bitmap_offsets) =
-            count(tuple((le_u32, le_u16, le_u16)), number_of_images as usize)(bytes)?;
-
-        for (offset_index, &bitmap) in bitmap_offsets.iter().enumerate() {
-            let (offset, width, height) = bitmap;
-
-            let dst_bitmap_size = width as usize * height as usize * 4;
-            let src_bitmap =
-                &buffer[offset as usize..(width as usize * height as usize * 2 + offset as usize)];
-            let mut buffer = vec![0u8; dst_bitmap_size];
-
-            let (bytes, pixels) = count(le_u16, width as usize * height as usize)(src_bitmap)?;
-            let mut index = 0;
-            for pixel in pixels {
-                let (red, green, blue) = match format.into() {
-                    S16ImageFormat::Rgb565 => (
-                        ((pixel & 0xf800) >> 8) as u8,
-                        ((pixel & 0x07e0) >> 3) as u8,
-                        ((pixel & 0x001f) << 3) as u8,
-                    ),
-                    S16ImageFormat::Rgb555 => (
-                        ((pixel & 0x7c00) >> 7) as u8,
-                        ((pixel & 0x03e0) >> 2) as u8,
-                        ((pixel & 0x001f) << 3) as u8,
-                    ),
-                };
-
-                buffer[index] = red;
-                buffer[index + 1] = green;
-                buffer[index + 2] = blue;
-                buffer[index + 3] = if red as u32 + green as u32 + blue as u32 == 0 {
-                    0
-                } else {
-                    255
-                };
-                index += 4;
+        let texture_format = settings.texture.texture_format();
+        let pixels: Vec<Vec<u8>> = file
+            .frames
+            .iter()
+            .map(|frame| settings.texture.frame_data(frame, file.format, settings))
+            .collect();
+
+        let atlas = settings.atlas.then(|| {
+            let sizes: Vec<UVec2> = file
+                .frames
+                .iter()
+                .map(|frame| UVec2::new(frame.width as u32, frame.height as u32))
+                .collect();
+            let layout = atlas_layout(&sizes);
+            let image = atlas_image(&layout, &pixels, texture_format);
+
+            S16Atlas {
+                image: load_context.add_labeled_asset("atlas".to_string(), image),
+                layout: load_context.add_labeled_asset("layout".to_string(), layout),
             }
+        });
+
+        let images = file
+            .frames
+            .iter()
+            .zip(pixels)
+            .enumerate()
+            .map(|(index, (frame, pixels))| {
+                let image = frame_image(
+                    UVec2::new(frame.width as u32, frame.height as u32),
+                    pixels,
+                    texture_format,
+                );
 
-            assert!(bytes.is_empty());
-
-            let image = Image::new_fill(
-                Extent3d {
-                    width: width as u32,
-                    height: height as u32,
-                    ..Default::default()
-                },
-                TextureDimension::D2,
-                &buffer,
-                TextureFormat::Rgba8UnormSrgb,
-                RenderAssetUsages::default(),
-            );
-
-            let image_handle = load_context.add_labeled_asset(format!("{}", offset_index), image);
-
-            images.push(S16ImageInfo {
-                offset,
-                width,
-                height,
-                image: image_handle,
-            });
+                S16ImageInfo {
+                    width: frame.width,
+                    height: frame.height,
+                    image: load_context.add_labeled_asset(format!("{}", index), image),
+                }
+            })
+            .collect();
+
+        S16Image {
+            name,
+            images,
+            format: file.format,
+            image_count: file.frames.len() as u16,
+            atlas,
         }
+    }
+}
 
-        Ok((
-            bytes,
-            S16Image {
-                name,
-                images,
-                format: format.into(),
-                image_count: number_of_images,
-            },
-        ))
+fn frame_image(size: UVec2, pixels: Vec<u8>, format: TextureFormat) -> Image {
+    Image::new(
+        Extent3d {
+            width: size.x,
+            height: size.y,
+            ..Default::default()
+        },
+        TextureDimension::D2,
+        pixels,
+        format,
+        RenderAssetUsages::default(),
+    )
+}
+
+/// Places frames of the given sizes in rows, in order, so that the
+/// layout's texture indices are frame numbers. Rows are about as wide as
+/// the atlas is high.
+fn atlas_layout(sizes: &[UVec2]) -> TextureAtlasLayout {
+    let padded = |size: UVec2| size + UVec2::splat(ATLAS_PADDING);
+    let area: u64 = sizes
+        .iter()
+        .map(|&size| padded(size).as_u64vec2().element_product())
+        .sum();
+    let widest = sizes.iter().map(|size| size.x).max().unwrap_or(0);
+    let row_width = widest.max((area as f64).sqrt().ceil() as u32);
+
+    let mut rects = Vec::with_capacity(sizes.len());
+    let mut position = UVec2::ZERO;
+    let mut row_height = 0;
+
+    for &size in sizes {
+        if position.x > 0 && position.x + size.x > row_width {
+            position = UVec2::new(0, position.y + row_height);
+            row_height = 0;
+        }
+
+        rects.push(URect::from_corners(position, position + size));
+        position.x += padded(size).x;
+        row_height = row_height.max(padded(size).y);
+    }
+
+    let extent = rects
+        .iter()
+        .fold(UVec2::ONE, |extent, rect| extent.max(rect.max));
+
+    let mut layout = TextureAtlasLayout::new_empty(extent);
+    for rect in rects {
+        layout.add_texture(rect);
+    }
+    layout
+}
+
+/// Copies each frame's pixels into its place in the layout.
+fn atlas_image(layout: &TextureAtlasLayout, frames: &[Vec<u8>], format: TextureFormat) -> Image {
+    let pixel_size = format.pixel_size();
+    let stride = layout.size.x as usize * pixel_size;
+    let mut pixels = vec![0; stride * layout.size.y as usize];
+
+    for (rect, frame) in layout.textures.iter().zip(frames) {
+        let row_bytes = rect.width() as usize * pixel_size;
+        if row_bytes == 0 {
+            continue;
+        }
+
+        for (y, row) in frame.chunks_exact(row_bytes).enumerate() {
+            let start = (rect.min.y as usize + y) * stride + rect.min.x as usize * pixel_size;
+            pixels[start..start + row_bytes].copy_from_slice(row);
+        }
     }
+
+    frame_image(layout.size, pixels, format)
 }
 
 impl AssetLoader for S16AssetLoader {
     type Asset = S16Image;
-    type Settings = ();
+    type Settings = S16LoaderSettings;
     type Error = S16AssetLoaderError;
     async fn load(
         &self,
         reader: &mut dyn Reader,
-        _settings: &(),
+        settings: &S16LoaderSettings,
         load_context: &mut LoadContext<'_>,
     ) -> Result<Self::Asset, Self::Error> {
         let mut bytes = Vec::new();
@@ -177,15 +346,176 @@ impl AssetLoader for S16AssetLoader {
             .await
             .map_err(S16AssetLoaderError::Io)?;
 
-        match S16Image::from_bytes(&bytes, load_context) {
-            Ok((_, image)) => Ok(image),
-            Err(e) => Err(S16AssetLoaderError::Parse(
-                e.map(|e| e.code.description().to_string()),
-            )),
-        }
+        S16Image::from_bytes(&bytes, settings, load_context).map_err(S16AssetLoaderError::Decode)
     }
 
     fn extensions(&self) -> &[&str] {
         &["s16", "S16"]
     }
 }
+
+impl AssetLoader for C16AssetLoader {
+    type Asset = S16Image;
+    type Settings = S16LoaderSettings;
+    type Error = S16AssetLoaderError;
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        settings: &S16LoaderSettings,
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(S16AssetLoaderError::Io)?;
+
+        let file = S16File::decode_c16(&bytes).map_err(S16AssetLoaderError::Decode)?;
+        Ok(S16Image::from_file(file, settings, load_context))
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["c16", "C16"]
+    }
+}
+
+#[test]
+fn test_atlas_layout() {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    let mut rng = StdRng::seed_from_u64(2);
+
+    for _ in 0..100 {
+        let sizes: Vec<UVec2> = (0..rng.gen_range(0..40))
+            .map(|_| UVec2::new(rng.gen_range(0..60), rng.gen_range(0..60)))
+            .collect();
+        let layout = atlas_layout(&sizes);
+
+        assert_eq!(layout.len(), sizes.len());
+        for (index, (rect, size)) in layout.textures.iter().zip(&sizes).enumerate() {
+            assert_eq!(rect.size(), *size, "frame {} keeps its size", index);
+            assert!(rect.max.cmple(layout.size).all());
+
+            // Frames don't touch, so filtering doesn't blend them.
+            for other in &layout.textures[..index] {
+                let padded =
+                    URect::from_corners(other.min, other.max + UVec2::splat(ATLAS_PADDING));
+                assert!(rect.is_empty() || other.is_empty() || rect.intersect(padded).is_empty());
+            }
+        }
+    }
+}
+
+#[test]
+fn test_atlas_image() {
+    let red = [255, 0, 0, 255];
+    let blue = [0, 0, 255, 255];
+    let frames = vec![red.repeat(2 * 3), Vec::new(), blue.repeat(4)];
+    let layout = atlas_layout(&[UVec2::new(2, 3), UVec2::ZERO, UVec2::new(1, 4)]);
+    let image = atlas_image(&layout, &frames, TextureFormat::Rgba8UnormSrgb);
+
+    let pixel = |position: UVec2| {
+        let start = (position.y * layout.size.x + position.x) as usize * 4;
+        image.data[start..start + 4].to_vec()
+    };
+
+    for (rect, colour) in [(layout.textures[0], red), (layout.textures[2], blue)] {
+        assert_eq!(pixel(rect.min), colour);
+        assert_eq!(pixel(rect.max - UVec2::ONE), colour);
+    }
+    assert_eq!(
+        image.data.iter().filter(|&&byte| byte == 255).count(),
+        (6 + 4) * 2
+    );
+}
+
+#[test]
+fn test_atlas_image_rgb565() {
+    let red = 0xf800u16.to_le_bytes();
+    let frames = vec![red.repeat(2), red.repeat(3)];
+    let layout = atlas_layout(&[UVec2::new(2, 1), UVec2::new(1, 3)]);
+    let image = atlas_image(&layout, &frames, TextureFormat::R16Uint);
+
+    assert_eq!(image.texture_descriptor.format, TextureFormat::R16Uint);
+    assert_eq!(image.data.len(), layout.size.element_product() as usize * 2);
+    for rect in &layout.textures {
+        let start = (rect.min.y * layout.size.x + rect.min.x) as usize * 2;
+        assert_eq!(image.data[start..start + 2], red);
+    }
+    assert_eq!(
+        image
+            .data
+            .chunks_exact(2)
+            .filter(|&pixel| pixel == red)
+            .count(),
+        5
+    );
+}
+
+#[test]
+fn test_atlas_shares_the_sprite_folder_load() {
+    use super::GameSpritesPlugin;
+    use codec::S16Frame;
+    use std::time::Duration;
+
+    let folder = std::env::temp_dir().join(format!("atlas-test-{}", std::process::id()));
+    std::fs::create_dir_all(folder.join("sprites")).unwrap();
+    let file = S16File {
+        format: S16Format::Rgb565,
+        frames: vec![
+            S16Frame {
+                width: 2,
+                height: 1,
+                pixels: vec![0xf800, 0x001f],
+            };
+            3
+        ],
+    };
+    std::fs::write(folder.join("sprites/Toy.s16"), file.encode().unwrap()).unwrap();
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        AssetPlugin {
+            file_path: folder.to_string_lossy().to_string(),
+            ..Default::default()
+        },
+        GameSpritesPlugin,
+    ));
+    app.init_asset::<Image>();
+    app.init_asset::<TextureAtlasLayout>();
+    app.update();
+
+    let atlas = S16Atlas::load(app.world().resource::<AssetServer>(), "sprites/Toy.s16");
+    let sprite: Handle<S16Image> = app
+        .world()
+        .resource::<AssetServer>()
+        .load("sprites/Toy.s16");
+
+    for _ in 0..1000 {
+        if app
+            .world()
+            .resource::<AssetServer>()
+            .is_loaded_with_dependencies(&sprite)
+        {
+            break;
+        }
+        app.update();
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    std::fs::remove_dir_all(&folder).unwrap();
+
+    // The folder's load, with the default settings, packed the atlas that
+    // `S16Atlas::load` points at.
+    let loaded = app.world().resource::<Assets<S16Image>>().get(&sprite);
+    let loaded = loaded.and_then(|sprite| sprite.atlas.as_ref()).unwrap();
+    assert_eq!(loaded.image.id(), atlas.image.id());
+    assert_eq!(loaded.layout.id(), atlas.layout.id());
+    assert_eq!(
+        app.world()
+            .resource::<Assets<TextureAtlasLayout>>()
+            .get(&atlas.layout)
+            .map(TextureAtlasLayout::len),
+        Some(3)
+    );
+}
@@ -0,0 +1,820 @@
+//! Reading and writing S16 and C16 sprite files without Bevy, for tools as
+//! well as the asset loaders.
+//!
+//! An S16 file is a header giving the pixel format and the number of frames,
+//! a table of each frame's offset and size, then each frame's pixels, 16 bits
+//! apiece, one row after another. Black is transparent.
+//!
+//! A C16 file is laid out the same way, but each frame's table entry gives
+//! the offset of every line, and lines are run-length encoded: a 16 bit tag
+//! whose low bit says whether a run is transparent or coloured and whose
+//! other bits give its length, followed by the pixels of a coloured run. A
+//! zero tag ends a line, and another ends the frame.
+
+use std::fmt::Display;
+
+/// How the 16 bits of a pixel are split between red, green and blue.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum S16Format {
+    #[default]
+    Rgb555 = 0,
+    Rgb565 = 1,
+}
+
+impl S16Format {
+    /// Splits a pixel into 8 bit red, green and blue, filling the low bits
+    /// of each channel from its high bits so that white is 255.
+    /// [`S16Format::pack`] drops them again, getting the pixel back.
+    pub fn unpack(self, pixel: u16) -> [u8; 3] {
+        let [red, green, blue] = self.unpack_shifted(pixel);
+        let five = |channel: u8| channel | (channel >> 5);
+        let six = |channel: u8| channel | (channel >> 6);
+
+        match self {
+            S16Format::Rgb565 => [five(red), six(green), five(blue)],
+            S16Format::Rgb555 => [five(red), five(green), five(blue)],
+        }
+    }
+
+    /// Splits a pixel into 8 bit red, green and blue, leaving the low bits
+    /// of each channel clear.
+    pub fn unpack_shifted(self, pixel: u16) -> [u8; 3] {
+        match self {
+            S16Format::Rgb565 => [
+                ((pixel & 0xf800) >> 8) as u8,
+                ((pixel & 0x07e0) >> 3) as u8,
+                ((pixel & 0x001f) << 3) as u8,
+            ],
+            S16Format::Rgb555 => [
+                ((pixel & 0x7c00) >> 7) as u8,
+                ((pixel & 0x03e0) >> 2) as u8,
+                ((pixel & 0x001f) << 3) as u8,
+            ],
+        }
+    }
+
+    /// Packs 8 bit red, green and blue into a pixel, dropping the bits the
+    /// format has no room for.
+    pub fn pack(self, [red, green, blue]: [u8; 3]) -> u16 {
+        let (red, green, blue) = (red as u16, green as u16, blue as u16);
+
+        match self {
+            S16Format::Rgb565 => ((red >> 3) << 11) | ((green >> 2) << 5) | (blue >> 3),
+            S16Format::Rgb555 => ((red >> 3) << 10) | ((green >> 3) << 5) | (blue >> 3),
+        }
+    }
+}
+
+impl TryFrom<u32> for S16Format {
+    type Error = S16Error;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(S16Format::Rgb555),
+            1 => Ok(S16Format::Rgb565),
+            _ => Err(S16Error::UnknownFormat(value)),
+        }
+    }
+}
+
+/// A frame of a sprite, as the 16 bit pixels stored in the file.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct S16Frame {
+    pub width: u16,
+    pub height: u16,
+    pub pixels: Vec<u16>,
+}
+
+/// How [`S16Frame::to_rgba8_with`] turns pixels into RGBA.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgba8Options {
+    /// Pixels with this value in the file are transparent. With `None`,
+    /// every pixel is opaque.
+    pub colour_key: Option<u16>,
+    /// Fill the low bits of each channel, as [`S16Format::unpack`] does,
+    /// rather than leave them clear.
+    pub expand: bool,
+    /// Multiply colours by alpha, so transparent pixels are black whatever
+    /// the colour key.
+    pub premultiplied: bool,
+}
+
+impl Default for Rgba8Options {
+    fn default() -> Self {
+        Self {
+            colour_key: Some(0),
+            expand: true,
+            premultiplied: false,
+        }
+    }
+}
+
+impl S16Frame {
+    /// The frame as 8 bit RGBA, with black transparent and everything else
+    /// opaque.
+    pub fn to_rgba8(&self, format: S16Format) -> Vec<u8> {
+        self.to_rgba8_with(format, Rgba8Options::default())
+    }
+
+    pub fn to_rgba8_with(&self, format: S16Format, options: Rgba8Options) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|&pixel| {
+                let colour = if options.expand {
+                    format.unpack(pixel)
+                } else {
+                    format.unpack_shifted(pixel)
+                };
+
+                if options.colour_key != Some(pixel) {
+                    let [red, green, blue] = colour;
+                    [red, green, blue, u8::MAX]
+                } else if options.premultiplied {
+                    [0; 4]
+                } else {
+                    let [red, green, blue] = colour;
+                    [red, green, blue, 0]
+                }
+            })
+            .collect()
+    }
+
+    /// The frame as little-endian RGB565 pixels, whatever the file's
+    /// format, with pixels of the colour key black.
+    pub fn to_rgb565(&self, format: S16Format, colour_key: Option<u16>) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|&pixel| {
+                if colour_key == Some(pixel) {
+                    0
+                } else {
+                    S16Format::Rgb565.pack(format.unpack_shifted(pixel))
+                }
+                .to_le_bytes()
+            })
+            .collect()
+    }
+
+    /// A frame from 8 bit RGBA. Transparent pixels, and those too dark for
+    /// the format to tell from black, become black.
+    pub fn from_rgba8(
+        format: S16Format,
+        width: u16,
+        height: u16,
+        rgba: &[u8],
+    ) -> Result<Self, S16Error> {
+        let expected = width as usize * height as usize * 4;
+        if rgba.len() != expected {
+            return Err(S16Error::WrongSize {
+                expected,
+                found: rgba.len(),
+            });
+        }
+
+        let pixels = rgba
+            .chunks_exact(4)
+            .map(|pixel| {
+                if pixel[3] == 0 {
+                    0
+                } else {
+                    format.pack([pixel[0], pixel[1], pixel[2]])
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+}
+
+/// A whole S16 file.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct S16File {
+    pub format: S16Format,
+    pub frames: Vec<S16Frame>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum S16Error {
+    /// The file ended before `needed` bytes at `offset`.
+    UnexpectedEof {
+        offset: usize,
+        needed: usize,
+    },
+    UnknownFormat(u32),
+    TooManyFrames(usize),
+    /// Pixels given for a frame don't match its size, in bytes.
+    WrongSize {
+        expected: usize,
+        found: usize,
+    },
+    /// A C16 run carries on past the end of its line.
+    RunTooLong {
+        frame: usize,
+        line: usize,
+    },
+    /// A C16 frame has more than [`MAX_C16_PIXELS`] pixels.
+    FrameTooBig {
+        frame: usize,
+        width: u16,
+        height: u16,
+    },
+}
+
+impl std::error::Error for S16Error {}
+
+impl Display for S16Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            S16Error::UnexpectedEof { offset, needed } => {
+                write!(f, "file ends before {} bytes at {}", needed, offset)
+            }
+            S16Error::UnknownFormat(format) => write!(f, "unknown pixel format {}", format),
+            S16Error::TooManyFrames(frames) => {
+                write!(f, "{} frames is more than an S16 file can hold", frames)
+            }
+            S16Error::WrongSize { expected, found } => {
+                write!(f, "expected {} bytes of pixels, found {}", expected, found)
+            }
+            S16Error::RunTooLong { frame, line } => {
+                write!(f, "a run is longer than line {} of frame {}", line, frame)
+            }
+            S16Error::FrameTooBig {
+                frame,
+                width,
+                height,
+            } => write!(
+                f,
+                "frame {} is {}x{}, more than {} pixels",
+                frame, width, height, MAX_C16_PIXELS
+            ),
+        }
+    }
+}
+
+fn bytes_at(input: &[u8], offset: usize, needed: usize) -> Result<&[u8], S16Error> {
+    offset
+        .checked_add(needed)
+        .and_then(|end| input.get(offset..end))
+        .ok_or(S16Error::UnexpectedEof { offset, needed })
+}
+
+fn u16_at(input: &[u8], offset: usize) -> Result<u16, S16Error> {
+    let bytes = bytes_at(input, offset, 2)?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn u32_at(input: &[u8], offset: usize) -> Result<u32, S16Error> {
+    let bytes = bytes_at(input, offset, 4)?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Bytes before the frame table.
+const HEADER_SIZE: usize = 6;
+/// Bytes of each frame's entry in the frame table.
+const ENTRY_SIZE: usize = 8;
+
+/// Set in a C16 file's flags when pixels are 565 rather than 555.
+const C16_RGB565: u32 = 1;
+/// Always set in a C16 file's flags.
+const C16_COMPRESSED: u32 = 2;
+
+/// Low bit of a C16 run tag, set for a run of colour.
+const C16_COLOUR_RUN: u16 = 1;
+
+/// Most pixels a C16 frame can decode to. A two byte tag can stand for
+/// thousands of transparent pixels, so the size of the file doesn't limit
+/// the size of its frames.
+pub const MAX_C16_PIXELS: usize = 4096 * 4096;
+
+impl S16File {
+    pub fn decode(input: &[u8]) -> Result<Self, S16Error> {
+        let format = S16Format::try_from(u32_at(input, 0)?)?;
+        let count = u16_at(input, 4)? as usize;
+
+        let frames = (0..count)
+            .map(|frame| {
+                let entry = HEADER_SIZE + frame * ENTRY_SIZE;
+                let offset = u32_at(input, entry)? as usize;
+                let width = u16_at(input, entry + 4)?;
+                let height = u16_at(input, entry + 6)?;
+
+                let size = width as usize * height as usize;
+                let pixels = bytes_at(input, offset, size * 2)?
+                    .chunks_exact(2)
+                    .map(|pixel| u16::from_le_bytes([pixel[0], pixel[1]]))
+                    .collect();
+
+                Ok(S16Frame {
+                    width,
+                    height,
+                    pixels,
+                })
+            })
+            .collect::<Result<_, S16Error>>()?;
+
+        Ok(Self { format, frames })
+    }
+
+    /// The file's bytes, frames laid out in order straight after the frame
+    /// table.
+    pub fn encode(&self) -> Result<Vec<u8>, S16Error> {
+        let count = u16::try_from(self.frames.len())
+            .map_err(|_| S16Error::TooManyFrames(self.frames.len()))?;
+
+        let mut table = Vec::with_capacity(HEADER_SIZE + self.frames.len() * ENTRY_SIZE);
+        table.extend((self.format as u32).to_le_bytes());
+        table.extend(count.to_le_bytes());
+
+        let mut pixels = Vec::new();
+        let mut offset = HEADER_SIZE + self.frames.len() * ENTRY_SIZE;
+
+        for frame in &self.frames {
+            let size = frame.width as usize * frame.height as usize;
+            if frame.pixels.len() != size {
+                return Err(S16Error::WrongSize {
+                    expected: size * 2,
+                    found: frame.pixels.len() * 2,
+                });
+            }
+
+            table.extend((offset as u32).to_le_bytes());
+            table.extend(frame.width.to_le_bytes());
+            table.extend(frame.height.to_le_bytes());
+
+            pixels.extend(frame.pixels.iter().flat_map(|pixel| pixel.to_le_bytes()));
+            offset += size * 2;
+        }
+
+        table.extend(pixels);
+        Ok(table)
+    }
+
+    pub fn decode_c16(input: &[u8]) -> Result<Self, S16Error> {
+        let flags = u32_at(input, 0)?;
+        if flags & !C16_RGB565 != C16_COMPRESSED {
+            return Err(S16Error::UnknownFormat(flags));
+        }
+        let format = S16Format::try_from(flags & C16_RGB565)?;
+        let count = u16_at(input, 4)? as usize;
+
+        let mut entry = HEADER_SIZE;
+        let mut frames = Vec::with_capacity(count);
+
+        for frame in 0..count {
+            let first_line = u32_at(input, entry)?;
+            let width = u16_at(input, entry + 4)? as usize;
+            let height = u16_at(input, entry + 6)? as usize;
+            entry += ENTRY_SIZE;
+
+            if width * height > MAX_C16_PIXELS {
+                return Err(S16Error::FrameTooBig {
+                    frame,
+                    width: width as u16,
+                    height: height as u16,
+                });
+            }
+
+            let rest = bytes_at(input, entry, height.saturating_sub(1) * 4)?;
+            entry += rest.len();
+            let lines: Vec<usize> = std::iter::once(first_line)
+                .chain(
+                    rest.chunks_exact(4)
+                        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+                )
+                .take(height)
+                .map(|offset| offset as usize)
+                .collect();
+
+            // Every line ends with a tag, so a file too short for them can't
+            // hold the frame, however big its header says it is.
+            for &offset in &lines {
+                bytes_at(input, offset, 2)?;
+            }
+
+            let mut pixels = Vec::new();
+
+            for (line, mut offset) in lines.into_iter().enumerate() {
+                let end = (line + 1) * width;
+
+                loop {
+                    let tag = u16_at(input, offset)?;
+                    offset += 2;
+
+                    if tag == 0 {
+                        break;
+                    }
+
+                    let length = (tag >> 1) as usize;
+                    if pixels.len() + length > end {
+                        return Err(S16Error::RunTooLong { frame, line });
+                    }
+
+                    if tag & C16_COLOUR_RUN != 0 {
+                        pixels.extend(
+                            bytes_at(input, offset, length * 2)?
+                                .chunks_exact(2)
+                                .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]])),
+                        );
+                        offset += length * 2;
+                    } else {
+                        pixels.resize(pixels.len() + length, 0);
+                    }
+                }
+
+                // Whatever the line's runs leave off is transparent.
+                pixels.resize(end, 0);
+            }
+
+            frames.push(S16Frame {
+                width: width as u16,
+                height: height as u16,
+                pixels,
+            });
+        }
+
+        Ok(Self { format, frames })
+    }
+
+    /// The file's bytes as C16, runs of black stored as transparent.
+    pub fn encode_c16(&self) -> Result<Vec<u8>, S16Error> {
+        let count = u16::try_from(self.frames.len())
+            .map_err(|_| S16Error::TooManyFrames(self.frames.len()))?;
+
+        let table_size: usize = HEADER_SIZE
+            + self
+                .frames
+                .iter()
+                .map(|frame| ENTRY_SIZE + (frame.height as usize).saturating_sub(1) * 4)
+                .sum::<usize>();
+
+        let mut table = Vec::with_capacity(table_size);
+        table.extend((C16_COMPRESSED | self.format as u32).to_le_bytes());
+        table.extend(count.to_le_bytes());
+
+        let mut lines = Vec::new();
+
+        for frame in &self.frames {
+            let width = frame.width as usize;
+            if frame.pixels.len() != width * frame.height as usize {
+                return Err(S16Error::WrongSize {
+                    expected: width * frame.height as usize * 2,
+                    found: frame.pixels.len() * 2,
+                });
+            }
+
+            let mut offsets = Vec::with_capacity(frame.height as usize);
+
+            for row in frame.pixels.chunks(width.max(1)) {
+                offsets.push((table_size + lines.len()) as u32);
+
+                for run in row.chunk_by(|a, b| (*a == 0) == (*b == 0)) {
+                    // The tag has 15 bits for the length.
+                    for run in run.chunks(u16::MAX as usize >> 1) {
+                        let colour = run[0] != 0;
+                        let tag = ((run.len() as u16) << 1) | (colour as u16 * C16_COLOUR_RUN);
+                        lines.extend(tag.to_le_bytes());
+
+                        if colour {
+                            lines.extend(run.iter().flat_map(|pixel| pixel.to_le_bytes()));
+                        }
+                    }
+                }
+
+                lines.extend(0u16.to_le_bytes());
+            }
+
+            // Frames with no width still have their lines, all the same
+            // empty one.
+            if offsets.len() < frame.height as usize {
+                offsets.resize(frame.height as usize, (table_size + lines.len()) as u32);
+                lines.extend(0u16.to_le_bytes());
+            }
+            lines.extend(0u16.to_le_bytes());
+
+            let (first, rest) = offsets.split_first().unwrap_or((&0, &[]));
+            table.extend(first.to_le_bytes());
+            table.extend(frame.width.to_le_bytes());
+            table.extend(frame.height.to_le_bytes());
+            for offset in rest {
+                table.extend(offset.to_le_bytes());
+            }
+        }
+
+        table.extend(lines);
+        Ok(table)
+    }
+}
+
+#[cfg(test)]
+fn random_file(rng: &mut impl rand::Rng, format: S16Format) -> S16File {
+    let frames = (0..rng.gen_range(0..6))
+        .map(|_| {
+            let (width, height) = (rng.gen_range(0..20), rng.gen_range(0..20));
+            S16Frame {
+                width,
+                height,
+                pixels: (0..width as usize * height as usize)
+                    .map(|_| rng.gen())
+                    .collect(),
+            }
+        })
+        .collect();
+
+    S16File { format, frames }
+}
+
+#[test]
+fn test_s16_round_trip() {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    let mut rng = StdRng::seed_from_u64(0);
+
+    for _ in 0..200 {
+        for format in [S16Format::Rgb555, S16Format::Rgb565] {
+            let mut file = random_file(&mut rng, format);
+            if format == S16Format::Rgb555 {
+                // The top bit isn't part of a 555 pixel.
+                for frame in &mut file.frames {
+                    frame.pixels.iter_mut().for_each(|pixel| *pixel &= 0x7fff);
+                }
+            }
+
+            let bytes = file.encode().unwrap();
+            assert_eq!(S16File::decode(&bytes).unwrap(), file);
+
+            // And through RGBA, as the command-line tool does with PNGs.
+            for frame in &file.frames {
+                let rgba = frame.to_rgba8(format);
+                assert_eq!(
+                    &S16Frame::from_rgba8(format, frame.width, frame.height, &rgba).unwrap(),
+                    frame
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_c16_round_trip() {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    let mut rng = StdRng::seed_from_u64(1);
+
+    for _ in 0..200 {
+        for format in [S16Format::Rgb555, S16Format::Rgb565] {
+            let mut file = random_file(&mut rng, format);
+            // Sprites have long runs of transparency.
+            for frame in &mut file.frames {
+                for pixel in frame.pixels.iter_mut() {
+                    if rng.gen_bool(0.5) {
+                        *pixel = 0;
+                    }
+                }
+            }
+
+            let bytes = file.encode_c16().unwrap();
+            assert_eq!(S16File::decode_c16(&bytes).unwrap(), file);
+        }
+    }
+}
+
+#[test]
+fn test_c16_layout() {
+    let file = S16File {
+        format: S16Format::Rgb565,
+        frames: vec![S16Frame {
+            width: 3,
+            height: 2,
+            pixels: vec![0, 0, 0xf800, 0x001f, 0, 0],
+        }],
+    };
+
+    let bytes = file.encode_c16().unwrap();
+    assert_eq!(
+        bytes,
+        [
+            3, 0, 0, 0, 1, 0, // compressed Rgb565, one frame
+            18, 0, 0, 0, 3, 0, 2, 0, // first line at 18, 3x2
+            26, 0, 0, 0, // second line at 26
+            4, 0, 3, 0, 0x00, 0xf8, 0, 0, // two clear, one red, end of line
+            3, 0, 0x1f, 0x00, 4, 0, 0, 0, // one blue, two clear, end of line
+            0, 0, // end of frame
+        ]
+    );
+
+    assert_eq!(
+        S16File::decode_c16(&file.encode().unwrap()),
+        Err(S16Error::UnknownFormat(1)),
+        "an S16 file isn't a C16 file"
+    );
+
+    let mut overrun = bytes.clone();
+    overrun[18] = 8;
+    assert_eq!(
+        S16File::decode_c16(&overrun),
+        Err(S16Error::RunTooLong { frame: 0, line: 0 })
+    );
+}
+
+#[test]
+fn test_c16_errors() {
+    // A 1x65535 frame whose line table isn't there.
+    assert_eq!(
+        S16File::decode_c16(&[2, 0, 0, 0, 1, 0, 14, 0, 0, 0, 1, 0, 0xff, 0xff, 0, 0]),
+        Err(S16Error::UnexpectedEof {
+            offset: 14,
+            needed: 65534 * 4
+        })
+    );
+
+    // A 65535x65535 frame from a file well short of the line table it
+    // would need, let alone the gigabytes it would decode to.
+    assert_eq!(
+        S16File::decode_c16(&[2, 0, 0, 0, 1, 0, 14, 0, 0, 0, 0xff, 0xff, 0xff, 0xff, 0, 0]),
+        Err(S16Error::FrameTooBig {
+            frame: 0,
+            width: u16::MAX,
+            height: u16::MAX
+        })
+    );
+
+    // Every line of a 65535x1024 frame can share the same empty line, and
+    // the frame's still too big.
+    let height = 1024;
+    let table = HEADER_SIZE + ENTRY_SIZE + (height - 1) * 4;
+    let mut huge = vec![2, 0, 0, 0, 1, 0];
+    huge.extend((table as u32).to_le_bytes());
+    huge.extend(u16::MAX.to_le_bytes());
+    huge.extend((height as u16).to_le_bytes());
+    for _ in 1..height {
+        huge.extend((table as u32).to_le_bytes());
+    }
+    huge.extend([0, 0, 0, 0]);
+    assert_eq!(
+        S16File::decode_c16(&huge),
+        Err(S16Error::FrameTooBig {
+            frame: 0,
+            width: u16::MAX,
+            height: height as u16
+        })
+    );
+
+    // A 65535x2 frame whose second line is past the end of the file.
+    assert_eq!(
+        S16File::decode_c16(&[
+            2, 0, 0, 0, 1, 0, // compressed Rgb555, one frame
+            18, 0, 0, 0, 0xff, 0xff, 2, 0, // first line at 18, 65535x2
+            0xff, 0xff, 0, 0, // second line at 65535
+            0, 0, 0, 0, // end of line, end of frame
+        ]),
+        Err(S16Error::UnexpectedEof {
+            offset: 65535,
+            needed: 2
+        })
+    );
+}
+
+#[test]
+fn test_s16_layout() {
+    let file = S16File {
+        format: S16Format::Rgb565,
+        frames: vec![
+            S16Frame {
+                width: 2,
+                height: 1,
+                pixels: vec![0xf800, 0x001f],
+            },
+            S16Frame {
+                width: 1,
+                height: 1,
+                pixels: vec![0x07e0],
+            },
+        ],
+    };
+
+    let bytes = file.encode().unwrap();
+    assert_eq!(
+        bytes,
+        [
+            1, 0, 0, 0, 2, 0, // Rgb565, two frames
+            22, 0, 0, 0, 2, 0, 1, 0, // first frame at 22, 2x1
+            26, 0, 0, 0, 1, 0, 1, 0, // second at 26, 1x1
+            0x00, 0xf8, 0x1f, 0x00, 0xe0, 0x07,
+        ]
+    );
+
+    assert_eq!(file.frames[0].to_rgba8(file.format)[..4], [255, 0, 0, 255]);
+    assert_eq!(
+        S16Frame::from_rgba8(S16Format::Rgb565, 1, 1, &[5, 2, 7, 255])
+            .unwrap()
+            .pixels,
+        [0],
+        "too dark to tell from black"
+    );
+}
+
+#[test]
+fn test_s16_errors() {
+    assert_eq!(
+        S16File::decode(&[2, 0, 0, 0, 0, 0]),
+        Err(S16Error::UnknownFormat(2))
+    );
+    assert_eq!(
+        S16File::decode(&[0, 0, 0, 0, 1, 0, 14, 0, 0, 0, 2, 0, 2, 0]),
+        Err(S16Error::UnexpectedEof {
+            offset: 14,
+            needed: 8
+        })
+    );
+    assert_eq!(
+        S16File::decode(&[0, 0, 0, 0, 1, 0, 0xff, 0xff, 0xff, 0xff, 1, 0, 1, 0]),
+        Err(S16Error::UnexpectedEof {
+            offset: u32::MAX as usize,
+            needed: 2
+        }),
+        "a frame offset past the end of the file"
+    );
+    assert!(S16Frame::from_rgba8(S16Format::Rgb555, 2, 2, &[0; 12]).is_err());
+}
+
+#[test]
+fn test_unpack() {
+    for format in [S16Format::Rgb555, S16Format::Rgb565] {
+        assert_eq!(format.unpack(0xffff), [255; 3]);
+        assert_eq!(format.unpack(0), [0; 3]);
+        for pixel in 0..=u16::MAX {
+            let pixel = format.pack(format.unpack(pixel));
+            assert_eq!(format.pack(format.unpack(pixel)), pixel);
+            assert_eq!(format.pack(format.unpack_shifted(pixel)), pixel);
+        }
+    }
+
+    assert_eq!(S16Format::Rgb565.unpack(0x0020), [0, 4, 0]);
+    assert_eq!(S16Format::Rgb565.unpack_shifted(0xffff), [248, 252, 248]);
+}
+
+#[test]
+fn test_to_rgb565() {
+    let frame = S16Frame {
+        width: 4,
+        height: 1,
+        pixels: vec![0, 0x7c00, 0x03e0, 0x7fff],
+    };
+
+    assert_eq!(
+        frame.to_rgb565(S16Format::Rgb555, Some(0)),
+        [0, 0, 0x00, 0xf8, 0xc0, 0x07, 0xdf, 0xff]
+    );
+    assert_eq!(
+        frame.to_rgb565(S16Format::Rgb565, Some(0x7fff)),
+        [0, 0, 0x00, 0x7c, 0xe0, 0x03, 0, 0],
+        "565 pixels are kept, but for the colour key"
+    );
+}
+
+#[test]
+fn test_rgba8_options() {
+    let frame = S16Frame {
+        width: 3,
+        height: 1,
+        pixels: vec![0, 0xf81f, 0x07e0],
+    };
+    let format = S16Format::Rgb565;
+
+    assert_eq!(
+        frame.to_rgba8(format),
+        [0, 0, 0, 0, 255, 0, 255, 255, 0, 255, 0, 255]
+    );
+
+    // Magenta is transparent, and keeps its colour unless premultiplied.
+    let keyed = Rgba8Options {
+        colour_key: Some(0xf81f),
+        ..Default::default()
+    };
+    assert_eq!(
+        frame.to_rgba8_with(format, keyed)[..8],
+        [0, 0, 0, 255, 255, 0, 255, 0]
+    );
+    let premultiplied = Rgba8Options {
+        premultiplied: true,
+        ..keyed
+    };
+    assert_eq!(
+        frame.to_rgba8_with(format, premultiplied)[4..8],
+        [0, 0, 0, 0]
+    );
+
+    let opaque = Rgba8Options {
+        colour_key: None,
+        expand: false,
+        premultiplied: false,
+    };
+    assert_eq!(
+        frame.to_rgba8_with(format, opaque),
+        [0, 0, 0, 255, 248, 0, 248, 255, 0, 252, 0, 255]
+    );
+}
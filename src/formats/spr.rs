@@ -0,0 +1,374 @@
+//! Creatures 1 sprites.
+//!
+//! An SPR file is a frame count, a table of each frame's offset and size,
+//! then each frame's pixels, a byte apiece, one row after another. Each
+//! byte picks a colour from a 256 colour palette, usually `palette.dta`,
+//! which gives red, green and blue for each colour, 6 bits apiece in a byte.
+//! Colour 0 is transparent.
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext, LoadDirectError, RenderAssetUsages},
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+use super::s16::S16ImageInfo;
+
+/// Colours in a palette.
+const PALETTE_COLOURS: usize = 256;
+/// Bytes before the frame table.
+const HEADER_SIZE: usize = 2;
+/// Bytes of each frame's entry in the frame table.
+const ENTRY_SIZE: usize = 8;
+
+/// The 256 colours an SPR file's pixels pick from, as 8 bit red, green and
+/// blue.
+#[derive(Debug, Asset, TypePath, Clone, PartialEq, Eq)]
+pub struct Palette {
+    pub colours: [[u8; 3]; PALETTE_COLOURS],
+}
+
+impl Palette {
+    /// Reads a `palette.dta`, widening its 6 bit channels so that the
+    /// brightest is 255.
+    pub fn decode(input: &[u8]) -> Result<Self, SprError> {
+        if input.len() != PALETTE_COLOURS * 3 {
+            return Err(SprError::WrongPaletteSize(input.len()));
+        }
+
+        let mut colours = [[0; 3]; PALETTE_COLOURS];
+        for (colour, bytes) in colours.iter_mut().zip(input.chunks_exact(3)) {
+            for (channel, &value) in colour.iter_mut().zip(bytes) {
+                let value = value & 0x3f;
+                *channel = (value << 2) | (value >> 4);
+            }
+        }
+
+        Ok(Self { colours })
+    }
+}
+
+/// A frame of a sprite, as the palette indices stored in the file.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SprFrame {
+    pub width: u16,
+    pub height: u16,
+    pub pixels: Vec<u8>,
+}
+
+impl SprFrame {
+    /// The frame as 8 bit RGBA, with colour 0 transparent and everything
+    /// else opaque.
+    pub fn to_rgba8(&self, palette: &Palette) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|&index| {
+                let [red, green, blue] = palette.colours[index as usize];
+                let alpha = if index == 0 { 0 } else { u8::MAX };
+                [red, green, blue, alpha]
+            })
+            .collect()
+    }
+}
+
+/// A whole SPR file.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SprFile {
+    pub frames: Vec<SprFrame>,
+}
+
+impl SprFile {
+    pub fn decode(input: &[u8]) -> Result<Self, SprError> {
+        let count = u16_at(input, 0)? as usize;
+
+        let frames = (0..count)
+            .map(|frame| {
+                let entry = HEADER_SIZE + frame * ENTRY_SIZE;
+                let offset = u32_at(input, entry)? as usize;
+                let width = u16_at(input, entry + 4)?;
+                let height = u16_at(input, entry + 6)?;
+
+                let size = width as usize * height as usize;
+                let pixels = bytes_at(input, offset, size)?.to_vec();
+
+                Ok(SprFrame {
+                    width,
+                    height,
+                    pixels,
+                })
+            })
+            .collect::<Result<_, SprError>>()?;
+
+        Ok(Self { frames })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SprError {
+    /// The file ended before `needed` bytes at `offset`.
+    UnexpectedEof { offset: usize, needed: usize },
+    /// A palette isn't 256 colours of 3 bytes, in bytes.
+    WrongPaletteSize(usize),
+}
+
+impl std::error::Error for SprError {}
+
+impl Display for SprError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SprError::UnexpectedEof { offset, needed } => {
+                write!(f, "file ends before {} bytes at {}", needed, offset)
+            }
+            SprError::WrongPaletteSize(size) => {
+                write!(
+                    f,
+                    "a palette is {} bytes, not {}",
+                    PALETTE_COLOURS * 3,
+                    size
+                )
+            }
+        }
+    }
+}
+
+fn bytes_at(input: &[u8], offset: usize, needed: usize) -> Result<&[u8], SprError> {
+    offset
+        .checked_add(needed)
+        .and_then(|end| input.get(offset..end))
+        .ok_or(SprError::UnexpectedEof { offset, needed })
+}
+
+fn u16_at(input: &[u8], offset: usize) -> Result<u16, SprError> {
+    let bytes = bytes_at(input, offset, 2)?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn u32_at(input: &[u8], offset: usize) -> Result<u32, SprError> {
+    let bytes = bytes_at(input, offset, 4)?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// The frames of an SPR file, laid out as [`super::s16::S16Image`] lays out
+/// S16 frames, labeled with their index, e.g. `Norn.spr#3`.
+#[derive(Debug, Asset, TypePath, Default)]
+pub struct SprImage {
+    pub name: String,
+    pub images: Vec<S16ImageInfo>,
+    pub image_count: u16,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SprLoaderSettings {
+    /// Asset path of the palette the sprite's colours are from.
+    pub palette: String,
+}
+
+impl Default for SprLoaderSettings {
+    fn default() -> Self {
+        Self {
+            palette: "palette.dta".to_string(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct SprAssetLoader;
+
+#[derive(Default)]
+pub struct PaletteAssetLoader;
+
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum SprAssetLoaderError {
+    Io(std::io::Error),
+    Decode(SprError),
+    Palette(LoadDirectError),
+}
+
+impl std::error::Error for SprAssetLoaderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SprAssetLoaderError::Io(e) => Some(e),
+            SprAssetLoaderError::Decode(e) => Some(e),
+            SprAssetLoaderError::Palette(e) => Some(e),
+        }
+    }
+}
+
+impl Display for SprAssetLoaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl SprImage {
+    /// The frames of an SPR file in the colours of `palette`, each added as
+    /// an image labeled with its index.
+    pub fn from_file(
+        file: SprFile,
+        palette: &Palette,
+        load_context: &mut LoadContext<'_>,
+    ) -> SprImage {
+        let name = load_context
+            .path()
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
+
+        let images = file
+            .frames
+            .iter()
+            .enumerate()
+            .map(|(index, frame)| {
+                let image = Image::new(
+                    Extent3d {
+                        width: frame.width as u32,
+                        height: frame.height as u32,
+                        ..Default::default()
+                    },
+                    TextureDimension::D2,
+                    frame.to_rgba8(palette),
+                    TextureFormat::Rgba8UnormSrgb,
+                    RenderAssetUsages::default(),
+                );
+
+                S16ImageInfo {
+                    width: frame.width,
+                    height: frame.height,
+                    image: load_context.add_labeled_asset(format!("{}", index), image),
+                }
+            })
+            .collect();
+
+        SprImage {
+            name,
+            images,
+            image_count: file.frames.len() as u16,
+        }
+    }
+}
+
+impl AssetLoader for SprAssetLoader {
+    type Asset = SprImage;
+    type Settings = SprLoaderSettings;
+    type Error = SprAssetLoaderError;
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        settings: &SprLoaderSettings,
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(SprAssetLoaderError::Io)?;
+
+        let file = SprFile::decode(&bytes).map_err(SprAssetLoaderError::Decode)?;
+
+        // The palette is a dependency of the sprite, so changing it reloads
+        // the sprite in its new colours.
+        let palette = load_context
+            .loader()
+            .immediate()
+            .load::<Palette>(&settings.palette)
+            .await
+            .map_err(SprAssetLoaderError::Palette)?;
+
+        Ok(SprImage::from_file(file, palette.get(), load_context))
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["spr", "SPR"]
+    }
+}
+
+impl AssetLoader for PaletteAssetLoader {
+    type Asset = Palette;
+    type Settings = ();
+    type Error = SprAssetLoaderError;
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(SprAssetLoaderError::Io)?;
+
+        Palette::decode(&bytes).map_err(SprAssetLoaderError::Decode)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["dta", "DTA"]
+    }
+}
+
+#[cfg(test)]
+fn grey_palette() -> Palette {
+    let bytes: Vec<u8> = (0..PALETTE_COLOURS)
+        .flat_map(|colour| [(colour / 4) as u8; 3])
+        .collect();
+    Palette::decode(&bytes).unwrap()
+}
+
+#[test]
+fn test_palette() {
+    let palette = grey_palette();
+    assert_eq!(palette.colours[0], [0, 0, 0]);
+    assert_eq!(palette.colours[4], [4, 4, 4]);
+    // The brightest 6 bit value widens to the brightest 8 bit one.
+    assert_eq!(palette.colours[255], [255, 255, 255]);
+
+    assert_eq!(
+        Palette::decode(&[0; 767]),
+        Err(SprError::WrongPaletteSize(767))
+    );
+}
+
+#[test]
+fn test_spr_layout() {
+    let bytes = [
+        2, 0, // two frames
+        18, 0, 0, 0, 2, 0, 2, 0, // at 18, 2x2
+        22, 0, 0, 0, 1, 0, 1, 0, // at 22, 1x1
+        0, 4, 8, 255, // first frame
+        16,  // second frame
+    ];
+
+    let file = SprFile::decode(&bytes).unwrap();
+    assert_eq!(
+        file.frames,
+        [
+            SprFrame {
+                width: 2,
+                height: 2,
+                pixels: vec![0, 4, 8, 255],
+            },
+            SprFrame {
+                width: 1,
+                height: 1,
+                pixels: vec![16],
+            },
+        ]
+    );
+
+    assert_eq!(
+        file.frames[0].to_rgba8(&grey_palette()),
+        [0, 0, 0, 0, 4, 4, 4, 255, 8, 8, 8, 255, 255, 255, 255, 255]
+    );
+
+    assert_eq!(
+        SprFile::decode(&bytes[..22]),
+        Err(SprError::UnexpectedEof {
+            offset: 22,
+            needed: 1
+        })
+    );
+}
@@ -0,0 +1,216 @@
+//! The size and shape of the world being played, read from its world file
+//! rather than assumed to be the standard Creatures 2 map.
+
+use bevy::prelude::*;
+
+use crate::{
+    constants::{TILE_COLUMNS, TILE_ROWS, TILE_SIZE},
+    formats::{
+        sfc::{self, CGallery, MapData},
+        WorldHandle,
+    },
+    wrap::WrappedSpace,
+};
+
+pub struct GameGeometryPlugin;
+
+impl Plugin for GameGeometryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WorldGeometry>();
+        app.register_type::<WorldGeometry>();
+        app.add_systems(Update, load_geometry);
+    }
+}
+
+/// How big the world is, in background tiles, and whether it wraps around.
+#[derive(Resource, Reflect, Clone, Copy, Debug, PartialEq)]
+pub struct WorldGeometry {
+    pub tile_size: Vec2,
+    pub columns: u32,
+    pub rows: u32,
+    pub wraps: bool,
+}
+
+impl Default for WorldGeometry {
+    /// The standard Creatures 2 map.
+    fn default() -> Self {
+        Self {
+            tile_size: TILE_SIZE,
+            columns: TILE_COLUMNS,
+            rows: TILE_ROWS,
+            wraps: true,
+        }
+    }
+}
+
+impl WorldGeometry {
+    /// From the map's tile gallery and its `map_is_wrappable` flag.
+    pub fn from_map(map: &MapData) -> Self {
+        Self::from_gallery(map.tile_gallery(), map.flags.map_is_wrappable != 0)
+    }
+
+    /// From a gallery of background tiles, stored a column at a time. The
+    /// gallery only says how big a tile is and how many there are, not where
+    /// one column ends, and the engine always draws columns `TILE_ROWS`
+    /// tiles tall, so only the number of columns varies between worlds. A
+    /// gallery without tiles is given the standard map's size.
+    pub fn from_gallery(gallery: &CGallery, wraps: bool) -> Self {
+        let default = Self::default();
+        let images = gallery.images();
+
+        let (tile_size, columns) = match images.first() {
+            Some(image) if image.size().cmpgt(UVec2::ZERO).all() => (
+                image.size().as_vec2(),
+                (images.len() as u32).div_ceil(TILE_ROWS),
+            ),
+            _ => (default.tile_size, default.columns),
+        };
+
+        Self {
+            tile_size,
+            columns,
+            rows: TILE_ROWS,
+            wraps,
+        }
+    }
+
+    pub fn size(&self) -> Vec2 {
+        Vec2::new(self.columns as f32, self.rows as f32) * self.tile_size
+    }
+
+    pub fn width(&self) -> f32 {
+        self.size().x
+    }
+
+    pub fn height(&self) -> f32 {
+        self.size().y
+    }
+
+    pub fn space(&self) -> WrappedSpace {
+        WrappedSpace::new(self.width(), self.wraps)
+    }
+
+    /// The background column drawn at `column`, which may be off either edge
+    /// of a wrapping world. `None` past the edges of one that doesn't wrap.
+    pub fn tile_column(&self, column: i32) -> Option<u32> {
+        let column = if self.wraps {
+            column.rem_euclid(self.columns as i32)
+        } else {
+            column
+        };
+
+        (0..self.columns as i32)
+            .contains(&column)
+            .then_some(column as u32)
+    }
+
+    /// Index of a tile in the background gallery.
+    pub fn tile_index(&self, column: u32, row: u32) -> u32 {
+        column * self.rows + row
+    }
+}
+
+/// Reads the geometry from the world file whenever the world is loaded.
+/// Runs before anything is spawned from the world, so they see the new
+/// geometry.
+pub fn load_geometry(
+    mut events: EventReader<AssetEvent<sfc::World>>,
+    world_handle: Res<WorldHandle>,
+    worlds: Res<Assets<sfc::World>>,
+    mut geometry: ResMut<WorldGeometry>,
+) {
+    let reloaded = events.read().any(|event| match event {
+        AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } => {
+            *id == world_handle.0.id()
+        }
+        _ => false,
+    });
+
+    if !reloaded {
+        return;
+    }
+
+    let Some(world) = worlds.get(&world_handle.0) else {
+        return;
+    };
+
+    geometry.set_if_neq(WorldGeometry::from_map(&world.doc.map));
+    info!(
+        "the world is {} by {} tiles, {}",
+        geometry.columns,
+        geometry.rows,
+        if geometry.wraps {
+            "wrapping around"
+        } else {
+            "not wrapping"
+        }
+    );
+}
+
+#[test]
+fn test_world_geometry_tiles() {
+    let geometry = WorldGeometry::default();
+    assert_eq!(geometry.size(), Vec2::new(8352.0, 2400.0));
+    assert_eq!(geometry.space(), WrappedSpace::WORLD);
+    assert_eq!(geometry.tile_column(-1), Some(57));
+    assert_eq!(geometry.tile_column(58), Some(0));
+    assert_eq!(geometry.tile_index(2, 3), 35);
+
+    let flat = WorldGeometry {
+        columns: 10,
+        wraps: false,
+        ..Default::default()
+    };
+    assert_eq!(flat.tile_column(9), Some(9));
+    assert_eq!(flat.tile_column(-1), None);
+    assert_eq!(flat.tile_column(10), None);
+    assert!(!flat.space().wraps());
+    assert_eq!(flat.space().width(), 1440.0);
+}
+
+#[test]
+fn test_world_geometry_from_map() {
+    use std::sync::{Arc, Mutex};
+
+    use crate::formats::sfc::{ArchiveTable, Doc};
+
+    let buf = include_bytes!("../assets/fixtures/synthetic.sfc");
+    let mut registry = Arc::new(Mutex::new(ArchiveTable::empty()));
+    let (_, doc) = Doc::parse(buf, &mut registry).expect("synthetic.sfc should parse");
+
+    // The synthetic world is the standard map's 58 columns of 16 tiles, and wraps.
+    let geometry = WorldGeometry::from_map(&doc.map);
+    assert_eq!(geometry, WorldGeometry::default());
+}
+
+#[test]
+fn test_world_geometry_from_gallery() {
+    use crate::formats::sfc::CImage;
+
+    let gallery = |count: usize, size: UVec2| {
+        CGallery::new("back", (0..count).map(|_| CImage::new(size)).collect())
+    };
+
+    // Ten columns of 100 by 120 tiles, the last one short.
+    let geometry = WorldGeometry::from_gallery(&gallery(155, UVec2::new(100, 120)), false);
+    assert_eq!(
+        geometry,
+        WorldGeometry {
+            tile_size: Vec2::new(100.0, 120.0),
+            columns: 10,
+            rows: TILE_ROWS,
+            wraps: false,
+        }
+    );
+    assert_eq!(geometry.size(), Vec2::new(1000.0, 1920.0));
+
+    let geometry = WorldGeometry::from_gallery(&gallery(16, UVec2::new(144, 300)), true);
+    assert_eq!(geometry.columns, 1);
+    assert_eq!(geometry.size(), Vec2::new(144.0, 4800.0));
+
+    // Without usable tiles, the standard map's tiles.
+    for gallery in [gallery(0, UVec2::ZERO), gallery(32, UVec2::new(0, 150))] {
+        let geometry = WorldGeometry::from_gallery(&gallery, true);
+        assert_eq!(geometry, WorldGeometry::default());
+    }
+}
@@ -0,0 +1,175 @@
+//! Running the world without a window or renderer, for tests and soak runs
+//! on machines without a GPU.
+
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use bevy::{
+    app::ScheduleRunnerPlugin, prelude::*, state::app::StatesPlugin, time::TimeUpdateStrategy,
+};
+
+use crate::{components::save::SaveWorld, state::GameState, time::TICKS_PER_SECOND};
+
+/// Ticks run with `--headless` when `--ticks` isn't given, a minute of game
+/// time.
+const DEFAULT_TICKS: u32 = 600;
+
+/// Stands in for `DefaultPlugins` under the simulation plugins. Each update
+/// advances the world by exactly one tick, as fast as the machine allows,
+/// and the app exits once `ticks` have run, saving the world to `save` first
+/// if it's given.
+pub struct HeadlessPlugin {
+    pub ticks: u32,
+    pub save: Option<PathBuf>,
+}
+
+#[derive(Resource, Debug)]
+pub struct HeadlessRun {
+    pub ticks: u32,
+    pub elapsed: u32,
+    pub save: Option<PathBuf>,
+    /// Wall clock start of the run, as `Time<Real>` follows the manual
+    /// updates.
+    pub started: Instant,
+}
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::ZERO)),
+            AssetPlugin::default(),
+            StatesPlugin,
+            HierarchyPlugin,
+            TransformPlugin,
+        ));
+
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+            1.0 / TICKS_PER_SECOND,
+        )));
+        app.insert_resource(HeadlessRun {
+            ticks: self.ticks,
+            elapsed: 0,
+            save: self.save.clone(),
+            started: Instant::now(),
+        });
+        app.add_systems(
+            FixedUpdate,
+            count_ticks.run_if(in_state(GameState::Running)),
+        );
+    }
+}
+
+/// The number of ticks to run headless for, if `--headless` was given.
+pub fn headless_ticks() -> Option<u32> {
+    if !std::env::args().any(|arg| arg == "--headless") {
+        return None;
+    }
+
+    let ticks = std::env::args()
+        .skip_while(|arg| arg != "--ticks")
+        .nth(1)
+        .and_then(|ticks| ticks.parse().ok())
+        .unwrap_or(DEFAULT_TICKS);
+
+    Some(ticks)
+}
+
+/// Where to save the world at the end of a headless run, given with
+/// `--save <path>`.
+pub fn headless_save() -> Option<PathBuf> {
+    std::env::args()
+        .skip_while(|arg| arg != "--save")
+        .nth(1)
+        .map(PathBuf::from)
+}
+
+fn count_ticks(
+    mut run: ResMut<HeadlessRun>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut saves: EventWriter<SaveWorld>,
+) {
+    run.elapsed += 1;
+
+    if run.elapsed >= run.ticks {
+        info!(
+            "ran {} ticks in {:.2}s",
+            run.elapsed,
+            run.started.elapsed().as_secs_f32()
+        );
+        if let Some(path) = run.save.take() {
+            saves.send(SaveWorld { path });
+        }
+        next_state.set(GameState::Finished);
+    }
+}
+
+#[test]
+fn test_headless_run() {
+    use crate::{
+        components::{
+            object::{Agent, Gallery},
+            physics::Physics,
+            room::Room,
+        },
+        formats::{
+            sfc::{ArchiveTable, Doc},
+            GameFormatsPlugin,
+        },
+        plugins::SimulationPlugins,
+    };
+    use std::sync::{Arc, Mutex};
+
+    let save = std::env::temp_dir().join(format!("headless-test-{}.sfc", std::process::id()));
+
+    let mut app = App::new();
+    app.add_plugins((
+        HeadlessPlugin {
+            ticks: 20,
+            save: Some(save.clone()),
+        },
+        SimulationPlugins.set(GameFormatsPlugin {
+            world: "fixtures/synthetic.sfc".to_string(),
+        }),
+    ));
+
+    let mut updates = 0;
+    while app.should_exit().is_none() {
+        app.update();
+        updates += 1;
+        assert!(updates < 10_000, "headless run should finish");
+        // Give the asset loader threads a chance to read the world.
+        std::thread::sleep(Duration::from_millis(1));
+    }
+
+    let world = app.world_mut();
+
+    assert_eq!(world.resource::<HeadlessRun>().elapsed, 20);
+    assert!(world.query::<&Room>().iter(world).count() > 0);
+    assert!(world.query::<&Agent>().iter(world).count() > 0);
+
+    // Bodies are sized from the world file without any sprites, and objects
+    // without parts, which have nowhere to be, have no body.
+    let mut bodies = world.query::<(&Agent, Option<&Gallery>, Option<&Physics>)>();
+    for (agent, gallery, physics) in bodies.iter(world) {
+        match (gallery, physics) {
+            (Some(_), Some(physics)) => assert_ne!(physics.size, Vec2::ZERO, "{}", agent.id),
+            (None, None) => assert_eq!(agent.class_name, "Object"),
+            _ => panic!(
+                "object {} should have a body exactly when it has parts",
+                agent.id
+            ),
+        }
+    }
+
+    // The world is saved on the way out.
+    let bytes = std::fs::read(&save).expect("the world should be saved");
+    std::fs::remove_file(&save).unwrap();
+    let mut registry = Arc::new(Mutex::new(ArchiveTable::empty()));
+    let (_, saved) = Doc::parse(&bytes, &mut registry).expect("saved world should parse");
+    assert_eq!(
+        saved.map.rooms.rooms.len(),
+        world.query::<&Room>().iter(world).count()
+    );
+}
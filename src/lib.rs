@@ -0,0 +1,13 @@
+pub mod camera;
+pub mod caos;
+pub mod components;
+pub mod constants;
+pub mod display;
+pub mod formats;
+pub mod geometry;
+pub mod headless;
+pub mod plugins;
+pub mod state;
+pub mod time;
+pub mod window;
+pub mod wrap;
@@ -1,36 +1,31 @@
-mod camera;
-mod components;
-mod constants;
-mod display;
-mod formats;
-mod state;
-mod time;
-mod window;
-
-use bevy::{input::common_conditions::input_toggle_active, prelude::*};
+use bevy::{input::common_conditions::input_toggle_active, log::LogPlugin, prelude::*};
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
 
-use camera::GameCameraPlugin;
-use components::GameComponentsPlugin;
-use display::GameDisplayPlugin;
-use formats::GameFormatsPlugin;
-use state::GameStatePlugin;
-use time::GameTimePlugin;
-use window::GameWindowPlugin;
+use cl::{
+    headless::{headless_save, headless_ticks, HeadlessPlugin},
+    plugins::{PresentationPlugins, SimulationPlugins},
+    window::GameWindowPlugin,
+};
 
 fn main() {
-    App::new()
-        .add_plugins((
-            GameTimePlugin,
-            GameDisplayPlugin,
-            GameWindowPlugin,
-            GameStatePlugin,
-            GameComponentsPlugin,
-            GameFormatsPlugin,
-            GameCameraPlugin,
-        ))
-        .add_plugins(
-            WorldInspectorPlugin::default().run_if(input_toggle_active(true, KeyCode::KeyI)),
-        )
-        .run();
+    let mut app = App::new();
+
+    match headless_ticks() {
+        Some(ticks) => {
+            let save = headless_save();
+            app.add_plugins((HeadlessPlugin { ticks, save }, LogPlugin::default()))
+                .add_plugins(SimulationPlugins);
+        }
+        None => {
+            app.add_plugins(GameWindowPlugin)
+                .add_plugins(SimulationPlugins)
+                .add_plugins(PresentationPlugins)
+                .add_plugins(
+                    WorldInspectorPlugin::default()
+                        .run_if(input_toggle_active(true, KeyCode::KeyI)),
+                );
+        }
+    }
+
+    app.run();
 }
@@ -0,0 +1,46 @@
+//! The game is split into the plugins that run the world and those that
+//! show it, so that the world can also run without a window.
+
+use bevy::{app::PluginGroupBuilder, prelude::*};
+
+use crate::{
+    camera::GameCameraPlugin,
+    caos::GameCaosPlugin,
+    components::{GameComponentsPlugin, GameComponentsRenderPlugin},
+    display::GameDisplayPlugin,
+    formats::{GameFormatsPlugin, GameSpritesPlugin},
+    geometry::GameGeometryPlugin,
+    state::GameStatePlugin,
+    time::{GamePausePlugin, GameTimePlugin},
+};
+
+/// Loads the world and runs it: rooms, objects and their scripts. Needs the
+/// asset, state, hierarchy and transform plugins, from either
+/// `DefaultPlugins` or [`crate::headless::HeadlessPlugin`], added first.
+pub struct SimulationPlugins;
+
+impl PluginGroup for SimulationPlugins {
+    fn build(self) -> PluginGroupBuilder {
+        PluginGroupBuilder::start::<Self>()
+            .add(GameStatePlugin)
+            .add(GameTimePlugin)
+            .add(GameFormatsPlugin::default())
+            .add(GameGeometryPlugin)
+            .add(GameComponentsPlugin)
+            .add(GameCaosPlugin)
+    }
+}
+
+/// Draws the world and handles input, on top of [`SimulationPlugins`].
+pub struct PresentationPlugins;
+
+impl PluginGroup for PresentationPlugins {
+    fn build(self) -> PluginGroupBuilder {
+        PluginGroupBuilder::start::<Self>()
+            .add(GameSpritesPlugin)
+            .add(GameDisplayPlugin)
+            .add(GameComponentsRenderPlugin)
+            .add(GameCameraPlugin)
+            .add(GamePausePlugin)
+    }
+}
@@ -4,7 +4,29 @@ use bevy::{
 };
 use std::time::Duration;
 
-use crate::state::GameState;
+use crate::{
+    components::{
+        environment::SourceDeltas,
+        room::{
+            DAYS_IN_SEASON, HEAT_SOURCE_DELTA, LIGHT_SOURCE_DELTA, NUMBER_OF_TIMES_OF_DAY,
+            RADIATION_SOURCE_DELTA, SEASONS_IN_YEAR,
+        },
+    },
+    formats::{
+        sfc::{self, MapDataFlags},
+        WorldHandle,
+    },
+    state::GameState,
+};
+
+/// Creatures 2 runs its world, and object scripts, at ten ticks a second.
+pub const TICKS_PER_SECOND: f64 = 10.0;
+
+/// Ticks each time of day lasts. The world file only records which part of
+/// the day it is, so this is five minutes of game time, 25 to a day.
+pub const TICKS_PER_TIME_OF_DAY: u32 = 3000;
+
+const DAYS_IN_YEAR: u32 = DAYS_IN_SEASON as u32 * SEASONS_IN_YEAR as u32;
 
 pub struct GameTimePlugin;
 
@@ -14,17 +36,223 @@ pub struct DebugGameTime;
 
 impl Plugin for GameTimePlugin {
     fn build(&self, app: &mut App) {
+        app.insert_resource(Time::<Fixed>::from_hz(TICKS_PER_SECOND));
+        app.init_resource::<WorldCalendar>();
+        app.register_type::<WorldCalendar>();
+        app.add_event::<TimeOfDayChanged>();
+        app.add_event::<SeasonChanged>();
         app.add_systems(Startup, setup_time);
         app.add_systems(
             Update,
             (
                 update_time.run_if(on_real_timer(Duration::from_secs(1))),
-                toggle_pause.run_if(input_just_pressed(KeyCode::Space)),
+                load_calendar,
             ),
         );
+        app.add_systems(
+            FixedUpdate,
+            (advance_calendar, update_source_deltas)
+                .chain()
+                .run_if(in_state(GameState::Running)),
+        );
     }
 }
 
+/// Pauses and resumes the world with the space bar.
+pub struct GamePausePlugin;
+
+impl Plugin for GamePausePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            toggle_pause.run_if(input_just_pressed(KeyCode::Space)),
+        );
+    }
+}
+
+#[derive(Reflect, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeOfDay {
+    Dawn,
+    Morning,
+    Afternoon,
+    Evening,
+    Night,
+}
+
+impl TimeOfDay {
+    pub const ALL: [TimeOfDay; NUMBER_OF_TIMES_OF_DAY] = [
+        TimeOfDay::Dawn,
+        TimeOfDay::Morning,
+        TimeOfDay::Afternoon,
+        TimeOfDay::Evening,
+        TimeOfDay::Night,
+    ];
+}
+
+#[derive(Reflect, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Season {
+    Spring,
+    Summer,
+    Autumn,
+    Winter,
+}
+
+impl Season {
+    pub const ALL: [Season; SEASONS_IN_YEAR] = [
+        Season::Spring,
+        Season::Summer,
+        Season::Autumn,
+        Season::Winter,
+    ];
+}
+
+/// Sent when the world moves into the next part of the day.
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeOfDayChanged(pub TimeOfDay);
+
+/// Sent when the world moves into the next season.
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SeasonChanged(pub Season);
+
+/// The world's clock and calendar, loaded from the world file and advanced
+/// once a tick while the world runs.
+#[derive(Resource, Reflect, Clone, Debug, Default, PartialEq, Eq)]
+#[reflect(Resource)]
+pub struct WorldCalendar {
+    /// Ticks into the current time of day.
+    pub ticks: u32,
+    pub time_of_day: u32,
+    pub day_in_year: u32,
+    pub year: u32,
+}
+
+/// What moved on when the calendar ticked.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CalendarChange {
+    pub time_of_day: bool,
+    pub season: bool,
+}
+
+impl WorldCalendar {
+    /// The calendar as the world file left it, with anything out of range
+    /// wrapped back into it.
+    pub fn from_flags(flags: &MapDataFlags) -> Self {
+        Self {
+            ticks: 0,
+            time_of_day: flags.time_of_day % NUMBER_OF_TIMES_OF_DAY as u32,
+            day_in_year: flags.day_in_year % DAYS_IN_YEAR,
+            year: flags.year,
+        }
+    }
+
+    pub fn write_to(&self, flags: &mut MapDataFlags) {
+        flags.time_of_day = self.time_of_day;
+        flags.day_in_year = self.day_in_year;
+        flags.year = self.year;
+    }
+
+    pub fn time_of_day(&self) -> TimeOfDay {
+        TimeOfDay::ALL[self.time_of_day as usize]
+    }
+
+    pub fn season(&self) -> Season {
+        Season::ALL[(self.day_in_year / DAYS_IN_SEASON as u32) as usize]
+    }
+
+    /// Moves the calendar on a tick, rolling over into the next time of day,
+    /// day and year as each fills up.
+    pub fn tick(&mut self) -> CalendarChange {
+        self.ticks += 1;
+
+        if self.ticks < TICKS_PER_TIME_OF_DAY {
+            return CalendarChange::default();
+        }
+
+        let season = self.season();
+        self.ticks = 0;
+        self.time_of_day += 1;
+
+        if self.time_of_day == NUMBER_OF_TIMES_OF_DAY as u32 {
+            self.time_of_day = 0;
+            self.day_in_year += 1;
+
+            if self.day_in_year == DAYS_IN_YEAR {
+                self.day_in_year = 0;
+                self.year += 1;
+            }
+        }
+
+        CalendarChange {
+            time_of_day: true,
+            season: self.season() != season,
+        }
+    }
+
+    /// How far the season and time of day move every room's heat, light and
+    /// radiation.
+    pub fn source_deltas(&self) -> SourceDeltas {
+        let (season, time) = (self.season() as usize, self.time_of_day as usize);
+
+        SourceDeltas {
+            heat: HEAT_SOURCE_DELTA[season][time],
+            light: LIGHT_SOURCE_DELTA[season][time],
+            radiation: RADIATION_SOURCE_DELTA[season][time],
+        }
+    }
+}
+
+/// Resets the calendar to the world file's whenever the world is loaded.
+fn load_calendar(
+    mut events: EventReader<AssetEvent<sfc::World>>,
+    world_handle: Res<WorldHandle>,
+    worlds: Res<Assets<sfc::World>>,
+    mut calendar: ResMut<WorldCalendar>,
+) {
+    let reloaded = events.read().any(|event| match event {
+        AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } => {
+            *id == world_handle.0.id()
+        }
+        _ => false,
+    });
+
+    if !reloaded {
+        return;
+    }
+
+    let Some(world) = worlds.get(&world_handle.0) else {
+        return;
+    };
+
+    *calendar = WorldCalendar::from_flags(&world.doc.map.flags);
+    info!(
+        "it's {:?} in {:?}, year {}",
+        calendar.time_of_day(),
+        calendar.season(),
+        calendar.year
+    );
+}
+
+/// Runs on `FixedUpdate`, so there's a calendar tick for every world tick.
+pub fn advance_calendar(
+    mut calendar: ResMut<WorldCalendar>,
+    mut times_of_day: EventWriter<TimeOfDayChanged>,
+    mut seasons: EventWriter<SeasonChanged>,
+) {
+    let change = calendar.tick();
+
+    if change.time_of_day {
+        times_of_day.send(TimeOfDayChanged(calendar.time_of_day()));
+    }
+
+    if change.season {
+        seasons.send(SeasonChanged(calendar.season()));
+    }
+}
+
+fn update_source_deltas(calendar: Res<WorldCalendar>, mut deltas: ResMut<SourceDeltas>) {
+    deltas.set_if_neq(calendar.source_deltas());
+}
+
 fn setup_time(mut commands: Commands, mut time: ResMut<Time<Virtual>>) {
     time.set_relative_speed(1.0); // startup - set time to normal speed
 
@@ -65,3 +293,74 @@ fn toggle_pause(
         window.title = "CL - Paused".into();
     }
 }
+
+#[test]
+fn test_calendar_tick() {
+    let mut calendar = WorldCalendar::from_flags(&MapDataFlags {
+        time_of_day: 4,
+        day_in_year: DAYS_IN_YEAR - 1,
+        year: 3,
+        ..Default::default()
+    });
+    assert_eq!(calendar.time_of_day(), TimeOfDay::Night);
+    assert_eq!(calendar.season(), Season::Winter);
+
+    for _ in 1..TICKS_PER_TIME_OF_DAY {
+        assert_eq!(calendar.tick(), CalendarChange::default());
+    }
+
+    // The last night of the year rolls over into the next spring.
+    assert_eq!(
+        calendar.tick(),
+        CalendarChange {
+            time_of_day: true,
+            season: true,
+        }
+    );
+    assert_eq!(calendar.time_of_day(), TimeOfDay::Dawn);
+    assert_eq!(calendar.season(), Season::Spring);
+    assert_eq!((calendar.day_in_year, calendar.year), (0, 4));
+    assert_eq!(calendar.source_deltas().heat, HEAT_SOURCE_DELTA[0][0]);
+
+    let mut flags = MapDataFlags::default();
+    calendar.write_to(&mut flags);
+    assert_eq!(
+        (flags.time_of_day, flags.day_in_year, flags.year),
+        (0, 0, 4)
+    );
+}
+
+#[test]
+fn test_calendar_events() {
+    use bevy::ecs::{event::Events, system::RunSystemOnce};
+
+    let mut world = World::new();
+    world.init_resource::<Events<TimeOfDayChanged>>();
+    world.init_resource::<Events<SeasonChanged>>();
+    world.insert_resource(WorldCalendar {
+        ticks: TICKS_PER_TIME_OF_DAY - 1,
+        time_of_day: 1,
+        day_in_year: 5,
+        year: 0,
+    });
+
+    world.run_system_once(advance_calendar).unwrap();
+
+    let times_of_day: Vec<_> = world
+        .resource_mut::<Events<TimeOfDayChanged>>()
+        .drain()
+        .collect();
+    assert_eq!(times_of_day, [TimeOfDayChanged(TimeOfDay::Afternoon)]);
+    assert!(world.resource::<Events<SeasonChanged>>().is_empty());
+
+    world.init_resource::<SourceDeltas>();
+    world.run_system_once(update_source_deltas).unwrap();
+    assert_eq!(
+        *world.resource::<SourceDeltas>(),
+        SourceDeltas {
+            heat: HEAT_SOURCE_DELTA[1][2],
+            light: LIGHT_SOURCE_DELTA[1][2],
+            radiation: RADIATION_SOURCE_DELTA[1][2],
+        }
+    );
+}
@@ -0,0 +1,343 @@
+//! Geometry in a world that may wrap around horizontally, so that walking off
+//! its right edge brings you back in on the left.
+//!
+//! Coordinates aren't required to be wrapped into the world: a point, and
+//! every copy of it a whole world width to either side, are the same place.
+
+use bevy::prelude::*;
+
+use crate::constants::{TILE_COLUMNS, TILE_SIZE};
+
+/// The horizontal extent of a world, and whether it wraps.
+#[derive(Reflect, Clone, Copy, Debug, PartialEq)]
+pub struct WrappedSpace {
+    width: f32,
+    wraps: bool,
+}
+
+impl Default for WrappedSpace {
+    fn default() -> Self {
+        Self::WORLD
+    }
+}
+
+impl WrappedSpace {
+    /// The standard Creatures 2 map. The world being played may differ, see
+    /// [`crate::geometry::WorldGeometry`].
+    pub const WORLD: Self = Self::new(TILE_COLUMNS as f32 * TILE_SIZE.x, true);
+
+    pub const fn new(width: f32, wraps: bool) -> Self {
+        Self { width, wraps }
+    }
+
+    /// From the world file's `map_is_wrappable` flag.
+    pub fn from_flag(width: f32, map_is_wrappable: u32) -> Self {
+        Self::new(width, map_is_wrappable != 0)
+    }
+
+    pub fn width(&self) -> f32 {
+        self.width
+    }
+
+    pub fn wraps(&self) -> bool {
+        self.wraps
+    }
+
+    /// `x` brought into the world, between 0 and its width.
+    pub fn wrap_x(&self, x: f32) -> f32 {
+        if self.wraps {
+            x.rem_euclid(self.width)
+        } else {
+            x
+        }
+    }
+
+    pub fn wrap_point(&self, point: Vec2) -> Vec2 {
+        Vec2::new(self.wrap_x(point.x), point.y)
+    }
+
+    /// The copy of `x` between `min` and `max`, if there is one. Where the
+    /// span is wider than the world the leftmost copy is picked.
+    pub fn x_within(&self, x: f32, min: f32, max: f32) -> Option<f32> {
+        let x = if self.wraps {
+            min + (x - min).rem_euclid(self.width)
+        } else {
+            x
+        };
+
+        (min <= x && x <= max).then_some(x)
+    }
+
+    /// Offset from `from` to `to` the short way around the world.
+    pub fn offset(&self, from: Vec2, to: Vec2) -> Vec2 {
+        let mut offset = to - from;
+
+        if self.wraps {
+            let half = self.width / 2.0;
+            offset.x = (offset.x + half).rem_euclid(self.width) - half;
+        }
+
+        offset
+    }
+
+    pub fn distance(&self, from: Vec2, to: Vec2) -> f32 {
+        self.offset(from, to).length()
+    }
+
+    /// Unit vector from `from` toward `to` the short way around, zero when
+    /// they're the same place.
+    pub fn direction(&self, from: Vec2, to: Vec2) -> Vec2 {
+        self.offset(from, to).normalize_or_zero()
+    }
+
+    /// Whether `point`, or any copy of it, is inside `rect`, edges included.
+    pub fn contains(&self, rect: Rect, point: Vec2) -> bool {
+        rect.min.y <= point.y
+            && point.y <= rect.max.y
+            && self.x_within(point.x, rect.min.x, rect.max.x).is_some()
+    }
+
+    /// The copy of `rect` whose centre is nearest `x`.
+    pub fn shift_near(&self, rect: Rect, x: f32) -> Rect {
+        let center = rect.center();
+        let offset = self.offset(Vec2::new(x, center.y), center);
+        let shift = Vec2::new(x + offset.x - center.x, 0.0);
+
+        Rect::from_corners(rect.min + shift, rect.max + shift)
+    }
+
+    /// The copies of `other` nearest to `rect`, one of which overlaps it if
+    /// any does.
+    fn copies_near(&self, rect: Rect, other: Rect) -> impl Iterator<Item = Rect> {
+        let near = self.shift_near(other, rect.center().x);
+        let shifts: &[f32] = if self.wraps {
+            &[0.0, -1.0, 1.0]
+        } else {
+            &[0.0]
+        };
+        let width = self.width;
+
+        shifts.iter().map(move |copies| {
+            let shift = Vec2::new(copies * width, 0.0);
+            Rect::from_corners(near.min + shift, near.max + shift)
+        })
+    }
+
+    /// Whether two rects overlap anywhere around the world. Rects that only
+    /// touch count as overlapping.
+    pub fn intersects(&self, a: Rect, b: Rect) -> bool {
+        self.intersection(a, b).is_some()
+    }
+
+    /// Where two rects overlap, in the same copy of the world as `a`.
+    pub fn intersection(&self, a: Rect, b: Rect) -> Option<Rect> {
+        self.copies_near(a, b)
+            .find(|b| {
+                a.min.x <= b.max.x && b.min.x <= a.max.x && a.min.y <= b.max.y && b.min.y <= a.max.y
+            })
+            .map(|b| a.intersect(b))
+    }
+
+    /// The smallest rect covering both, in the same copy of the world as
+    /// `a`. In a wrapping world it's never wider than the world.
+    pub fn union(&self, a: Rect, b: Rect) -> Rect {
+        let b = self.shift_near(b, a.center().x);
+        let mut union = a.union(b);
+
+        if self.wraps && union.width() > self.width {
+            union.max.x = union.min.x + self.width;
+        }
+
+        union
+    }
+}
+
+#[cfg(test)]
+const CASES: usize = 2000;
+
+#[cfg(test)]
+fn random_spaces() -> [WrappedSpace; 3] {
+    [
+        WrappedSpace::WORLD,
+        WrappedSpace::new(1000.0, true),
+        WrappedSpace::new(1000.0, false),
+    ]
+}
+
+/// A point with whole coordinates, so that shifting it by world widths is
+/// exact.
+#[cfg(test)]
+fn random_point(rng: &mut impl rand::Rng, space: &WrappedSpace) -> Vec2 {
+    let width = space.width() as i32;
+    Vec2::new(
+        rng.gen_range(-width..2 * width) as f32,
+        rng.gen_range(-3000..3000) as f32,
+    )
+}
+
+#[cfg(test)]
+fn random_rect(rng: &mut impl rand::Rng, space: &WrappedSpace) -> Rect {
+    let corner = random_point(rng, space);
+    let size = Vec2::new(
+        rng.gen_range(0..space.width() as i32 / 2) as f32,
+        rng.gen_range(0..1000) as f32,
+    );
+    Rect::from_corners(corner, corner + size)
+}
+
+#[cfg(test)]
+fn random_shift(rng: &mut impl rand::Rng, space: &WrappedSpace) -> Vec2 {
+    if space.wraps() {
+        Vec2::new(rng.gen_range(-3..=3) as f32 * space.width(), 0.0)
+    } else {
+        Vec2::ZERO
+    }
+}
+
+#[test]
+fn test_wrapped_space_wrap() {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    let mut rng = StdRng::seed_from_u64(0);
+
+    for space in random_spaces() {
+        for _ in 0..CASES {
+            let point = random_point(&mut rng, &space);
+            let wrapped = space.wrap_point(point);
+
+            if space.wraps() {
+                assert!((0.0..space.width()).contains(&wrapped.x));
+            } else {
+                assert_eq!(wrapped, point);
+            }
+
+            let shift = random_shift(&mut rng, &space);
+            assert_eq!(space.wrap_point(point + shift), wrapped);
+        }
+    }
+}
+
+#[test]
+fn test_wrapped_space_offset() {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    let mut rng = StdRng::seed_from_u64(1);
+
+    for space in random_spaces() {
+        for _ in 0..CASES {
+            let (a, b, c) = (
+                random_point(&mut rng, &space),
+                random_point(&mut rng, &space),
+                random_point(&mut rng, &space),
+            );
+            let offset = space.offset(a, b);
+
+            // Following the offset gets you to `b`, the short way round.
+            assert_eq!(space.wrap_point(a + offset), space.wrap_point(b));
+            if space.wraps() {
+                assert!(offset.x.abs() <= space.width() / 2.0);
+            } else {
+                assert_eq!(offset, b - a);
+            }
+
+            // Moving either end by whole worlds changes nothing.
+            let shift = random_shift(&mut rng, &space);
+            assert_eq!(space.offset(a + shift, b), offset);
+            assert_eq!(space.offset(a, b - shift), offset);
+
+            // Distance is a metric.
+            assert_eq!(space.distance(a, a), 0.0);
+            assert_eq!(space.distance(a, b), space.distance(b, a));
+            assert!(space.distance(a, c) <= space.distance(a, b) + space.distance(b, c) + 0.01);
+
+            let direction = space.direction(a, b);
+            if space.wrap_point(a) == space.wrap_point(b) {
+                assert_eq!(direction, Vec2::ZERO);
+            } else {
+                assert!((direction.length() - 1.0).abs() < 1e-4);
+                assert!(direction.dot(offset) > 0.0);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_wrapped_space_rects() {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    let mut rng = StdRng::seed_from_u64(2);
+
+    for space in random_spaces() {
+        for _ in 0..CASES {
+            let (a, b) = (random_rect(&mut rng, &space), random_rect(&mut rng, &space));
+            let point = random_point(&mut rng, &space);
+            let shift = random_shift(&mut rng, &space);
+            let moved = Rect::from_corners(b.min + shift, b.max + shift);
+
+            // Containment doesn't care which copy of the point or rect.
+            assert_eq!(space.contains(a, point), space.contains(a, point + shift));
+            assert_eq!(
+                space.contains(a, point),
+                space.contains(Rect::from_corners(a.min + shift, a.max + shift), point)
+            );
+            assert!(space.contains(a, a.center()));
+
+            // Nor does intersection, which is symmetric and agrees with
+            // containment for the corners.
+            let intersects = space.intersects(a, b);
+            assert_eq!(space.intersects(b, a), intersects);
+            assert_eq!(space.intersects(a, moved), intersects);
+            if space.contains(a, b.min) || space.contains(b, a.min) {
+                assert!(intersects);
+            }
+
+            if let Some(overlap) = space.intersection(a, b) {
+                assert!(a.contains(overlap.min) && a.contains(overlap.max));
+                assert!(space.contains(b, overlap.center()));
+            }
+
+            // The union covers both rects.
+            let union = space.union(a, moved);
+            for corner in [a.min, a.max, b.min, b.max, b.center()] {
+                assert!(
+                    space.contains(union, corner),
+                    "{:?} doesn't cover {} of {:?} and {:?}",
+                    union,
+                    corner,
+                    a,
+                    b
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_wrapped_space_seam() {
+    let space = WrappedSpace::WORLD;
+    let width = space.width();
+
+    // Either side of the seam is a step apart, not a whole world.
+    let left = Vec2::new(5.0, 0.0);
+    let right = Vec2::new(width - 5.0, 0.0);
+    assert_eq!(space.offset(right, left), Vec2::new(10.0, 0.0));
+    assert_eq!(space.direction(left, right), Vec2::NEG_X);
+
+    // A room across the seam contains points on both sides of it.
+    let room = Rect::new(width - 100.0, 0.0, width + 100.0, -100.0);
+    assert!(space.contains(room, Vec2::new(50.0, -50.0)));
+    assert!(space.contains(room, Vec2::new(width - 50.0, -50.0)));
+    assert!(!space.contains(room, Vec2::new(150.0, -50.0)));
+
+    // A view just right of the seam sees it, and draws it next to itself.
+    let view = Rect::new(-50.0, 0.0, 400.0, -300.0);
+    assert!(space.intersects(view, room));
+    assert_eq!(
+        space.shift_near(room, view.center().x),
+        Rect::new(-100.0, 0.0, 100.0, -100.0)
+    );
+
+    let flat = WrappedSpace::new(width, false);
+    assert!(!flat.contains(room, Vec2::new(50.0, -50.0)));
+    assert!(!flat.intersects(view, room));
+}